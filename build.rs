@@ -1,4 +1,172 @@
+use std::io::Write;
+
+/// Version assumed without the wireshark feature, only the mock backend is built then so nothing depends on the ABI.
+/// The hand written structs were made for this release.
+const FALLBACK_VERSION: (u32, u32, u32) = (3, 4, 0);
+
+/// Releases that changed the ABI, for each the `ws_<major>_<minor>` cfg is enabled if wireshark is at least that version.
+const ABI_VERSIONS: [(u32, u32); 3] = [(3, 6), (4, 0), (4, 2)];
+
+/// Environment variable that overrides the detected version, for example when cross compiling.
+const VERSION_VARIABLE: &str = "WIRESHARK_VERSION";
+
+fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = version.trim().split('.').map(|v| v.parse::<u32>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next()?.ok()?;
+    let micro = parts.next().unwrap_or(Ok(0)).ok()?;
+    Some((major, minor, micro))
+}
+
+/// Determine the wireshark version, from the environment variable or from pkg-config. The structs differ between
+/// releases, so guessing the version with the wireshark feature enabled would produce a plugin that corrupts memory, the
+/// build fails instead.
+fn wireshark_version() -> (u32, u32, u32) {
+    println!("cargo:rerun-if-env-changed={}", VERSION_VARIABLE);
+    if let Ok(version) = std::env::var(VERSION_VARIABLE) {
+        match parse_version(&version) {
+            Some(v) => return v,
+            None => panic!(
+                "Could not parse {}={:?}, expected major.minor[.micro].",
+                VERSION_VARIABLE, version
+            ),
+        }
+    }
+
+    // Without the wireshark feature there's nothing to link against, so don't bother looking.
+    if std::env::var_os("CARGO_FEATURE_WIRESHARK").is_none() {
+        return FALLBACK_VERSION;
+    }
+
+    match pkg_config::Config::new().cargo_metadata(false).probe("wireshark") {
+        Ok(library) => match parse_version(&library.version) {
+            Some(v) => v,
+            None => panic!(
+                "Could not parse wireshark version {:?}, set {} to the version.",
+                library.version, VERSION_VARIABLE
+            ),
+        },
+        Err(e) => panic!(
+            "Could not find wireshark with pkg-config, set {} to the version to build for: {}",
+            VERSION_VARIABLE, e
+        ),
+    }
+}
+
+/// Headers the sys module is generated from, relative to the wireshark include directory.
+#[cfg(feature = "bindgen")]
+const HEADERS: [&str; 8] = [
+    "epan/packet.h",
+    "epan/expert.h",
+    "epan/prefs.h",
+    "epan/proto.h",
+    "epan/tvbuff.h",
+    "epan/ftypes/ftypes.h",
+    "epan/value_string.h",
+    "epan/range.h",
+];
+
+/// Generate the raw bindings from the installed headers into sys.rs in the output directory.
+#[cfg(feature = "bindgen")]
+fn generate_bindings(out_dir: &std::path::Path) {
+    let library = pkg_config::Config::new()
+        .cargo_metadata(false)
+        .probe("wireshark")
+        .expect("The bindgen feature needs the wireshark headers, which pkg-config could not find.");
+
+    let wrapper: String = HEADERS.iter().map(|h| format!("#include <{}>\n", h)).collect();
+    let mut builder = bindgen::Builder::default()
+        .header_contents("wrapper.h", &wrapper)
+        .allowlist_type("header_field_info|field_info|hf_register_info|proto_plugin|fvalue_t|packet_info")
+        .allowlist_type("expert_field|expert_field_info|ei_register_info|enum_val_t")
+        .allowlist_type("value_string|val64_string|range_string|range_admin_t|epan_range")
+        .allowlist_function("(proto|tvb|fvalue|dissector|heur_dissector|create_dissector|register)_.*")
+        .allowlist_var("(BASE|STR|SEP|ENC)_.*")
+        .derive_default(true)
+        .layout_tests(false);
+    for path in library.include_paths.iter() {
+        builder = builder.clang_arg(format!("-I{}", path.display()));
+    }
+    for header in HEADERS.iter() {
+        for path in library.include_paths.iter() {
+            let full = path.join(header);
+            if full.exists() {
+                println!("cargo:rerun-if-changed={}", full.display());
+            }
+        }
+    }
+    builder
+        .generate()
+        .expect("Failed to generate the bindings.")
+        .write_to_file(out_dir.join("sys.rs"))
+        .expect("Failed to write the bindings.");
+}
+
+/// The C sources of the shim, for the parts of wireshark that can't be used from Rust directly.
+const SHIM_SOURCES: [&str; 3] = [
+    "src/epan/exceptions.c",
+    "src/epan/column_utils.c",
+    "src/epan/frame_data.c",
+];
+
+/// Compile the shim that catches wireshark's exceptions and reads the values that differ between versions, this needs
+/// the wireshark headers. Without the shim exceptions would unwind through Rust frames and the wrong columns would be
+/// written, so the build fails if the headers are missing.
+fn build_shim() {
+    for source in SHIM_SOURCES.iter() {
+        println!("cargo:rerun-if-changed={}", source);
+    }
+    if std::env::var_os("CARGO_FEATURE_WIRESHARK").is_none() {
+        return;
+    }
+    let library = match pkg_config::Config::new().cargo_metadata(false).probe("wireshark") {
+        Ok(library) => library,
+        Err(e) => panic!(
+            "The wireshark headers are needed to build the shim that catches wireshark's exceptions, but pkg-config \
+             could not find them: {}",
+            e
+        ),
+    };
+    cc::Build::new()
+        .files(SHIM_SOURCES.iter())
+        .includes(library.include_paths.iter())
+        .compile("wireshark_dissector_rs_shim");
+}
+
 fn main() {
-    println!("cargo:rustc-link-lib=wireshark");
-    println!("cargo:rustc-link-lib=glib-2.0");
+    // Only link against wireshark if the feature is enabled, the mock backend doesn't need it.
+    if std::env::var_os("CARGO_FEATURE_WIRESHARK").is_some() {
+        println!("cargo:rustc-link-lib=wireshark");
+        println!("cargo:rustc-link-lib=glib-2.0");
+    }
+
+    let (major, minor, micro) = wireshark_version();
+    for (abi_major, abi_minor) in ABI_VERSIONS.iter() {
+        let cfg = format!("ws_{}_{}", abi_major, abi_minor);
+        println!("cargo:rustc-check-cfg=cfg({})", cfg);
+        if (major, minor) >= (*abi_major, *abi_minor) {
+            println!("cargo:rustc-cfg={}", cfg);
+        }
+    }
+
+    build_shim();
+
+    let out_dir = std::path::PathBuf::from(std::env::var_os("OUT_DIR").unwrap());
+    #[cfg(feature = "bindgen")]
+    generate_bindings(&out_dir);
+
+    // Write the version constants, these are included by the version module.
+    let mut f = std::fs::File::create(out_dir.join("wireshark_version.rs")).unwrap();
+    writeln!(f, "/// Major version of wireshark this crate is built for.").unwrap();
+    writeln!(f, "pub const WIRESHARK_VERSION_MAJOR: u32 = {};", major).unwrap();
+    writeln!(f, "/// Minor version of wireshark this crate is built for.").unwrap();
+    writeln!(f, "pub const WIRESHARK_VERSION_MINOR: u32 = {};", minor).unwrap();
+    writeln!(f, "/// Micro version of wireshark this crate is built for.").unwrap();
+    writeln!(f, "pub const WIRESHARK_VERSION_MICRO: u32 = {};", micro).unwrap();
+    writeln!(
+        f,
+        "/// The major and minor version, as used for the plugin_release symbol."
+    )
+    .unwrap();
+    writeln!(f, "pub const WIRESHARK_RELEASE: &str = \"{}.{}\";", major, minor).unwrap();
 }
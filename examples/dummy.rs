@@ -3,7 +3,9 @@
 
 extern crate wireshark_dissector_rs;
 
-use wireshark_dissector_rs::dissector::{self, BasicHeaderFieldInfo, FieldDisplay, FieldType, HeaderFieldInfo};
+use wireshark_dissector_rs::dissector::{
+    self, BasicHeaderFieldInfo, ExpertField, ExpertGroup, ExpertSeverity, FieldDisplay, FieldType, HeaderFieldInfo,
+};
 use wireshark_dissector_rs::epan::{self, proto::Encoding};
 
 // Need something to identify the tree foldouts by.
@@ -18,6 +20,8 @@ enum TreeIdentifier {
 struct MyDissector {
     field_mapping: Vec<(Box<dyn HeaderFieldInfo>, epan::proto::HFIndex)>,
     tree_indices: Vec<epan::proto::ETTIndex>,
+    expert_mapping: Vec<(ExpertField, epan::expert::EIIndex)>,
+    preferences: Option<epan::Preferences>,
     fields_made_at_runtime: Vec<BasicHeaderFieldInfo>,
 }
 
@@ -48,6 +52,22 @@ impl MyDissector {
         FieldType::UINT16,
         FieldDisplay::BASE_DEC,
     );
+
+    /// Expert info to flag an even uint32 byte, just to show how expert info is used.
+    const EXPERT_EVEN: ExpertField = ExpertField::simple(
+        "proto.expert.even",
+        ExpertGroup::PROTOCOL,
+        ExpertSeverity::NOTE,
+        "The uint32 byte is even",
+    );
+
+    /// Expert info for data after the fields we know about.
+    const EXPERT_TRAILING: ExpertField = ExpertField::simple(
+        "proto.expert.trailing",
+        ExpertGroup::UNDECODED,
+        ExpertSeverity::WARN,
+        "Trailing data",
+    );
 }
 
 impl MyDissector {
@@ -61,6 +81,16 @@ impl MyDissector {
         panic!("Couldn't find field id for {:?}", desired_field);
     }
 
+    /// Helper function to retrieve the EIIndex that's associated to one of the ExpertFields we used during setup.
+    fn get_expert_id(self: &Self, desired_expert: &ExpertField) -> epan::expert::EIIndex {
+        for (expert, index) in &self.expert_mapping {
+            if expert == desired_expert {
+                return *index;
+            }
+        }
+        panic!("Couldn't find expert id for {:?}", desired_expert);
+    }
+
     /// Helper function to retrieve the ETTIndex associated to a particular tree identifier.
     fn get_tree_id(self: &Self, identifier: TreeIdentifier) -> epan::proto::ETTIndex {
         match identifier {
@@ -132,6 +162,8 @@ impl MyDissector {
         MyDissector {
             field_mapping: Vec::new(),
             tree_indices: Vec::new(),
+            expert_mapping: Vec::new(),
+            preferences: None,
             fields_made_at_runtime: vec![
                 runtime_defined_field,
                 field_with_strings,
@@ -164,6 +196,31 @@ impl dissector::Dissector for MyDissector {
         self.field_mapping = hfindices;
     }
 
+    /// This function is called during setup, it must provide all the expert info we may end up adding.
+    fn get_expert_fields(self: &Self) -> Vec<ExpertField> {
+        return vec![MyDissector::EXPERT_EVEN, MyDissector::EXPERT_TRAILING];
+    }
+
+    /// This function is called after registering the expert info, it stores the indices.
+    fn set_expert_indices(self: &mut Self, ei_indices: Vec<(ExpertField, epan::expert::EIIndex)>) {
+        self.expert_mapping = ei_indices;
+    }
+
+    /// This function is called during setup, it provides the preferences that show up in the protocol preferences.
+    fn get_preferences(self: &Self) -> Vec<dissector::Preference> {
+        return vec![dissector::Preference {
+            name: dissector::StringContainer::StaticStr("show_info"),
+            title: dissector::StringContainer::StaticStr("Show the uint32 byte in the info column"),
+            description: dissector::StringContainer::StaticStr("Whether the info column shows the uint32 byte."),
+            kind: dissector::PreferenceKind::Bool { default: true },
+        }];
+    }
+
+    /// This function is called after registering the preferences, the handle provides their current values.
+    fn set_preferences(self: &mut Self, preferences: epan::Preferences) {
+        self.preferences = Some(preferences);
+    }
+
     /// This function is called during setup, it should return how many tree foldouts should be registered.
     fn get_tree_count(self: &Self) -> usize {
         return TreeIdentifier::Last as usize;
@@ -176,7 +233,11 @@ impl dissector::Dissector for MyDissector {
     }
 
     /// The main dissection function, this is called whenever we are to dissect something.
-    fn dissect(self: &Self, proto: &mut epan::ProtoTree, tvb: &mut epan::TVB) -> usize {
+    fn dissect(self: &Self, proto: &mut epan::ProtoTree, tvb: &mut epan::TVB, pinfo: &mut epan::PacketInfo) -> usize {
+        // Show our protocol in the packet list, instead of the protocol that called us.
+        pinfo.col_set_str(epan::Column::Protocol, "testproto");
+        pinfo.col_clear(epan::Column::Info);
+
         // Usually, we want to use an offset and increment it as we progress through the packet.
         let mut offset = 0;
 
@@ -250,15 +311,36 @@ impl dissector::Dissector for MyDissector {
             Encoding::BIG_ENDIAN,
         );
 
-        // And we can prepend text if the returned value is even.
+        // And we can prepend text if the returned value is even, and flag it with expert info.
         if retval % 2 == 0 {
             item.prepend_text("foo");
+            item.add_expert(pinfo, self.get_expert_id(&MyDissector::EXPERT_EVEN));
+        }
+
+        // The info column holds a summary of the packet.
+        let show_info = self.preferences.as_ref().and_then(|p| p.boolean("show_info"));
+        if show_info.unwrap_or(true) {
+            pinfo.col_add_str(epan::Column::Info, &format!("uint32 byte: {}", retval));
         }
 
         // Or add our second foldout.
         let mut more_folds = item.add_subtree(self.get_tree_id(TreeIdentifier::FirstElements));
         more_folds.add_item(self.get_id(&MyDissector::FIELD64), tvb, offset, 1, Encoding::BIG_ENDIAN);
 
+        // Anything after the last field is flagged as trailing data.
+        let end = offset + 18;
+        if tvb.reported_length() > end {
+            let remaining = tvb.reported_length() - end;
+            proto.add_expert_format(
+                pinfo,
+                self.get_expert_id(&MyDissector::EXPERT_TRAILING),
+                tvb,
+                end,
+                remaining,
+                &format!("Trailing data: {} bytes", remaining),
+            );
+        }
+
         tvb.reported_length()
     }
 
@@ -289,27 +371,34 @@ impl dissector::Dissector for MyDissector {
             //~ abbrev: "usb.device",
             //~ pattern: 0x00030003,
             //~ },
+            //~ dissector::Registration::String {
+            //~ table: "media_type",
+            //~ pattern: "application/x-dummy",
+            //~ },
+            //~ dissector::Registration::StringDecodeAs { abbrev: "ws.protocol" },
         ];
     }
 }
 
-use std::rc::Rc;
-
-/// This function is the main entry point for the plugin. It's the only symbol called automatically.
-#[no_mangle]
-pub fn plugin_register() {
-    let z = Rc::new(MyDissector::new());
-    dissector::setup(z);
+// This creates the symbols wireshark needs to load the plugin and registers our dissector.
+wireshark_dissector_rs::wireshark_plugin!(dissectors: [MyDissector::new()]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wireshark_dissector_rs::{mock, snapshot};
+
+    /// Dissect a packet with the mock backend and compare it against the snapshot, run with UPDATE_SNAPSHOTS=1 to
+    /// update the snapshot after changing the dissector.
+    #[test]
+    fn dissect_snapshot() {
+        let mut dissector = MyDissector::new();
+        let harness = mock::Harness::new(&mut dissector);
+        snapshot::assert_dissection_hex(
+            &harness,
+            &dissector,
+            "01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f 10 11 12 13 14 15 16",
+            "examples/snapshots/dummy.snap",
+        );
+    }
 }
-
-// And we need these public symbols to tell wireshark we are a plugin that's made for the right version.
-#[no_mangle]
-static plugin_version: [libc::c_char; 4] = [50, 46, 54, 0]; // "2.6"
-#[no_mangle]
-static plugin_release: [libc::c_char; 4] = [50, 46, 54, 0]; // "2.6"
-
-// Later versions of wireshark also want these integers.
-#[no_mangle]
-static plugin_want_major: u32 = 2;
-#[no_mangle]
-static plugin_want_minor: u32 = 6;
@@ -2,9 +2,11 @@
 // SPDX-License-Identifier: GPL-2.0-or-later
 
 use crate::epan;
+#[cfg(feature = "wireshark")]
 use crate::plugin;
 extern crate libc;
 use core::fmt::Debug;
+use std::cell::{Ref, RefCell, RefMut};
 
 pub use crate::epan::HeaderFieldInfo;
 
@@ -15,6 +17,14 @@ pub use crate::epan::HeaderFieldInfo;
 /// that were retrieved from [`Dissector::get_fields()`], paired with the [`epan::proto::HFIndex`] values that should be used when
 /// display dissection results in the protocol tree.
 ///
+/// Expert info the dissector may report, to flag malformed or suspicious data, is registered in the same way; the
+/// elements from [`Dissector::get_expert_fields()`] are handed back to [`Dissector::set_expert_indices()`] paired with
+/// the [`epan::expert::EIIndex`] to use. Both have a default implementation for dissectors without expert info.
+///
+/// The preferences of the dissector are declared with [`Dissector::get_preferences()`] and registered with the
+/// protocol. The [`epan::Preferences`] handed to [`Dissector::set_preferences()`] provides the current values, and
+/// [`Dissector::preferences_changed()`] is called whenever they are changed.
+///
 /// Besides the fields, the dissector also needs to register the subtree foldouts that it will use. During the setup the
 /// [`Dissector::get_tree_count()`] method will be called, which should return the number of foldouts to register. After registration
 /// the [`Dissector::set_tree_indices()`] method is called with a vector of indices to be used.
@@ -22,8 +32,12 @@ pub use crate::epan::HeaderFieldInfo;
 /// The final step of protocol registration, during the handoff is registering the dissector to be called on packets.
 /// The desired registrations need to be returned from [`Dissector::get_registration()`], see Registration for more information.
 ///
+/// Dissectors that keep state between packets, for example to match requests with responses, must reset it whenever a
+/// capture file is (re)loaded; [`Dissector::init()`] is called before dissecting a file and [`Dissector::cleanup()`]
+/// after. As the dissection only gets `&Self`, such state can be kept in a [`FileState`].
+///
 /// Whenever the dissector is invoked, it's [`Dissector::dissect()`] (or [`Dissector::heuristic_dissect()`]) method will be called with the
-/// protocol tree and data buffer.
+/// protocol tree, data buffer and the packet info, the latter provides access to the columns of the packet list.
 pub trait Dissector {
     /// This function must return a vector of all the possible fields the dissector will end up using.
     fn get_fields(self: &Self) -> Vec<Box<dyn HeaderFieldInfo>>;
@@ -32,9 +46,62 @@ pub trait Dissector {
     /// to refer to the registered fields.
     fn set_field_indices(self: &mut Self, hf_indices: Vec<(Box<dyn HeaderFieldInfo>, epan::proto::HFIndex)>);
 
+    /// This function should return all the expert info the dissector may add, see [`ExpertField`].
+    fn get_expert_fields(self: &Self) -> Vec<ExpertField> {
+        return Vec::new();
+    }
+
+    /// After the expert info is registered, this function is called with the [`epan::expert::EIIndex`] to use with
+    /// [`epan::ProtoTree::add_expert()`] and friends for each element from [`Dissector::get_expert_fields()`].
+    fn set_expert_indices(self: &mut Self, _ei_indices: Vec<(ExpertField, epan::expert::EIIndex)>) {}
+
+    /// This function should return the preferences of the dissector, see [`Preference`].
+    fn get_preferences(self: &Self) -> Vec<Preference> {
+        return Vec::new();
+    }
+
+    /// After the preferences are registered, this function is called with the handle that provides their current
+    /// values, it can be stored and used during the dissection.
+    fn set_preferences(self: &mut Self, _preferences: epan::Preferences) {}
+
+    /// Called when the preferences of this dissector changed, because the user changed them or because they were read
+    /// from the preferences file. This is wireshark's `apply_cb`.
+    fn preferences_changed(self: &Self, _preferences: &epan::Preferences) {}
+
+    /// Called before the packets of a capture file are dissected, this also happens when the file is dissected again,
+    /// for example after changing preferences. Any state from a previous file must be reset here.
+    fn init(self: &Self) {}
+
+    /// Called when the capture file is closed, state that isn't needed anymore can be released here.
+    fn cleanup(self: &Self) {}
+
+    /// This function should return the reassembly tables of the dissector, see [`Reassembly`].
+    fn get_reassembly_tables(self: &Self) -> Vec<Reassembly> {
+        return Vec::new();
+    }
+
+    /// After registering the reassembly tables, with their fields and subtrees, this function is called with a handle
+    /// for each element from [`Dissector::get_reassembly_tables()`], used to add the fragments during the dissection.
+    fn set_reassembly_tables(self: &mut Self, _tables: Vec<(Reassembly, epan::ReassemblyTable)>) {}
+
+    /// This function should return the dissector tables this protocol owns, see [`DissectorTableInfo`]. Other
+    /// dissectors can register in these, like they do with [`Registration::UInt`].
+    fn get_dissector_tables(self: &Self) -> Vec<DissectorTableInfo> {
+        return Vec::new();
+    }
+
+    /// After registering the dissector tables this function is called with a handle for each element from
+    /// [`Dissector::get_dissector_tables()`], used to hand data to the registered dissectors during the dissection.
+    fn set_dissector_tables(self: &mut Self, _tables: Vec<(DissectorTableInfo, epan::DissectorTable)>) {}
+
     /// Called when there is something to dissect, so probably called for every packet. This function must return how
     /// many bytes it used from the tvb.
-    fn dissect(self: &Self, _proto: &mut epan::ProtoTree, _tvb: &mut epan::TVB) -> usize {
+    fn dissect(
+        self: &Self,
+        _proto: &mut epan::ProtoTree,
+        _tvb: &mut epan::TVB,
+        _pinfo: &mut epan::PacketInfo,
+    ) -> usize {
         0
     }
 
@@ -56,11 +123,52 @@ pub trait Dissector {
     fn set_tree_indices(self: &mut Self, _ett_indices: Vec<epan::proto::ETTIndex>) {}
 
     /// This function is called when using a heuristic dissection.
-    fn heuristic_dissect(self: &Self, _proto: &mut epan::ProtoTree, _tvb: &mut epan::TVB) -> bool {
+    fn heuristic_dissect(
+        self: &Self,
+        _proto: &mut epan::ProtoTree,
+        _tvb: &mut epan::TVB,
+        _pinfo: &mut epan::PacketInfo,
+    ) -> bool {
         false
     }
 }
 
+/// Holds mutable state that lives as long as a capture file, the dissector can't modify itself during dissection as it
+/// only gets `&Self`. Call [`FileState::reset()`] from [`Dissector::init()`] to start every file with the default
+/// state.
+///
+/// Wireshark may call the dissector from different threads, the registration for example runs on a worker thread, but
+/// never from two threads at the same time. That's why neither the dissectors handed to `setup` nor this state need any
+/// locking. Borrowing the state mutably while it is already borrowed panics.
+#[derive(Debug, Default)]
+pub struct FileState<T: Default> {
+    state: RefCell<T>,
+}
+
+impl<T: Default> FileState<T> {
+    /// Create the state with its default value.
+    pub fn new() -> Self {
+        FileState {
+            state: RefCell::new(T::default()),
+        }
+    }
+
+    /// Borrow the state for reading.
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.state.borrow()
+    }
+
+    /// Borrow the state for modification.
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        self.state.borrow_mut()
+    }
+
+    /// Replace the state with its default value.
+    pub fn reset(&self) {
+        self.state.replace(T::default());
+    }
+}
+
 //-------------------------------------------------
 pub type FieldType = epan::ftypes::ftenum;
 pub type FieldDisplay = epan::proto::FieldDisplay;
@@ -187,9 +295,193 @@ impl Debug for BasicHeaderFieldInfo {
     }
 }
 
+pub type ExpertSeverity = epan::expert::Severity;
+pub type ExpertGroup = epan::expert::Group;
+
+/// Describes an expert info to register, these show up in the Expert Information dialog and can be filtered on with
+/// their abbreviation as well as with `_ws.expert`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpertField {
+    /// This is the abbreviation / internal name of the expert info (`proto.expert_name`).
+    pub abbrev: StringContainer,
+    /// The category of the problem.
+    pub group: ExpertGroup,
+    /// How serious the problem is.
+    pub severity: ExpertSeverity,
+    /// The text shown for the expert info, unless it's added with one of the `_format` functions.
+    pub summary: StringContainer,
+}
+
+impl ExpertField {
+    /// Helper function to easily make constant [`ExpertField`] entries.
+    pub const fn simple(
+        abbrev: &'static str,
+        group: ExpertGroup,
+        severity: ExpertSeverity,
+        summary: &'static str,
+    ) -> Self {
+        ExpertField {
+            abbrev: StringContainer::StaticStr(abbrev),
+            group: group,
+            severity: severity,
+            summary: StringContainer::StaticStr(summary),
+        }
+    }
+}
+
+/// The type of a preference, with its default value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreferenceKind {
+    /// An unsigned integer, shown in the provided base, which is 10 or 16.
+    UInt { default: u32, base: u32 },
+    /// A checkbox.
+    Bool { default: bool },
+    /// A string, for example key material.
+    String { default: String },
+    /// One of a fixed set of values, these are (name, description, value) entries. The name is what's stored in the
+    /// preferences file and the description is shown to the user.
+    Enum {
+        default: i32,
+        values: Vec<(String, String, i32)>,
+        radio_buttons: bool,
+    },
+    /// A range of integers, like ports, each value must be at most max_value.
+    Range { default: Vec<(u32, u32)>, max_value: u32 },
+    /// The path of a file, for_writing selects a save dialog instead of an open dialog.
+    Filename { default: String, for_writing: bool },
+}
+
+/// The type of the values in a dissector table.
+#[derive(Debug, Clone, Copy)]
+pub enum DissectorTableKind {
+    /// Integer values, like an opcode or port.
+    UInt {
+        /// One of the unsigned integer types, up to [`FieldType::UINT32`].
+        field_type: FieldType,
+        /// How the values are shown, for example in the Decode As dialog.
+        display: FieldDisplay,
+    },
+    /// String values, like a media type.
+    String {
+        /// Whether the values are compared case sensitive.
+        case_sensitive: bool,
+    },
+}
+
+/// Describes a dissector table to register, owned by this protocol. Like the C dissectors, the name is usually the
+/// abbreviation of the field the table is keyed on, like `myproto.opcode`.
+#[derive(Debug, Clone)]
+pub struct DissectorTableInfo {
+    /// The name other dissectors register in the table by.
+    pub name: StringContainer,
+    /// The name shown to the user, for example in the Decode As dialog.
+    pub ui_name: StringContainer,
+    /// The type of the values.
+    pub kind: DissectorTableKind,
+}
+
+/// Describes a preference to register, these show up in the protocol preferences.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preference {
+    /// The name of the preference, it is stored as `protocol.name` in the preferences file.
+    pub name: StringContainer,
+    /// The title shown to the user.
+    pub title: StringContainer,
+    /// The longer description, shown as tooltip.
+    pub description: StringContainer,
+    /// The type and default value.
+    pub kind: PreferenceKind,
+}
+
+/// Which fragments belong to the same message, besides having the same id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReassemblyKey {
+    /// Fragments with the same source and destination address.
+    Addresses,
+    /// Fragments with the same source and destination address and port.
+    AddressesPorts,
+}
+
+/// Describes a reassembly table to register, for a protocol that splits its messages into fragments. The fields and
+/// subtrees that show the fragments are registered with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reassembly {
+    /// The name of the message, used in the labels, like `Message fragments` and `Reassembled Message`.
+    pub name: StringContainer,
+    /// Prefix for the abbreviations of the fields, like `proto.msg`, which gives `proto.msg.fragment` and others.
+    pub abbrev: StringContainer,
+    /// How fragments with the same id are matched.
+    pub key: ReassemblyKey,
+}
+
+impl Reassembly {
+    /// The fields that show the fragments, in the order of wireshark's `fragment_items`.
+    pub(crate) fn fields(self: &Self) -> Vec<BasicHeaderFieldInfo> {
+        let name = self.name.as_str();
+        let field = |title: String, abbrev: &str, field_type: FieldType, display: FieldDisplay| BasicHeaderFieldInfo {
+            name: StringContainer::String(title),
+            abbrev: StringContainer::String(format!("{}.{}", self.abbrev.as_str(), abbrev)),
+            field_type,
+            display,
+            ..Default::default()
+        };
+        use epan::ftypes::ftenum::{BOOLEAN, BYTES, FRAMENUM, NONE, UINT32};
+        use epan::proto::FieldDisplay::{BASE_DEC, BASE_NONE};
+        return vec![
+            field(format!("{} fragments", name), "fragments", NONE, BASE_NONE),
+            field(format!("{} fragment", name), "fragment", FRAMENUM, BASE_NONE),
+            field(
+                format!("{} fragment overlap", name),
+                "fragment.overlap",
+                BOOLEAN,
+                BASE_NONE,
+            ),
+            field(
+                format!("{} fragment overlapping with conflicting data", name),
+                "fragment.overlap.conflicts",
+                BOOLEAN,
+                BASE_NONE,
+            ),
+            field(
+                format!("{} has multiple tail fragments", name),
+                "fragment.multiple_tails",
+                BOOLEAN,
+                BASE_NONE,
+            ),
+            field(
+                format!("{} fragment too long", name),
+                "fragment.too_long_fragment",
+                BOOLEAN,
+                BASE_NONE,
+            ),
+            field(
+                format!("{} defragmentation error", name),
+                "fragment.error",
+                FRAMENUM,
+                BASE_NONE,
+            ),
+            field(format!("{} fragment count", name), "fragment.count", UINT32, BASE_DEC),
+            field("Reassembled in".to_owned(), "reassembled.in", FRAMENUM, BASE_NONE),
+            field(
+                format!("Reassembled {} length", name),
+                "reassembled.length",
+                UINT32,
+                BASE_DEC,
+            ),
+            field(
+                format!("Reassembled {} data", name),
+                "reassembled.data",
+                BYTES,
+                BASE_NONE,
+            ),
+        ];
+    }
+}
+
 // https://rust-lang.github.io/rfcs/0418-struct-variants.html
 // This is so fancy
 /// Specifies how to register this dissector.
+#[derive(Debug, Clone)]
 pub enum Registration {
     /// Register as a postdissector, this calls `register_postdissector`, it is always ran, after all all other dissectors.
     Post,
@@ -213,6 +505,19 @@ pub enum Registration {
         /// The table to register for.
         abbrev: &'static str,
     },
+    /// Register in a table keyed by strings, this calls `dissector_add_string`, for example a media type in
+    /// `media_type` or a topic in `mqtt.topic`.
+    String {
+        /// The table to register for.
+        table: &'static str,
+        /// The string in this table to register.
+        pattern: &'static str,
+    },
+    /// Register this dissector for manual 'decode as' functionality in a table keyed by strings, like `ws.protocol`.
+    StringDecodeAs {
+        /// The table to register for.
+        abbrev: &'static str,
+    },
     /// As a heuristic dissector for the provided table and using display names from this.
     Heuristic {
         /// The table to register for.
@@ -226,11 +531,16 @@ pub enum Registration {
     },
 }
 
-use std::rc::Rc;
-/// Pass the dissector for setup, this is the main entry function that registers the plugin.
+/// Pass the dissectors for setup, this is the main entry function that registers the plugin.
 ///
-/// Currently, the one dissector that's handed in is stored in a global static. During setup we use it as a mutable
-/// after setup it will be immutable and multiple threads from wireshark may interact with it.
-pub fn setup<T: 'static + Dissector>(d: Rc<T>) {
-    plugin::setup(d);
+/// Each dissector that's handed in is stored in a global static and gets its own protocol id, fields, subtrees and
+/// dissector handle. During setup we use them as mutable, after setup they will be immutable, see [`FileState`] for
+/// the threads wireshark calls them from. At most [`MAX_DISSECTORS`] dissectors can be held by a single plugin.
+#[cfg(feature = "wireshark")]
+pub fn setup(dissectors: Vec<Box<dyn Dissector>>) {
+    plugin::setup(dissectors);
 }
+
+/// The maximum number of dissectors that can be passed to [`setup()`].
+#[cfg(feature = "wireshark")]
+pub const MAX_DISSECTORS: usize = plugin::MAX_DISSECTORS;
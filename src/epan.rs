@@ -15,7 +15,8 @@
 // 1.5.2 Adding Items and Values to the Protocol Tree. https://github.com/wireshark/wireshark/blob/ebfbf958f6930b2dad486b33277470e8368dc111/doc/README.dissector#L1351
 
 // Reassembly 2.7.2 Modifying the pinfo struct; https://github.com/wireshark/wireshark/blob/ebfbf958f6930b2dad486b33277470e8368dc111/doc/README.dissector#L3472
-// Yeah, that doesn't work for USB packets... gg.
+// That's the desegmentation TCP offers, which doesn't work for USB packets; those use the fragment_add_* functions
+// of reassemble.h, see ReassemblyTable.
 // https://doc.rust-lang.org/nomicon/ffi.html
 
 // We can probably hook; https://github.com/wireshark/wireshark/blob/ebfbf958f6930b2dad486b33277470e8368dc111/epan/dissectors/packet-usb.c#L3516-L3518
@@ -27,19 +28,41 @@
 #![allow(dead_code)]
 extern crate libc;
 
+use crate::dissector;
+use crate::mock;
+#[cfg(feature = "wireshark")]
+use crate::util;
+
 // These files follow the same structure as the header files.
+pub mod address;
+pub mod column_utils;
+pub mod conversation;
+pub mod exceptions;
+pub mod expert;
+pub mod frame_data;
 pub mod ftypes;
 pub mod glib;
+pub mod guid_utils;
+pub mod nstime;
 pub mod packet;
 pub mod packet_info;
+pub mod packet_tcp;
+pub mod prefs;
 pub mod proto;
+pub mod proto_data;
 pub mod range;
+pub mod reassemble;
+pub mod show_exception;
+#[cfg(feature = "bindgen")]
+pub mod sys;
 pub mod tvbuff;
 pub mod value_string;
+pub mod wmem;
 
 pub type FieldType = ftypes::ftenum;
 pub type FieldDisplay = proto::FieldDisplay;
 pub type Encoding = proto::Encoding;
+pub type Exception = exceptions::Exception;
 /*
    Dissector
        get_fields()
@@ -68,10 +91,12 @@ pub type Encoding = proto::Encoding;
     Todo: switch from pointers to references with proper lifetime if that's possible?
 */
 
+#[cfg(feature = "wireshark")]
 /// Wrapper around the fvalue_t found in the FieldInfo struct
 pub struct FValue<'a> {
     value: &'a ftypes::fvalue_t,
 }
+#[cfg(feature = "wireshark")]
 impl FValue<'_> {
     /// Create the FValue from the input argument.
     pub unsafe fn from(v: &ftypes::fvalue_t) -> FValue {
@@ -127,6 +152,7 @@ impl FValue<'_> {
     }
     */
 }
+#[cfg(feature = "wireshark")]
 impl Debug for FValue<'_> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "FValue<'_> {{ ")?;
@@ -239,7 +265,10 @@ impl HeaderFieldInfo for WrappedHeaderFieldInfo {
     /// Obtain the field type enum.
     fn feature_type(&self) -> ftypes::ftenum {
         unsafe {
+            #[cfg(not(feature = "bindgen"))]
             return (*self.hfi).type_;
+            #[cfg(feature = "bindgen")]
+            return ftypes::ftenum::from_raw((*self.hfi).type_);
         }
     }
 
@@ -262,11 +291,13 @@ impl Debug for WrappedHeaderFieldInfo {
     }
 }
 
+#[cfg(feature = "wireshark")]
 /// Struct to represent field information, serves as a wrapper around the `field_info` C struct.
 pub struct FieldInfo {
     fi: *const proto::field_info,
 }
 
+#[cfg(feature = "wireshark")]
 impl FieldInfo {
     /// Function to make this structure from a raw pointer.
     pub unsafe fn from_ptr(field_info: *const proto::field_info) -> FieldInfo {
@@ -297,7 +328,7 @@ impl FieldInfo {
     }
 
     /// data source tvbuff
-    pub fn ds_tvb(self: &Self) -> Option<TVB> {
+    pub fn ds_tvb(self: &Self) -> Option<TVB<'_>> {
         unsafe {
             if (*self.fi).ds_tvb.is_null() {
                 return None;
@@ -306,10 +337,17 @@ impl FieldInfo {
         }
     }
 
+    #[cfg(not(ws_4_0))]
     pub fn value(self: &Self) -> FValue {
         unsafe { FValue::from(&(*self.fi).value) }
     }
+
+    #[cfg(ws_4_0)]
+    pub fn value(self: &Self) -> FValue {
+        unsafe { FValue::from(&*(*self.fi).value) }
+    }
 }
+#[cfg(feature = "wireshark")]
 impl Debug for FieldInfo {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "FieldInfo {{ ")?;
@@ -321,20 +359,62 @@ impl Debug for FieldInfo {
     }
 }
 
+/// The backend a [`ProtoTree`] operates on, either wireshark itself, or the recording from the [`crate::mock`] module.
+#[derive(Copy, Clone)]
+enum TreeBackend {
+    #[cfg(feature = "wireshark")]
+    Wireshark(*mut proto::proto_tree),
+    Mock(mock::NodeRef),
+}
+
+/// Format string for the _format_value functions, the text is passed as argument such that it's never interpreted.
+#[cfg(feature = "wireshark")]
+const FORMAT_STRING: &[u8; 3] = b"%s\0";
+
 /// Struct to represent a protocol tree, serves as a wrapper around the `proto_tree_*` C functions.
 #[derive(Copy, Clone)]
 pub struct ProtoTree {
-    tree: *mut proto::proto_tree,
+    tree: TreeBackend,
 }
 
 impl ProtoTree {
     /// Function to make this structure from a raw pointer.
+    #[cfg(feature = "wireshark")]
     pub unsafe fn from_ptr(tree: *mut proto::proto_tree) -> ProtoTree {
-        return ProtoTree { tree: tree };
+        return ProtoTree {
+            tree: TreeBackend::Wireshark(tree),
+        };
+    }
+
+    /// Make a tree that records into the in-memory mock backend.
+    pub(crate) fn from_mock(node: mock::NodeRef) -> ProtoTree {
+        return ProtoTree {
+            tree: TreeBackend::Mock(node),
+        };
+    }
+
+    /// Obtain the raw pointer to hand to wireshark functions, panics if this is a mock tree.
+    #[cfg(feature = "wireshark")]
+    fn ptr(&self) -> *mut proto::proto_tree {
+        match self.tree {
+            TreeBackend::Wireshark(tree) => tree,
+            TreeBackend::Mock(_) => panic!("Can't pass a mock tree to wireshark."),
+        }
+    }
+
+    /// Obtain the node to hand to the mock backend, panics if this is a wireshark tree.
+    fn mock(&self) -> mock::NodeRef {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(_) => panic!("Can't pass a wireshark tree to the mock backend."),
+            TreeBackend::Mock(node) => node,
+        }
     }
 
     /// Add an item to a proto_tree, using the text label registered to that item.
     /// The item is extracted from the tvbuff handed to it.
+    /// If wireshark throws an exception, for example because the item is outside the buffer, the exception is made
+    /// pending and the dissection ends when the dissector returns; see [`exceptions`].
     pub fn add_item(
         self: &mut Self,
         hfindex: proto::HFIndex,
@@ -343,13 +423,39 @@ impl ProtoTree {
         length: usize,
         encoding: proto::Encoding,
     ) -> ProtoItem {
-        unsafe {
-            ProtoItem {
-                item: proto::proto_tree_add_item(self.tree, hfindex, tvb.into(), start as i32, length as i32, encoding),
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => unsafe {
+                ProtoItem::contain(|| {
+                    proto::proto_tree_add_item(tree, hfindex, tvb.ptr(), start as i32, length as i32, encoding)
+                })
+            },
+            TreeBackend::Mock(node) => {
+                ProtoItem::from_mock(node.add_item(hfindex, tvb.mock(), start, length, encoding))
             }
         }
     }
 
+    /// Like [`ProtoTree::add_item()`], but an exception is returned instead of made pending, such that the dissector
+    /// can handle it. Nothing is added if an exception is already pending.
+    pub fn try_add_item(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        encoding: proto::Encoding,
+    ) -> Result<ProtoItem, Exception> {
+        if let Some(exception) = exceptions::pending() {
+            return Err(exception);
+        }
+        let item = self.add_item(hfindex, tvb, start, length, encoding);
+        match exceptions::take_pending() {
+            Some(exception) => return Err(exception),
+            None => return Ok(item),
+        }
+    }
+
     /// Add bits to a proto_tree, using the text label registered to that item. The item is
     /// extracted from the tvbuff handed to it.
     pub fn add_bits_item(
@@ -360,16 +466,22 @@ impl ProtoTree {
         no_of_bits: usize,
         encoding: proto::Encoding,
     ) -> ProtoItem {
-        unsafe {
-            ProtoItem {
-                item: proto::proto_tree_add_bits_item(
-                    self.tree,
-                    hfindex,
-                    tvb.into(),
-                    bit_offset as i32,
-                    no_of_bits as i32,
-                    encoding,
-                ),
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => unsafe {
+                ProtoItem::contain(|| {
+                    proto::proto_tree_add_bits_item(
+                        tree,
+                        hfindex,
+                        tvb.ptr(),
+                        bit_offset as i32,
+                        no_of_bits as i32,
+                        encoding,
+                    )
+                })
+            },
+            TreeBackend::Mock(node) => {
+                ProtoItem::from_mock(node.add_bits_item(hfindex, tvb.mock(), bit_offset, no_of_bits, encoding))
             }
         }
     }
@@ -385,171 +497,4361 @@ impl ProtoTree {
         length: usize,
         encoding: proto::Encoding,
     ) -> (ProtoItem, i32) {
-        let mut retval: i32 = 0;
-        unsafe {
-            return (
-                ProtoItem {
-                    item: proto::proto_tree_add_item_ret_int(
-                        self.tree,
-                        hfindex,
-                        tvb.into(),
-                        start as i32,
-                        length as i32,
-                        encoding,
-                        &mut retval as *mut i32,
-                    ),
-                },
-                retval,
-            );
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let mut retval: i32 = 0;
+                unsafe {
+                    return (
+                        ProtoItem::contain(|| {
+                            proto::proto_tree_add_item_ret_int(
+                                tree,
+                                hfindex,
+                                tvb.ptr(),
+                                start as i32,
+                                length as i32,
+                                encoding,
+                                &mut retval as *mut i32,
+                            )
+                        }),
+                        retval,
+                    );
+                }
+            }
+            TreeBackend::Mock(node) => {
+                let item = node.add_item(hfindex, tvb.mock(), start, length, encoding);
+                let retval = item.value().as_i64().unwrap_or(0) as i32;
+                return (ProtoItem::from_mock(item), retval);
+            }
         }
     }
 
-    /// Function to retrieve all field info's currently associated with the protocol tree.
-    pub fn all_finfos(self: &mut Self) -> Vec<FieldInfo> {
-        let mut res: Vec<FieldInfo> = Vec::new();
+    /// Like [`ProtoTree::add_item_ret_int()`], but for unsigned integers of up to 32 bits.
+    pub fn add_item_ret_uint(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        encoding: proto::Encoding,
+    ) -> (ProtoItem, u32) {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let mut retval: u32 = 0;
+                unsafe {
+                    return (
+                        ProtoItem::contain(|| {
+                            proto::proto_tree_add_item_ret_uint(
+                                tree,
+                                hfindex,
+                                tvb.ptr(),
+                                start as i32,
+                                length as i32,
+                                encoding,
+                                &mut retval as *mut u32,
+                            )
+                        }),
+                        retval,
+                    );
+                }
+            }
+            TreeBackend::Mock(node) => {
+                let item = node.add_item(hfindex, tvb.mock(), start, length, encoding);
+                let retval = item.value().as_u64().unwrap_or(0) as u32;
+                return (ProtoItem::from_mock(item), retval);
+            }
+        }
+    }
 
-        // see wslua_field.c function wslua_all_field_infos
-        if self.tree.is_null()
-        // Not too sure when this happens... tree seems to be null when first invoked?
-        {
-            return res;
+    /// Like [`ProtoTree::add_item_ret_int()`], but for unsigned integers of up to 64 bits.
+    pub fn add_item_ret_uint64(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        encoding: proto::Encoding,
+    ) -> (ProtoItem, u64) {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let mut retval: u64 = 0;
+                unsafe {
+                    return (
+                        ProtoItem::contain(|| {
+                            proto::proto_tree_add_item_ret_uint64(
+                                tree,
+                                hfindex,
+                                tvb.ptr(),
+                                start as i32,
+                                length as i32,
+                                encoding,
+                                &mut retval as *mut u64,
+                            )
+                        }),
+                        retval,
+                    );
+                }
+            }
+            TreeBackend::Mock(node) => {
+                let item = node.add_item(hfindex, tvb.mock(), start, length, encoding);
+                let retval = item.value().as_u64().unwrap_or(0);
+                return (ProtoItem::from_mock(item), retval);
+            }
         }
-        unsafe {
-            let fields = proto::proto_all_finfos(self.tree);
-            for i in 0..(*fields).len() {
-                let field =
-                    std::mem::transmute::<*mut libc::c_void, *const proto::field_info>((*fields).index(i as isize));
-                res.push(FieldInfo::from_ptr(field));
+    }
+
+    /// Like [`ProtoTree::add_item_ret_int()`], but for signed integers of up to 64 bits.
+    pub fn add_item_ret_int64(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        encoding: proto::Encoding,
+    ) -> (ProtoItem, i64) {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let mut retval: i64 = 0;
+                unsafe {
+                    return (
+                        ProtoItem::contain(|| {
+                            proto::proto_tree_add_item_ret_int64(
+                                tree,
+                                hfindex,
+                                tvb.ptr(),
+                                start as i32,
+                                length as i32,
+                                encoding,
+                                &mut retval as *mut i64,
+                            )
+                        }),
+                        retval,
+                    );
+                }
+            }
+            TreeBackend::Mock(node) => {
+                let item = node.add_item(hfindex, tvb.mock(), start, length, encoding);
+                let retval = item.value().as_i64().unwrap_or(0);
+                return (ProtoItem::from_mock(item), retval);
             }
-            glib::g_ptr_array_free(fields, true);
-            // The field info's actually stay in scope, as they are part of the proto datastructure.
-            // the lua part also persists them after they're gone.
         }
-        return res;
     }
-}
 
-use std::ffi::CString;
+    /// Like [`ProtoTree::add_item_ret_int()`], but for booleans.
+    pub fn add_item_ret_boolean(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        encoding: proto::Encoding,
+    ) -> (ProtoItem, bool) {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                // A gboolean before 4.2, a bool from then on.
+                #[cfg(not(ws_4_2))]
+                let mut retval: i32 = 0;
+                #[cfg(ws_4_2)]
+                let mut retval: bool = false;
+                let item = unsafe {
+                    ProtoItem::contain(|| {
+                        proto::proto_tree_add_item_ret_boolean(
+                            tree,
+                            hfindex,
+                            tvb.ptr(),
+                            start as i32,
+                            length as i32,
+                            encoding,
+                            &mut retval,
+                        )
+                    })
+                };
+                #[cfg(not(ws_4_2))]
+                let retval = retval != 0;
+                return (item, retval);
+            }
+            TreeBackend::Mock(node) => {
+                let item = node.add_item(hfindex, tvb.mock(), start, length, encoding);
+                let retval = item.value() == mock::Value::Bool(true);
+                return (ProtoItem::from_mock(item), retval);
+            }
+        }
+    }
 
-/// Struct to represent a protocol item, serves as a wrapper around the `proto_item_*` C functions.
-#[derive(Copy, Clone)]
-pub struct ProtoItem {
-    item: *mut proto::proto_item,
-}
-impl From<&mut ProtoItem> for *mut proto::proto_item {
-    fn from(field: &mut ProtoItem) -> Self {
-        return field.item;
+    /// Add an item and return the number of bytes it covers, this is useful for fields where the length is
+    /// determined during dissection, like null terminated strings or counted byte arrays.
+    pub fn add_item_ret_length(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        encoding: proto::Encoding,
+    ) -> (ProtoItem, usize) {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let mut retval: i32 = 0;
+                unsafe {
+                    return (
+                        ProtoItem::contain(|| {
+                            proto::proto_tree_add_item_ret_length(
+                                tree,
+                                hfindex,
+                                tvb.ptr(),
+                                start as i32,
+                                length as i32,
+                                encoding,
+                                &mut retval as *mut i32,
+                            )
+                        }),
+                        retval as usize,
+                    );
+                }
+            }
+            TreeBackend::Mock(node) => {
+                let item = node.add_item(hfindex, tvb.mock(), start, length, encoding);
+                let retval = item.length();
+                return (ProtoItem::from_mock(item), retval);
+            }
+        }
     }
-}
 
-impl ProtoItem {
-    /// Replace text of item after it already has been created.
-    pub fn set_text(self: &mut Self, text: &str) {
-        let to_add = CString::new(text).unwrap().into_raw();
-        unsafe {
-            proto::proto_item_set_text(self.item.into(), to_add);
-            // and clean up the string again.
-            let _ = CString::from_raw(to_add);
+    /// Add a string item and return the string, the encoding specifies the character encoding. The string is
+    /// converted to UTF-8 by wireshark.
+    pub fn add_item_ret_string(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        encoding: proto::Encoding,
+    ) -> (ProtoItem, String) {
+        let (item, value, _) = self.add_item_ret_string_and_length(hfindex, tvb, start, length, encoding);
+        return (item, value);
+    }
+
+    /// Like [`ProtoTree::add_item_ret_string()`], but also returns the number of bytes the string covers in the
+    /// buffer.
+    pub fn add_item_ret_string_and_length(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        encoding: proto::Encoding,
+    ) -> (ProtoItem, String, usize) {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let mut retval: *const u8 = std::ptr::null();
+                let mut lenretval: i32 = 0;
+                unsafe {
+                    // Without an allocator the string is allocated with g_malloc, we copy it and free it again.
+                    let item = ProtoItem::contain(|| {
+                        proto::proto_tree_add_item_ret_string_and_length(
+                            tree,
+                            hfindex,
+                            tvb.ptr(),
+                            start as i32,
+                            length as i32,
+                            encoding,
+                            std::ptr::null_mut(),
+                            &mut retval as *mut *const u8,
+                            &mut lenretval as *mut i32,
+                        )
+                    });
+                    let mut value = String::new();
+                    if !retval.is_null() {
+                        value = std::ffi::CStr::from_ptr(retval as *const libc::c_char)
+                            .to_string_lossy()
+                            .into_owned();
+                        wmem::wmem_free(std::ptr::null_mut(), retval as *mut libc::c_void);
+                    }
+                    return (item, value, lenretval as usize);
+                }
+            }
+            TreeBackend::Mock(node) => {
+                let item = node.add_item(hfindex, tvb.mock(), start, length, encoding);
+                let value = match item.value() {
+                    mock::Value::String(v) => v,
+                    _ => String::new(),
+                };
+                return (ProtoItem::from_mock(item), value, item.length());
+            }
         }
     }
 
-    /// Append to text of item after it has already been created.
-    pub fn append_text(self: &mut Self, text: &str) {
-        let to_add = CString::new(text).unwrap().into_raw();
-        unsafe {
-            proto::proto_item_append_text(self.item.into(), to_add);
-            let _ = CString::from_raw(to_add);
+    /// Add a variable length integer of at most length bytes, the encoding specifies the type of varint, for example
+    /// [`proto::Encoding::VARINT_PROTOBUF`]. Returns the value and the number of bytes it used, which is zero if the
+    /// integer doesn't end within length bytes.
+    pub fn add_item_ret_varint(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        encoding: proto::Encoding,
+    ) -> (ProtoItem, u64, usize) {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let mut retval: u64 = 0;
+                let mut lenretval: i32 = 0;
+                unsafe {
+                    return (
+                        ProtoItem::contain(|| {
+                            proto::proto_tree_add_item_ret_varint(
+                                tree,
+                                hfindex,
+                                tvb.ptr(),
+                                start as i32,
+                                length as i32,
+                                encoding,
+                                &mut retval as *mut u64,
+                                &mut lenretval as *mut i32,
+                            )
+                        }),
+                        retval,
+                        lenretval as usize,
+                    );
+                }
+            }
+            TreeBackend::Mock(node) => {
+                let (item, retval, used) = node.add_varint(hfindex, tvb.mock(), start, length, encoding);
+                return (ProtoItem::from_mock(item), retval, used);
+            }
         }
     }
 
-    /// Prepend to text of item after it has already been created.
-    pub fn prepend_text(self: &mut Self, text: &str) {
-        let to_add = CString::new(text).unwrap().into_raw();
-        unsafe {
-            proto::proto_item_prepend_text(self.item.into(), to_add);
-            let _ = CString::from_raw(to_add);
+    /// Add an unsigned integer item with a value that doesn't come from the buffer, start and length mark the bytes it
+    /// relates to.
+    pub fn add_uint(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: u32,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => unsafe {
+                ProtoItem::contain(|| {
+                    proto::proto_tree_add_uint(tree, hfindex, tvb.ptr(), start as i32, length as i32, value)
+                })
+            },
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                mock::UINT_TYPES,
+                mock::Value::UInt(value as u64),
+                None,
+            )),
         }
     }
 
-    pub fn add_subtree(self: &mut Self, ett_id: proto::ETTIndex) -> ProtoTree {
-        unsafe { ProtoTree::from_ptr(proto::proto_item_add_subtree(self.item.into(), ett_id)) }
+    /// Like [`ProtoTree::add_uint()`], but the value is displayed as the provided text instead.
+    pub fn add_uint_format_value(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: u32,
+        text: &str,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let text = util::c_string(text);
+                unsafe {
+                    ProtoItem::contain(|| {
+                        proto::proto_tree_add_uint_format_value(
+                            tree,
+                            hfindex,
+                            tvb.ptr(),
+                            start as i32,
+                            length as i32,
+                            value,
+                            FORMAT_STRING.as_ptr() as *const libc::c_char,
+                            text.as_ptr(),
+                        )
+                    })
+                }
+            }
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                mock::UINT_TYPES,
+                mock::Value::UInt(value as u64),
+                Some(text),
+            )),
+        }
     }
-}
-/// Struct to represent a Testy Virtual Buffer, serves as a wrapper around the `tvb_*` C functions.
-#[derive(Copy, Clone)]
-pub struct TVB {
-    tvb: *mut tvbuff::tvbuff_t,
-}
-impl TVB {
-    /// Create this structure from a raw pointer.
-    pub unsafe fn from_ptr(tvb: *mut tvbuff::tvbuff_t) -> TVB {
-        return TVB { tvb: tvb };
+
+    /// Add a signed integer item with a value that doesn't come from the buffer, start and length mark the bytes it
+    /// relates to.
+    pub fn add_int(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: i32,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => unsafe {
+                ProtoItem::contain(|| {
+                    proto::proto_tree_add_int(tree, hfindex, tvb.ptr(), start as i32, length as i32, value)
+                })
+            },
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                mock::INT_TYPES,
+                mock::Value::Int(value as i64),
+                None,
+            )),
+        }
     }
 
-    /// Function to create a byte slice that can be used to access the data from the tvb.
-    /// This comes with the following disclaimer in the header:
-    ///
-    /// This function is possibly expensive, temporarily allocating
-    /// another copy of the packet data. Furthermore, it's dangerous because once
-    /// this pointer is given to the user, there's no guarantee that the user will
-    /// honor the 'length' and not overstep the boundaries of the buffer.
-    ///
-    /// If you're thinking of using tvb_get_ptr, STOP WHAT YOU ARE DOING
-    /// IMMEDIATELY. Go take a break. Consider that tvb_get_ptr hands you
-    /// a raw, unprotected pointer that you can easily use to create a
-    /// security vulnerability or otherwise crash Wireshark. Then consider
-    /// that you can probably find a function elsewhere in this file that
-    /// does exactly what you want in a much more safe and robust manner.
-    pub fn tvb_get_ptr(&self, offset: usize) -> &[u8] {
-        unsafe {
-            let mut available_length = tvbuff::tvb_reported_length_remaining(self.tvb, offset as i32);
-            if available_length < 0 {
-                available_length = 0;
+    /// Like [`ProtoTree::add_int()`], but the value is displayed as the provided text instead.
+    pub fn add_int_format_value(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: i32,
+        text: &str,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let text = util::c_string(text);
+                unsafe {
+                    ProtoItem::contain(|| {
+                        proto::proto_tree_add_int_format_value(
+                            tree,
+                            hfindex,
+                            tvb.ptr(),
+                            start as i32,
+                            length as i32,
+                            value,
+                            FORMAT_STRING.as_ptr() as *const libc::c_char,
+                            text.as_ptr(),
+                        )
+                    })
+                }
             }
-            let data_ptr = tvbuff::tvb_get_ptr(self.tvb, offset as i32, available_length as i32);
-            return std::slice::from_raw_parts(data_ptr, available_length as usize);
-        };
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                mock::INT_TYPES,
+                mock::Value::Int(value as i64),
+                Some(text),
+            )),
+        }
     }
 
-    /// Get reported length of buffer.
-    pub fn reported_length(&self) -> usize {
-        unsafe {
-            return tvbuff::tvb_reported_length(self.tvb) as usize;
+    /// Add a 64 bit unsigned integer item with a value that doesn't come from the buffer, start and length mark the bytes it
+    /// relates to.
+    pub fn add_uint64(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: u64,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => unsafe {
+                ProtoItem::contain(|| {
+                    proto::proto_tree_add_uint64(tree, hfindex, tvb.ptr(), start as i32, length as i32, value)
+                })
+            },
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                mock::UINT64_TYPES,
+                mock::Value::UInt(value),
+                None,
+            )),
         }
     }
 
-    /// Computes bytes of reported packet data to end of buffer, from offset
-    /// (which can be negative, to indicate bytes from end of buffer). Function
-    /// returns 0 if offset is either at the end of the buffer or out of bounds.
-    /// No exception is thrown.
-    pub fn reported_length_remaining(&self, offset: usize) -> i32 {
-        unsafe {
-            return tvbuff::tvb_reported_length_remaining(self.tvb, offset as i32);
+    /// Like [`ProtoTree::add_uint64()`], but the value is displayed as the provided text instead.
+    pub fn add_uint64_format_value(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: u64,
+        text: &str,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let text = util::c_string(text);
+                unsafe {
+                    ProtoItem::contain(|| {
+                        proto::proto_tree_add_uint64_format_value(
+                            tree,
+                            hfindex,
+                            tvb.ptr(),
+                            start as i32,
+                            length as i32,
+                            value,
+                            FORMAT_STRING.as_ptr() as *const libc::c_char,
+                            text.as_ptr(),
+                        )
+                    })
+                }
+            }
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                mock::UINT64_TYPES,
+                mock::Value::UInt(value),
+                Some(text),
+            )),
         }
     }
 
-    /// Retrieve a block of memory from the buffer.
-    ///
-    /// Does not suffer from possible
-    /// expense of tvb_get_ptr(), since this routine is smart enough
-    /// to copy data in chunks if the request range actually exists in
-    /// different "real" tvbuffs.
-    pub fn get_mem(&self, offset: usize, length: usize) -> Vec<u8> {
-        let mut v: Vec<u8> = vec![0; length];
-        unsafe {
-            tvbuff::tvb_memcpy(self.tvb, v.as_mut_ptr() as *mut libc::c_void, offset as i32, length);
+    /// Add a 64 bit signed integer item with a value that doesn't come from the buffer, start and length mark the bytes it
+    /// relates to.
+    pub fn add_int64(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: i64,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => unsafe {
+                ProtoItem::contain(|| {
+                    proto::proto_tree_add_int64(tree, hfindex, tvb.ptr(), start as i32, length as i32, value)
+                })
+            },
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                mock::INT64_TYPES,
+                mock::Value::Int(value),
+                None,
+            )),
+        }
+    }
+
+    /// Like [`ProtoTree::add_int64()`], but the value is displayed as the provided text instead.
+    pub fn add_int64_format_value(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: i64,
+        text: &str,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let text = util::c_string(text);
+                unsafe {
+                    ProtoItem::contain(|| {
+                        proto::proto_tree_add_int64_format_value(
+                            tree,
+                            hfindex,
+                            tvb.ptr(),
+                            start as i32,
+                            length as i32,
+                            value,
+                            FORMAT_STRING.as_ptr() as *const libc::c_char,
+                            text.as_ptr(),
+                        )
+                    })
+                }
+            }
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                mock::INT64_TYPES,
+                mock::Value::Int(value),
+                Some(text),
+            )),
         }
-        return v;
     }
-}
 
-impl From<&mut TVB> for *mut tvbuff::tvbuff_t {
-    fn from(field: &mut TVB) -> Self {
-        return field.tvb;
+    /// Add a boolean item with a value that doesn't come from the buffer, start and length mark the bytes it
+    /// relates to.
+    pub fn add_boolean(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: bool,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => unsafe {
+                ProtoItem::contain(|| {
+                    proto::proto_tree_add_boolean(tree, hfindex, tvb.ptr(), start as i32, length as i32, value as _)
+                })
+            },
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                &[FieldType::BOOLEAN],
+                mock::Value::Bool(value),
+                None,
+            )),
+        }
     }
-}
-impl From<&TVB> for *const tvbuff::tvbuff_t {
-    fn from(field: &TVB) -> Self {
-        return field.tvb;
+
+    /// Like [`ProtoTree::add_boolean()`], but the value is displayed as the provided text instead.
+    pub fn add_boolean_format_value(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: bool,
+        text: &str,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let text = util::c_string(text);
+                unsafe {
+                    ProtoItem::contain(|| {
+                        proto::proto_tree_add_boolean_format_value(
+                            tree,
+                            hfindex,
+                            tvb.ptr(),
+                            start as i32,
+                            length as i32,
+                            value as _,
+                            FORMAT_STRING.as_ptr() as *const libc::c_char,
+                            text.as_ptr(),
+                        )
+                    })
+                }
+            }
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                &[FieldType::BOOLEAN],
+                mock::Value::Bool(value),
+                Some(text),
+            )),
+        }
+    }
+
+    /// Add a floating point item with a value that doesn't come from the buffer, start and length mark the bytes it
+    /// relates to.
+    pub fn add_double(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: f64,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => unsafe {
+                ProtoItem::contain(|| {
+                    proto::proto_tree_add_double(tree, hfindex, tvb.ptr(), start as i32, length as i32, value)
+                })
+            },
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                &[FieldType::DOUBLE],
+                mock::Value::Double(value),
+                None,
+            )),
+        }
+    }
+
+    /// Like [`ProtoTree::add_double()`], but the value is displayed as the provided text instead.
+    pub fn add_double_format_value(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: f64,
+        text: &str,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let text = util::c_string(text);
+                unsafe {
+                    ProtoItem::contain(|| {
+                        proto::proto_tree_add_double_format_value(
+                            tree,
+                            hfindex,
+                            tvb.ptr(),
+                            start as i32,
+                            length as i32,
+                            value,
+                            FORMAT_STRING.as_ptr() as *const libc::c_char,
+                            text.as_ptr(),
+                        )
+                    })
+                }
+            }
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                &[FieldType::DOUBLE],
+                mock::Value::Double(value),
+                Some(text),
+            )),
+        }
+    }
+
+    /// Add a string item with a value that doesn't come from the buffer, start and length mark the bytes it
+    /// relates to.
+    pub fn add_string(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: &str,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let value = util::c_string(value);
+                unsafe {
+                    ProtoItem::contain(|| {
+                        proto::proto_tree_add_string(
+                            tree,
+                            hfindex,
+                            tvb.ptr(),
+                            start as i32,
+                            length as i32,
+                            value.as_ptr(),
+                        )
+                    })
+                }
+            }
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                mock::STRING_TYPES,
+                mock::Value::String(value.to_owned()),
+                None,
+            )),
+        }
+    }
+
+    /// Like [`ProtoTree::add_string()`], but the value is displayed as the provided text instead.
+    pub fn add_string_format_value(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: &str,
+        text: &str,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let value = util::c_string(value);
+                let text = util::c_string(text);
+                unsafe {
+                    ProtoItem::contain(|| {
+                        proto::proto_tree_add_string_format_value(
+                            tree,
+                            hfindex,
+                            tvb.ptr(),
+                            start as i32,
+                            length as i32,
+                            value.as_ptr(),
+                            FORMAT_STRING.as_ptr() as *const libc::c_char,
+                            text.as_ptr(),
+                        )
+                    })
+                }
+            }
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                mock::STRING_TYPES,
+                mock::Value::String(value.to_owned()),
+                Some(text),
+            )),
+        }
+    }
+
+    /// Add a byte array item with a value that doesn't come from the buffer, start and length mark the bytes it
+    /// relates to.
+    pub fn add_bytes(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: &[u8],
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => unsafe {
+                ProtoItem::contain(|| {
+                    proto::proto_tree_add_bytes_with_length(
+                        tree,
+                        hfindex,
+                        tvb.ptr(),
+                        start as i32,
+                        length as i32,
+                        value.as_ptr(),
+                        value.len() as i32,
+                    )
+                })
+            },
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                mock::BYTES_TYPES,
+                mock::Value::Bytes(value.to_vec()),
+                None,
+            )),
+        }
+    }
+
+    /// Like [`ProtoTree::add_bytes()`], but the value is displayed as the provided text instead.
+    /// Panics if the value holds fewer than `length` bytes.
+    pub fn add_bytes_format_value(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: &[u8],
+        text: &str,
+    ) -> ProtoItem {
+        // Wireshark reads length bytes from the provided value, the mock backend keeps the same bytes.
+        assert!(
+            value.len() >= length,
+            "Value has {} bytes, need at least {}.",
+            value.len(),
+            length
+        );
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let text = util::c_string(text);
+                unsafe {
+                    ProtoItem::contain(|| {
+                        proto::proto_tree_add_bytes_format_value(
+                            tree,
+                            hfindex,
+                            tvb.ptr(),
+                            start as i32,
+                            length as i32,
+                            value.as_ptr(),
+                            FORMAT_STRING.as_ptr() as *const libc::c_char,
+                            text.as_ptr(),
+                        )
+                    })
+                }
+            }
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                mock::BYTES_TYPES,
+                mock::Value::Bytes(value[..length].to_vec()),
+                Some(text),
+            )),
+        }
+    }
+
+    /// Add an IPv4 address item with a value that doesn't come from the buffer, start and length mark the bytes it
+    /// relates to.
+    pub fn add_ipv4(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: std::net::Ipv4Addr,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => unsafe {
+                ProtoItem::contain(|| {
+                    proto::proto_tree_add_ipv4(
+                        tree,
+                        hfindex,
+                        tvb.ptr(),
+                        start as i32,
+                        length as i32,
+                        u32::from_ne_bytes(value.octets()),
+                    )
+                })
+            },
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                &[FieldType::IPv4],
+                mock::Value::Bytes(value.octets().to_vec()),
+                None,
+            )),
+        }
+    }
+
+    /// Like [`ProtoTree::add_ipv4()`], but the value is displayed as the provided text instead.
+    pub fn add_ipv4_format_value(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: std::net::Ipv4Addr,
+        text: &str,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let text = util::c_string(text);
+                unsafe {
+                    ProtoItem::contain(|| {
+                        proto::proto_tree_add_ipv4_format_value(
+                            tree,
+                            hfindex,
+                            tvb.ptr(),
+                            start as i32,
+                            length as i32,
+                            u32::from_ne_bytes(value.octets()),
+                            FORMAT_STRING.as_ptr() as *const libc::c_char,
+                            text.as_ptr(),
+                        )
+                    })
+                }
+            }
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                &[FieldType::IPv4],
+                mock::Value::Bytes(value.octets().to_vec()),
+                Some(text),
+            )),
+        }
+    }
+
+    /// Add an IPv6 address item with a value that doesn't come from the buffer, start and length mark the bytes it
+    /// relates to.
+    pub fn add_ipv6(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: std::net::Ipv6Addr,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let value = value.octets();
+                unsafe {
+                    ProtoItem::contain(|| {
+                        proto::proto_tree_add_ipv6(
+                            tree,
+                            hfindex,
+                            tvb.ptr(),
+                            start as i32,
+                            length as i32,
+                            value.as_ptr(),
+                        )
+                    })
+                }
+            }
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                &[FieldType::IPv6],
+                mock::Value::Bytes(value.octets().to_vec()),
+                None,
+            )),
+        }
+    }
+
+    /// Like [`ProtoTree::add_ipv6()`], but the value is displayed as the provided text instead.
+    pub fn add_ipv6_format_value(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: std::net::Ipv6Addr,
+        text: &str,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let value = value.octets();
+                let text = util::c_string(text);
+                unsafe {
+                    ProtoItem::contain(|| {
+                        proto::proto_tree_add_ipv6_format_value(
+                            tree,
+                            hfindex,
+                            tvb.ptr(),
+                            start as i32,
+                            length as i32,
+                            value.as_ptr(),
+                            FORMAT_STRING.as_ptr() as *const libc::c_char,
+                            text.as_ptr(),
+                        )
+                    })
+                }
+            }
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                &[FieldType::IPv6],
+                mock::Value::Bytes(value.octets().to_vec()),
+                Some(text),
+            )),
+        }
+    }
+
+    /// Add an ethernet address item with a value that doesn't come from the buffer, start and length mark the bytes it
+    /// relates to.
+    pub fn add_ether(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: &[u8; 6],
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => unsafe {
+                ProtoItem::contain(|| {
+                    proto::proto_tree_add_ether(tree, hfindex, tvb.ptr(), start as i32, length as i32, value.as_ptr())
+                })
+            },
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                &[FieldType::ETHER],
+                mock::Value::Bytes(value.to_vec()),
+                None,
+            )),
+        }
+    }
+
+    /// Like [`ProtoTree::add_ether()`], but the value is displayed as the provided text instead.
+    pub fn add_ether_format_value(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: &[u8; 6],
+        text: &str,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let text = util::c_string(text);
+                unsafe {
+                    ProtoItem::contain(|| {
+                        proto::proto_tree_add_ether_format_value(
+                            tree,
+                            hfindex,
+                            tvb.ptr(),
+                            start as i32,
+                            length as i32,
+                            value.as_ptr(),
+                            FORMAT_STRING.as_ptr() as *const libc::c_char,
+                            text.as_ptr(),
+                        )
+                    })
+                }
+            }
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                &[FieldType::ETHER],
+                mock::Value::Bytes(value.to_vec()),
+                Some(text),
+            )),
+        }
+    }
+
+    /// Add a GUID item with a value that doesn't come from the buffer, start and length mark the bytes it
+    /// relates to.
+    pub fn add_guid(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: &guid_utils::e_guid_t,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => unsafe {
+                ProtoItem::contain(|| {
+                    proto::proto_tree_add_guid(
+                        tree,
+                        hfindex,
+                        tvb.ptr(),
+                        start as i32,
+                        length as i32,
+                        value as *const guid_utils::e_guid_t,
+                    )
+                })
+            },
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                &[FieldType::GUID],
+                mock::Value::Bytes(value.to_bytes().to_vec()),
+                None,
+            )),
+        }
+    }
+
+    /// Like [`ProtoTree::add_guid()`], but the value is displayed as the provided text instead.
+    pub fn add_guid_format_value(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: &guid_utils::e_guid_t,
+        text: &str,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let text = util::c_string(text);
+                unsafe {
+                    ProtoItem::contain(|| {
+                        proto::proto_tree_add_guid_format_value(
+                            tree,
+                            hfindex,
+                            tvb.ptr(),
+                            start as i32,
+                            length as i32,
+                            value as *const guid_utils::e_guid_t,
+                            FORMAT_STRING.as_ptr() as *const libc::c_char,
+                            text.as_ptr(),
+                        )
+                    })
+                }
+            }
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                &[FieldType::GUID],
+                mock::Value::Bytes(value.to_bytes().to_vec()),
+                Some(text),
+            )),
+        }
+    }
+
+    /// Add a time item with a value that doesn't come from the buffer, start and length mark the bytes it
+    /// relates to.
+    pub fn add_time(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: &nstime::nstime_t,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => unsafe {
+                ProtoItem::contain(|| {
+                    proto::proto_tree_add_time(
+                        tree,
+                        hfindex,
+                        tvb.ptr(),
+                        start as i32,
+                        length as i32,
+                        value as *const nstime::nstime_t,
+                    )
+                })
+            },
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                mock::TIME_TYPES,
+                mock::Value::Time {
+                    secs: value.secs,
+                    nsecs: value.nsecs,
+                },
+                None,
+            )),
+        }
+    }
+
+    /// Like [`ProtoTree::add_time()`], but the value is displayed as the provided text instead.
+    pub fn add_time_format_value(
+        self: &mut Self,
+        hfindex: proto::HFIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        value: &nstime::nstime_t,
+        text: &str,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let text = util::c_string(text);
+                unsafe {
+                    ProtoItem::contain(|| {
+                        proto::proto_tree_add_time_format_value(
+                            tree,
+                            hfindex,
+                            tvb.ptr(),
+                            start as i32,
+                            length as i32,
+                            value as *const nstime::nstime_t,
+                            FORMAT_STRING.as_ptr() as *const libc::c_char,
+                            text.as_ptr(),
+                        )
+                    })
+                }
+            }
+            TreeBackend::Mock(node) => ProtoItem::from_mock(node.add_value(
+                hfindex,
+                tvb.mock(),
+                start,
+                length,
+                mock::TIME_TYPES,
+                mock::Value::Time {
+                    secs: value.secs,
+                    nsecs: value.nsecs,
+                },
+                Some(text),
+            )),
+        }
+    }
+
+    /// Add an item for the expert info to the tree, the item shows the summary the expert info was registered with and
+    /// the expert info is reported for the packet, even if there is no tree. Start and length mark the bytes it
+    /// relates to.
+    pub fn add_expert(
+        self: &mut Self,
+        pinfo: &mut PacketInfo,
+        eiindex: expert::EIIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let pinfo: *mut packet_info::packet_info = pinfo.into();
+                unsafe {
+                    ProtoItem::contain(|| {
+                        expert::proto_tree_add_expert(tree, pinfo, &eiindex, tvb.ptr(), start as i32, length as i32)
+                    })
+                }
+            }
+            TreeBackend::Mock(node) => {
+                let _ = pinfo;
+                ProtoItem::from_mock(node.add_expert(eiindex, tvb.mock(), start, length, None))
+            }
+        }
+    }
+
+    /// Like [`ProtoTree::add_expert()`], but the provided text is used instead of the summary, both for the item and
+    /// the expert info.
+    pub fn add_expert_format(
+        self: &mut Self,
+        pinfo: &mut PacketInfo,
+        eiindex: expert::EIIndex,
+        tvb: &TVB,
+        start: usize,
+        length: usize,
+        text: &str,
+    ) -> ProtoItem {
+        match self.tree {
+            #[cfg(feature = "wireshark")]
+            TreeBackend::Wireshark(tree) => {
+                let pinfo: *mut packet_info::packet_info = pinfo.into();
+                let text = util::c_string(text);
+                unsafe {
+                    ProtoItem::contain(|| {
+                        expert::proto_tree_add_expert_format(
+                            tree,
+                            pinfo,
+                            &eiindex,
+                            tvb.ptr(),
+                            start as i32,
+                            length as i32,
+                            FORMAT_STRING.as_ptr() as *const libc::c_char,
+                            text.as_ptr(),
+                        )
+                    })
+                }
+            }
+            TreeBackend::Mock(node) => {
+                let _ = pinfo;
+                ProtoItem::from_mock(node.add_expert(eiindex, tvb.mock(), start, length, Some(text)))
+            }
+        }
+    }
+
+    /// Function to retrieve all field info's currently associated with the protocol tree.
+    ///
+    /// The mock backend doesn't have any wireshark field info's, it always returns an empty vector.
+    #[cfg(feature = "wireshark")]
+    pub fn all_finfos(self: &mut Self) -> Vec<FieldInfo> {
+        let mut res: Vec<FieldInfo> = Vec::new();
+
+        let tree = match self.tree {
+            TreeBackend::Wireshark(tree) => tree,
+            TreeBackend::Mock(_) => return res,
+        };
+
+        // see wslua_field.c function wslua_all_field_infos
+        if tree.is_null()
+        // Not too sure when this happens... tree seems to be null when first invoked?
+        {
+            return res;
+        }
+        unsafe {
+            let fields = proto::proto_all_finfos(tree);
+            for i in 0..(*fields).len() {
+                let field =
+                    std::mem::transmute::<*mut libc::c_void, *const proto::field_info>((*fields).index(i as isize));
+                res.push(FieldInfo::from_ptr(field));
+            }
+            glib::g_ptr_array_free(fields, true);
+            // The field info's actually stay in scope, as they are part of the proto datastructure.
+            // the lua part also persists them after they're gone.
+        }
+        return res;
+    }
+}
+
+#[cfg(feature = "wireshark")]
+use std::ffi::CString;
+
+/// The backend a [`ProtoItem`] operates on, either wireshark itself, or the recording from the [`crate::mock`] module.
+#[derive(Copy, Clone)]
+enum ItemBackend {
+    #[cfg(feature = "wireshark")]
+    Wireshark(*mut proto::proto_item),
+    Mock(mock::NodeRef),
+}
+
+/// Struct to represent a protocol item, serves as a wrapper around the `proto_item_*` C functions.
+#[derive(Copy, Clone)]
+pub struct ProtoItem {
+    item: ItemBackend,
+}
+#[cfg(feature = "wireshark")]
+impl From<&mut ProtoItem> for *mut proto::proto_item {
+    /// Obtain the raw pointer, this is a null pointer for items from the mock backend.
+    fn from(field: &mut ProtoItem) -> Self {
+        match field.item {
+            ItemBackend::Wireshark(item) => item,
+            ItemBackend::Mock(_) => std::ptr::null_mut(),
+        }
+    }
+}
+
+impl ProtoItem {
+    /// Function to make this structure from a raw pointer.
+    #[cfg(feature = "wireshark")]
+    pub unsafe fn from_ptr(item: *mut proto::proto_item) -> ProtoItem {
+        return ProtoItem {
+            item: ItemBackend::Wireshark(item),
+        };
+    }
+
+    /// Make the item with the function that adds it to the tree. An exception thrown by the function is made pending
+    /// and results in a null item, which the wireshark functions accept and ignore.
+    #[cfg(feature = "wireshark")]
+    unsafe fn contain(f: impl FnOnce() -> *mut proto::proto_item) -> ProtoItem {
+        return ProtoItem::from_ptr(exceptions::contain(f).unwrap_or(std::ptr::null_mut()));
+    }
+
+    /// Make an item that records into the in-memory mock backend.
+    pub(crate) fn from_mock(node: mock::NodeRef) -> ProtoItem {
+        return ProtoItem {
+            item: ItemBackend::Mock(node),
+        };
+    }
+
+    /// Replace text of item after it already has been created.
+    pub fn set_text(self: &mut Self, text: &str) {
+        match self.item {
+            #[cfg(feature = "wireshark")]
+            ItemBackend::Wireshark(item) => {
+                let text = util::c_string(text);
+                unsafe {
+                    proto::proto_item_set_text(item, FORMAT_STRING.as_ptr() as *const libc::c_char, text.as_ptr());
+                }
+            }
+            ItemBackend::Mock(node) => node.set_text(text),
+        }
+    }
+
+    /// Append to text of item after it has already been created.
+    pub fn append_text(self: &mut Self, text: &str) {
+        match self.item {
+            #[cfg(feature = "wireshark")]
+            ItemBackend::Wireshark(item) => {
+                let text = util::c_string(text);
+                unsafe {
+                    proto::proto_item_append_text(item, FORMAT_STRING.as_ptr() as *const libc::c_char, text.as_ptr());
+                }
+            }
+            ItemBackend::Mock(node) => node.append_text(text),
+        }
+    }
+
+    /// Prepend to text of item after it has already been created.
+    pub fn prepend_text(self: &mut Self, text: &str) {
+        match self.item {
+            #[cfg(feature = "wireshark")]
+            ItemBackend::Wireshark(item) => {
+                let text = util::c_string(text);
+                unsafe {
+                    proto::proto_item_prepend_text(item, FORMAT_STRING.as_ptr() as *const libc::c_char, text.as_ptr());
+                }
+            }
+            ItemBackend::Mock(node) => node.prepend_text(text),
+        }
+    }
+
+    pub fn add_subtree(self: &mut Self, ett_id: proto::ETTIndex) -> ProtoTree {
+        match self.item {
+            #[cfg(feature = "wireshark")]
+            ItemBackend::Wireshark(item) => unsafe { ProtoTree::from_ptr(proto::proto_item_add_subtree(item, ett_id)) },
+            ItemBackend::Mock(node) => ProtoTree::from_mock(node.add_subtree(ett_id)),
+        }
+    }
+
+    /// Mark the item as generated, for values that don't come from the packet itself but are derived by the dissector,
+    /// like the frame of the matching request. Wireshark shows these between square brackets.
+    pub fn set_generated(self: &mut Self) {
+        match self.item {
+            #[cfg(feature = "wireshark")]
+            ItemBackend::Wireshark(item) => unsafe { proto::proto_item_set_generated(item) },
+            ItemBackend::Mock(node) => node.set_generated(),
+        }
+    }
+
+    /// Attach the expert info to this item, for example to flag the value of a field as invalid. This is reported for
+    /// the packet even if the item isn't in a tree.
+    pub fn add_expert(self: &mut Self, pinfo: &mut PacketInfo, eiindex: expert::EIIndex) {
+        match self.item {
+            #[cfg(feature = "wireshark")]
+            ItemBackend::Wireshark(item) => {
+                let pinfo: *mut packet_info::packet_info = pinfo.into();
+                exceptions::contain(|| unsafe { expert::expert_add_info(pinfo, item, &eiindex) });
+            }
+            ItemBackend::Mock(node) => {
+                let _ = pinfo;
+                node.add_expert_info(eiindex);
+            }
+        }
+    }
+}
+
+/// Errors from reading the buffer, these mirror the exceptions wireshark would throw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TVBError {
+    /// The read goes past the captured data, but not past the reported length; the packet was cut short.
+    BoundsError,
+    /// The read goes past the reported length; the packet is malformed.
+    ReportedBoundsError,
+    /// The data can't be decoded with the requested encoding, or the encoding isn't supported for this read.
+    DecodeError,
+}
+
+impl std::fmt::Display for TVBError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TVBError::BoundsError => write!(f, "read past the end of the captured data"),
+            TVBError::ReportedBoundsError => write!(f, "read past the end of the packet"),
+            TVBError::DecodeError => write!(f, "could not decode the data with this encoding"),
+        }
+    }
+}
+
+impl std::error::Error for TVBError {}
+
+/// The backend a [`TVB`] operates on, either a wireshark buffer, or a buffer from the [`crate::mock`] module.
+#[derive(Copy, Clone)]
+enum BufferBackend {
+    #[cfg(feature = "wireshark")]
+    Wireshark(*mut tvbuff::tvbuff_t),
+    Mock(mock::BufferRef),
+}
+
+/// Struct to represent a Testy Virtual Buffer, serves as a wrapper around the `tvb_*` C functions.
+///
+/// The lifetime is that of the packet the buffer belongs to; wireshark frees the buffer, its subsets and its children
+/// once the packet is done. Buffers derived from this one carry the same lifetime, so none of them can be kept past the
+/// dissection that handed out the first one.
+#[derive(Copy, Clone)]
+pub struct TVB<'a> {
+    tvb: BufferBackend,
+    packet: std::marker::PhantomData<&'a ()>,
+}
+impl<'a> TVB<'a> {
+    /// Create this structure from a raw pointer, the caller picks a lifetime that doesn't exceed that of the packet.
+    #[cfg(feature = "wireshark")]
+    pub unsafe fn from_ptr(tvb: *mut tvbuff::tvbuff_t) -> TVB<'a> {
+        return TVB {
+            tvb: BufferBackend::Wireshark(tvb),
+            packet: std::marker::PhantomData,
+        };
+    }
+
+    /// Make a buffer that is backed by the in-memory mock backend.
+    pub(crate) fn from_mock(buffer: mock::BufferRef) -> TVB<'a> {
+        return TVB {
+            tvb: BufferBackend::Mock(buffer),
+            packet: std::marker::PhantomData,
+        };
+    }
+
+    /// Obtain the raw pointer to hand to wireshark functions, panics if this is a mock buffer.
+    #[cfg(feature = "wireshark")]
+    fn ptr(&self) -> *mut tvbuff::tvbuff_t {
+        match self.tvb {
+            BufferBackend::Wireshark(tvb) => tvb,
+            BufferBackend::Mock(_) => panic!("Can't pass a mock TVB to wireshark."),
+        }
+    }
+
+    /// Obtain the mock buffer to hand to the mock backend, panics if this is a wireshark buffer.
+    fn mock(&self) -> mock::BufferRef {
+        match self.tvb {
+            #[cfg(feature = "wireshark")]
+            BufferBackend::Wireshark(_) => panic!("Can't pass a wireshark TVB to the mock backend."),
+            BufferBackend::Mock(buffer) => buffer,
+        }
+    }
+
+    /// Function to create a byte slice that can be used to access the data from the tvb.
+    /// This comes with the following disclaimer in the header:
+    ///
+    /// This function is possibly expensive, temporarily allocating
+    /// another copy of the packet data. Furthermore, it's dangerous because once
+    /// this pointer is given to the user, there's no guarantee that the user will
+    /// honor the 'length' and not overstep the boundaries of the buffer.
+    ///
+    /// If you're thinking of using tvb_get_ptr, STOP WHAT YOU ARE DOING
+    /// IMMEDIATELY. Go take a break. Consider that tvb_get_ptr hands you
+    /// a raw, unprotected pointer that you can easily use to create a
+    /// security vulnerability or otherwise crash Wireshark. Then consider
+    /// that you can probably find a function elsewhere in this file that
+    /// does exactly what you want in a much more safe and robust manner.
+    pub fn tvb_get_ptr(&self, offset: usize) -> &[u8] {
+        match self.tvb {
+            #[cfg(feature = "wireshark")]
+            BufferBackend::Wireshark(tvb) => unsafe {
+                let mut available_length = tvbuff::tvb_reported_length_remaining(tvb, offset as i32);
+                if available_length < 0 {
+                    available_length = 0;
+                }
+                match exceptions::contain(|| tvbuff::tvb_get_ptr(tvb, offset as i32, available_length as i32)) {
+                    Some(data_ptr) => return std::slice::from_raw_parts(data_ptr, available_length as usize),
+                    None => return &[],
+                }
+            },
+            BufferBackend::Mock(buffer) => &buffer.data()[offset.min(buffer.len())..],
+        }
+    }
+
+    /// Get reported length of buffer.
+    pub fn reported_length(&self) -> usize {
+        match self.tvb {
+            #[cfg(feature = "wireshark")]
+            BufferBackend::Wireshark(tvb) => unsafe {
+                return tvbuff::tvb_reported_length(tvb) as usize;
+            },
+            BufferBackend::Mock(buffer) => buffer.len(),
+        }
+    }
+
+    /// Computes bytes of reported packet data to end of buffer, from offset
+    /// (which can be negative, to indicate bytes from end of buffer). Function
+    /// returns 0 if offset is either at the end of the buffer or out of bounds.
+    /// No exception is thrown.
+    pub fn reported_length_remaining(&self, offset: usize) -> i32 {
+        match self.tvb {
+            #[cfg(feature = "wireshark")]
+            BufferBackend::Wireshark(tvb) => unsafe {
+                return tvbuff::tvb_reported_length_remaining(tvb, offset as i32);
+            },
+            BufferBackend::Mock(buffer) => buffer.len().saturating_sub(offset) as i32,
+        }
+    }
+
+    /// Retrieve a block of memory from the buffer.
+    ///
+    /// Does not suffer from possible
+    /// expense of tvb_get_ptr(), since this routine is smart enough
+    /// to copy data in chunks if the request range actually exists in
+    /// different "real" tvbuffs.
+    ///
+    /// If the range is out of bounds the exception is made pending and an empty vector is returned, use
+    /// [`TVB::try_get_mem()`] to handle the error instead.
+    pub fn get_mem(&self, offset: usize, length: usize) -> Vec<u8> {
+        match self.try_get_mem(offset, length) {
+            Ok(v) => return v,
+            Err(e) => {
+                exceptions::set_pending(e);
+                return Vec::new();
+            }
+        }
+    }
+
+    /// Like [`TVB::get_mem()`], but returns the exception if the range is out of bounds.
+    pub fn try_get_mem(&self, offset: usize, length: usize) -> Result<Vec<u8>, Exception> {
+        match self.tvb {
+            #[cfg(feature = "wireshark")]
+            BufferBackend::Wireshark(tvb) => {
+                let mut v: Vec<u8> = vec![0; length];
+                let data = v.as_mut_ptr() as *mut libc::c_void;
+                exceptions::catch(|| unsafe { tvbuff::tvb_memcpy(tvb, data, offset as i32, length) })?;
+                return Ok(v);
+            }
+            BufferBackend::Mock(buffer) => {
+                if let Some(exception) = exceptions::pending() {
+                    return Err(exception);
+                }
+                return Ok(buffer.get(offset, length)?.to_vec());
+            }
+        }
+    }
+
+    /// Get the length of the captured data in the buffer, this can be less than the reported length if the packet
+    /// was cut short during capture.
+    pub fn captured_length(&self) -> usize {
+        match self.tvb {
+            #[cfg(feature = "wireshark")]
+            BufferBackend::Wireshark(tvb) => unsafe {
+                return tvbuff::tvb_captured_length(tvb) as usize;
+            },
+            BufferBackend::Mock(buffer) => buffer.len(),
+        }
+    }
+
+    /// A buffer for length bytes of this one, starting at offset. It shares the data and items added to it are
+    /// highlighted in the right place in the bytes pane. Returns the error wireshark would throw if the range isn't
+    /// part of the packet; if it's only partly captured, the buffer holds the part that was captured.
+    pub fn subset(&self, offset: usize, length: usize) -> Result<TVB<'a>, TVBError> {
+        match offset.checked_add(length) {
+            Some(end) if end <= self.reported_length() => {}
+            _ => return Err(TVBError::ReportedBoundsError),
+        }
+        self.ensure_bytes_exist(offset, 0)?;
+        match self.tvb {
+            #[cfg(feature = "wireshark")]
+            BufferBackend::Wireshark(tvb) => unsafe {
+                // Can't throw, the range was checked above.
+                let captured = length.min(self.captured_length() - offset);
+                let subset = tvbuff::tvb_new_subset_length_caplen(tvb, offset as i32, captured as i32, length as i32);
+                return Ok(TVB::from_ptr(subset));
+            },
+            BufferBackend::Mock(buffer) => return Ok(TVB::from_mock(buffer.subset(offset, length))),
+        }
+    }
+
+    /// A buffer for the rest of this one, starting at offset. See [`TVB::subset()`].
+    pub fn subset_remaining(&self, offset: usize) -> Result<TVB<'a>, TVBError> {
+        self.ensure_bytes_exist(offset, 0)?;
+        match self.tvb {
+            #[cfg(feature = "wireshark")]
+            BufferBackend::Wireshark(tvb) => unsafe {
+                // Can't throw, the offset was checked above.
+                return Ok(TVB::from_ptr(tvbuff::tvb_new_subset_remaining(tvb, offset as i32)));
+            },
+            BufferBackend::Mock(buffer) => return Ok(TVB::from_mock(buffer.subset(offset, buffer.len()))),
+        }
+    }
+
+    /// A buffer with new data, for example decompressed, decrypted or de-escaped from this buffer. The data is moved
+    /// to wireshark, which frees it together with this buffer once the packet is done, so the child has the lifetime of
+    /// this buffer. Add it with [`PacketInfo::add_new_data_source()`] to show it in the bytes pane.
+    pub fn new_child_real_data(&self, data: Vec<u8>) -> TVB<'a> {
+        match self.tvb {
+            #[cfg(feature = "wireshark")]
+            BufferBackend::Wireshark(tvb) => unsafe {
+                // Wireshark releases the data with the free callback, so it must come from g_malloc.
+                let real_data = glib::g_malloc(data.len().max(1)) as *mut u8;
+                std::ptr::copy_nonoverlapping(data.as_ptr(), real_data, data.len());
+                let child = tvbuff::tvb_new_child_real_data(tvb, real_data, data.len() as u32, data.len() as i32);
+                tvbuff::tvb_set_free_cb(child, Some(glib::g_free));
+                return TVB::from_ptr(child);
+            },
+            BufferBackend::Mock(buffer) => return TVB::from_mock(buffer.new_child(data)),
+        }
+    }
+
+    /// Check whether length bytes can be read at offset, returns the error wireshark would throw if not.
+    pub fn ensure_bytes_exist(&self, offset: usize, length: usize) -> Result<(), TVBError> {
+        let end = match offset.checked_add(length) {
+            Some(end) => end,
+            None => return Err(TVBError::ReportedBoundsError),
+        };
+        if end <= self.captured_length() {
+            return Ok(());
+        }
+        if end <= self.reported_length() {
+            return Err(TVBError::BoundsError);
+        }
+        return Err(TVBError::ReportedBoundsError);
+    }
+
+    /// Retrieve N bytes from the buffer, after checking they exist.
+    fn get_array<const N: usize>(&self, offset: usize) -> Result<[u8; N], TVBError> {
+        self.ensure_bytes_exist(offset, N)?;
+        let mut v = [0u8; N];
+        match self.tvb {
+            #[cfg(feature = "wireshark")]
+            BufferBackend::Wireshark(tvb) => unsafe {
+                // Can't throw, the range was checked above.
+                tvbuff::tvb_memcpy(tvb, v.as_mut_ptr() as *mut libc::c_void, offset as i32, N);
+            },
+            BufferBackend::Mock(buffer) => v.copy_from_slice(buffer.get(offset, N).unwrap()),
+        }
+        return Ok(v);
+    }
+
+    /// Retrieve an unsigned integer of N bytes, the encoding specifies the endianness.
+    fn get_uint<const N: usize>(&self, offset: usize, encoding: proto::Encoding) -> Result<u64, TVBError> {
+        let bytes = self.get_array::<N>(offset)?;
+        let little_endian = (encoding as u32) & (proto::Encoding::LITTLE_ENDIAN as u32) != 0;
+        let mut v: u64 = 0;
+        for i in 0..N {
+            let byte = if little_endian { bytes[N - 1 - i] } else { bytes[i] };
+            v = (v << 8) | byte as u64;
+        }
+        return Ok(v);
+    }
+
+    /// Retrieve an 8 bit unsigned integer.
+    pub fn get_u8(&self, offset: usize) -> Result<u8, TVBError> {
+        return Ok(self.get_array::<1>(offset)?[0]);
+    }
+
+    /// Retrieve a 16 bit unsigned integer, the encoding specifies the endianness.
+    pub fn get_u16(&self, offset: usize, encoding: proto::Encoding) -> Result<u16, TVBError> {
+        return Ok(self.get_uint::<2>(offset, encoding)? as u16);
+    }
+
+    /// Retrieve a 24 bit unsigned integer, the encoding specifies the endianness.
+    pub fn get_u24(&self, offset: usize, encoding: proto::Encoding) -> Result<u32, TVBError> {
+        return Ok(self.get_uint::<3>(offset, encoding)? as u32);
+    }
+
+    /// Retrieve a 32 bit unsigned integer, the encoding specifies the endianness.
+    pub fn get_u32(&self, offset: usize, encoding: proto::Encoding) -> Result<u32, TVBError> {
+        return Ok(self.get_uint::<4>(offset, encoding)? as u32);
+    }
+
+    /// Retrieve a 64 bit unsigned integer, the encoding specifies the endianness.
+    pub fn get_u64(&self, offset: usize, encoding: proto::Encoding) -> Result<u64, TVBError> {
+        return self.get_uint::<8>(offset, encoding);
+    }
+
+    /// Retrieve an 8 bit signed integer.
+    pub fn get_i8(&self, offset: usize) -> Result<i8, TVBError> {
+        return Ok(self.get_u8(offset)? as i8);
+    }
+
+    /// Retrieve a 16 bit signed integer, the encoding specifies the endianness.
+    pub fn get_i16(&self, offset: usize, encoding: proto::Encoding) -> Result<i16, TVBError> {
+        return Ok(self.get_u16(offset, encoding)? as i16);
+    }
+
+    /// Retrieve a 24 bit signed integer, the encoding specifies the endianness. The value is sign extended.
+    pub fn get_i24(&self, offset: usize, encoding: proto::Encoding) -> Result<i32, TVBError> {
+        return Ok(((self.get_u24(offset, encoding)? << 8) as i32) >> 8);
+    }
+
+    /// Retrieve a 32 bit signed integer, the encoding specifies the endianness.
+    pub fn get_i32(&self, offset: usize, encoding: proto::Encoding) -> Result<i32, TVBError> {
+        return Ok(self.get_u32(offset, encoding)? as i32);
+    }
+
+    /// Retrieve a 64 bit signed integer, the encoding specifies the endianness.
+    pub fn get_i64(&self, offset: usize, encoding: proto::Encoding) -> Result<i64, TVBError> {
+        return Ok(self.get_u64(offset, encoding)? as i64);
+    }
+
+    /// Retrieve a single precision IEEE 754 floating point value, the encoding specifies the endianness.
+    pub fn get_f32(&self, offset: usize, encoding: proto::Encoding) -> Result<f32, TVBError> {
+        return Ok(f32::from_bits(self.get_u32(offset, encoding)?));
+    }
+
+    /// Retrieve a double precision IEEE 754 floating point value, the encoding specifies the endianness.
+    pub fn get_f64(&self, offset: usize, encoding: proto::Encoding) -> Result<f64, TVBError> {
+        return Ok(f64::from_bits(self.get_u64(offset, encoding)?));
+    }
+
+    /// Retrieve a GUID, the encoding specifies the endianness of the first three integers.
+    pub fn get_guid(&self, offset: usize, encoding: proto::Encoding) -> Result<guid_utils::e_guid_t, TVBError> {
+        let mut guid = guid_utils::e_guid_t::from(self.get_array::<16>(offset)?);
+        if (encoding as u32) & (proto::Encoding::LITTLE_ENDIAN as u32) != 0 {
+            guid.data1 = guid.data1.swap_bytes();
+            guid.data2 = guid.data2.swap_bytes();
+            guid.data3 = guid.data3.swap_bytes();
+        }
+        return Ok(guid);
+    }
+
+    /// Retrieve a string of length bytes, the encoding specifies the character encoding. The string is converted to
+    /// UTF-8 and ends at the first null character. Wireshark throws for encodings it can't decode, this is returned
+    /// as [`TVBError::DecodeError`].
+    pub fn get_string(&self, offset: usize, length: usize, encoding: proto::Encoding) -> Result<String, TVBError> {
+        self.ensure_bytes_exist(offset, length)?;
+        match self.tvb {
+            #[cfg(feature = "wireshark")]
+            BufferBackend::Wireshark(tvb) => {
+                // Without an allocator the string is allocated with g_malloc, we copy it and free it again.
+                let ptr = exceptions::catch(|| unsafe {
+                    tvbuff::tvb_get_string_enc(std::ptr::null_mut(), tvb, offset as i32, length as i32, encoding)
+                })?;
+                unsafe {
+                    let value = std::ffi::CStr::from_ptr(ptr as *const libc::c_char)
+                        .to_string_lossy()
+                        .into_owned();
+                    wmem::wmem_free(std::ptr::null_mut(), ptr as *mut libc::c_void);
+                    return Ok(value);
+                }
+            }
+            BufferBackend::Mock(buffer) => {
+                mock::get_string(buffer.get(offset, length).unwrap(), encoding).ok_or(TVBError::DecodeError)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "wireshark")]
+impl From<&mut TVB<'_>> for *mut tvbuff::tvbuff_t {
+    /// Obtain the raw pointer, this is a null pointer for buffers from the mock backend.
+    fn from(field: &mut TVB<'_>) -> Self {
+        match field.tvb {
+            BufferBackend::Wireshark(tvb) => tvb,
+            BufferBackend::Mock(_) => std::ptr::null_mut(),
+        }
+    }
+}
+#[cfg(feature = "wireshark")]
+impl From<&TVB<'_>> for *const tvbuff::tvbuff_t {
+    /// Obtain the raw pointer, this is a null pointer for buffers from the mock backend.
+    fn from(field: &TVB<'_>) -> Self {
+        match field.tvb {
+            BufferBackend::Wireshark(tvb) => tvb,
+            BufferBackend::Mock(_) => std::ptr::null(),
+        }
+    }
+}
+
+/// The columns in the packet list that a dissector can write to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Column {
+    /// The protocol column, usually set to the short name of the protocol.
+    Protocol,
+    /// The info column, with a summary of the packet.
+    Info,
+}
+
+impl Column {
+    /// The id wireshark uses for this column.
+    #[cfg(feature = "wireshark")]
+    fn id(self) -> libc::c_int {
+        match self {
+            Column::Protocol => column_utils::col_protocol(),
+            Column::Info => column_utils::col_info(),
+        }
+    }
+}
+
+/// A source or destination address of a packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Address {
+    /// There is no address.
+    None,
+    /// An ethernet MAC address.
+    Ether([u8; 6]),
+    /// An IPv4 address.
+    IPv4(std::net::Ipv4Addr),
+    /// An IPv6 address.
+    IPv6(std::net::Ipv6Addr),
+    /// Any other address type, with the raw data of the address.
+    Other { address_type: i32, data: Vec<u8> },
+}
+
+impl Address {
+    /// Convert the address from wireshark, copying the data.
+    #[cfg(feature = "wireshark")]
+    unsafe fn from_raw(raw: &address::address) -> Address {
+        use std::convert::TryFrom;
+        let data: &[u8] = if raw.data.is_null() || raw.len <= 0 {
+            &[]
+        } else {
+            std::slice::from_raw_parts(raw.data as *const u8, raw.len as usize)
+        };
+        return match raw.type_ {
+            address::AT_NONE => Address::None,
+            address::AT_ETHER if data.len() == 6 => Address::Ether(<[u8; 6]>::try_from(data).unwrap()),
+            address::AT_IPv4 if data.len() == 4 => {
+                Address::IPv4(std::net::Ipv4Addr::new(data[0], data[1], data[2], data[3]))
+            }
+            address::AT_IPv6 if data.len() == 16 => Address::IPv6(<[u8; 16]>::try_from(data).unwrap().into()),
+            address_type => Address::Other {
+                address_type,
+                data: data.to_vec(),
+            },
+        };
+    }
+}
+
+/// The direction of peer to peer traffic, for protocols where the capture point is one of the peers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum P2PDirection {
+    /// The direction is not known.
+    Unknown,
+    /// The packet was sent by the capturing peer.
+    Sent,
+    /// The packet was received by the capturing peer.
+    Received,
+}
+
+/// The backend a [`PacketInfo`] operates on, either wireshark itself, or the recording from the [`crate::mock`] module.
+#[derive(Copy, Clone)]
+enum InfoBackend {
+    #[cfg(feature = "wireshark")]
+    Wireshark(*mut packet_info::packet_info),
+    Mock(mock::InfoRef),
+}
+
+/// Struct to represent the information about the packet being dissected, serves as a wrapper around `packet_info` and
+/// the `col_*` C functions.
+///
+/// Like the tree, the columns are not changed anymore once an exception is pending.
+#[derive(Copy, Clone)]
+pub struct PacketInfo {
+    info: InfoBackend,
+}
+
+impl PacketInfo {
+    /// Function to make this structure from a raw pointer.
+    #[cfg(feature = "wireshark")]
+    pub unsafe fn from_ptr(info: *mut packet_info::packet_info) -> PacketInfo {
+        return PacketInfo {
+            info: InfoBackend::Wireshark(info),
+        };
+    }
+
+    /// Make packet info that records into the in-memory mock backend.
+    pub(crate) fn from_mock(info: mock::InfoRef) -> PacketInfo {
+        return PacketInfo {
+            info: InfoBackend::Mock(info),
+        };
+    }
+
+    /// Obtain the packet info to hand to the mock backend, panics if this is wireshark's packet info.
+    fn mock(&self) -> mock::InfoRef {
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(_) => panic!("Can't pass wireshark packet info to the mock backend."),
+            InfoBackend::Mock(info) => info,
+        }
+    }
+
+    /// The column info to hand to the col_* functions, may be null, which they accept.
+    #[cfg(feature = "wireshark")]
+    unsafe fn cinfo(info: *mut packet_info::packet_info) -> *mut column_utils::column_info {
+        if info.is_null() {
+            return std::ptr::null_mut();
+        }
+        return (*info).cinfo;
+    }
+
+    /// Set the column to a static string, like the protocol name. Wireshark keeps a pointer to the string instead of a
+    /// copy, the C string for it is made the first time and reused for the packets after that.
+    pub fn col_set_str(self: &mut Self, column: Column, text: &'static str) {
+        if exceptions::pending().is_some() {
+            return;
+        }
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => unsafe {
+                // The string has to persist, the permanent C string is looked up by the address of the text.
+                column_utils::col_set_str(PacketInfo::cinfo(info), column.id(), util::static_string_ptr(text));
+            },
+            InfoBackend::Mock(info) => info.set_column(column, text),
+        }
+    }
+
+    /// Set the column to a copy of the text, replacing what was there after the fence.
+    pub fn col_add_str(self: &mut Self, column: Column, text: &str) {
+        if exceptions::pending().is_some() {
+            return;
+        }
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => {
+                let text = util::c_string(text);
+                unsafe {
+                    column_utils::col_add_str(PacketInfo::cinfo(info), column.id(), text.as_ptr());
+                }
+            }
+            InfoBackend::Mock(info) => info.set_column(column, text),
+        }
+    }
+
+    /// Append the text to the column.
+    pub fn col_append_str(self: &mut Self, column: Column, text: &str) {
+        if exceptions::pending().is_some() {
+            return;
+        }
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => {
+                let text = util::c_string(text);
+                unsafe {
+                    column_utils::col_append_str(PacketInfo::cinfo(info), column.id(), text.as_ptr());
+                }
+            }
+            InfoBackend::Mock(info) => info.append_column(column, None, text),
+        }
+    }
+
+    /// Append the text to the column, preceded by the separator if the column isn't empty.
+    pub fn col_append_sep_str(self: &mut Self, column: Column, separator: &str, text: &str) {
+        if exceptions::pending().is_some() {
+            return;
+        }
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => {
+                let separator = util::c_string(separator);
+                let text = util::c_string(text);
+                unsafe {
+                    column_utils::col_append_sep_str(
+                        PacketInfo::cinfo(info),
+                        column.id(),
+                        separator.as_ptr(),
+                        text.as_ptr(),
+                    );
+                }
+            }
+            InfoBackend::Mock(info) => info.append_column(column, Some(separator), text),
+        }
+    }
+
+    /// Clear the column, text before the fence is kept.
+    pub fn col_clear(self: &mut Self, column: Column) {
+        if exceptions::pending().is_some() {
+            return;
+        }
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => unsafe {
+                column_utils::col_clear(PacketInfo::cinfo(info), column.id());
+            },
+            InfoBackend::Mock(info) => info.clear_column(column),
+        }
+    }
+
+    /// Put a fence at the end of the current text, dissectors called after this one can only append to it.
+    pub fn col_set_fence(self: &mut Self, column: Column) {
+        if exceptions::pending().is_some() {
+            return;
+        }
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => unsafe {
+                column_utils::col_set_fence(PacketInfo::cinfo(info), column.id());
+            },
+            InfoBackend::Mock(info) => info.set_column_fence(column),
+        }
+    }
+
+    /// The number of the frame this packet is in, starting at 1.
+    pub fn number(self: &Self) -> u32 {
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => unsafe { (*info).num },
+            InfoBackend::Mock(info) => info.packet().number,
+        }
+    }
+
+    /// Whether this packet was dissected before. This is false on the first pass through the capture, which is where
+    /// state that depends on earlier packets should be built up.
+    pub fn visited(self: &Self) -> bool {
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => unsafe { frame_data::pinfo_fd_visited(info) },
+            InfoBackend::Mock(info) => info.packet().visited,
+        }
+    }
+
+    /// The time the packet was captured, since the epoch.
+    pub fn abs_ts(self: &Self) -> nstime::nstime_t {
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => unsafe { (*info).abs_ts },
+            InfoBackend::Mock(info) => info.packet().abs_ts,
+        }
+    }
+
+    /// The time the packet was captured, relative to the first packet in the capture.
+    pub fn rel_ts(self: &Self) -> nstime::nstime_t {
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => unsafe { (*info).rel_ts },
+            InfoBackend::Mock(info) => info.packet().rel_ts,
+        }
+    }
+
+    /// The source address, this is the network layer address if there is one, the link layer address otherwise.
+    pub fn src(self: &Self) -> Address {
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => unsafe { Address::from_raw(&(*info).src) },
+            InfoBackend::Mock(info) => info.packet().src,
+        }
+    }
+
+    /// The destination address, like [`PacketInfo::src()`].
+    pub fn dst(self: &Self) -> Address {
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => unsafe { Address::from_raw(&(*info).dst) },
+            InfoBackend::Mock(info) => info.packet().dst,
+        }
+    }
+
+    /// The source port, for example the TCP or UDP port.
+    pub fn src_port(self: &Self) -> u32 {
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => unsafe { (*info).srcport },
+            InfoBackend::Mock(info) => info.packet().src_port,
+        }
+    }
+
+    /// The destination port.
+    pub fn dst_port(self: &Self) -> u32 {
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => unsafe { (*info).destport },
+            InfoBackend::Mock(info) => info.packet().dst_port,
+        }
+    }
+
+    /// The direction of the packet, for peer to peer captures.
+    pub fn p2p_dir(self: &Self) -> P2PDirection {
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => match unsafe { (*info).p2p_dir } {
+                packet_info::P2P_DIR_SENT => P2PDirection::Sent,
+                packet_info::P2P_DIR_RECV => P2PDirection::Received,
+                _ => P2PDirection::Unknown,
+            },
+            InfoBackend::Mock(info) => info.packet().p2p_dir,
+        }
+    }
+
+    /// Attach the value to this packet under the key, replacing the value of the same type attached before. Data
+    /// attached on the first pass is still there when the packet is dissected again, which happens in arbitrary order,
+    /// so this is where results that depend on earlier packets should be kept. Each dissector has its own keys, the
+    /// data is dropped when the capture file is closed. Fails if the data under this key is being accessed already.
+    pub fn set_proto_data<T: 'static>(self: &mut Self, key: u32, value: T) -> Result<(), DataError> {
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => unsafe {
+                match PacketInfo::proto_data(info, key, true) {
+                    Some(data) => return data.set(value),
+                    None => return Ok(()),
+                }
+            },
+            InfoBackend::Mock(info) => info.packet_data(key).set(value),
+        }
+    }
+
+    /// Show the buffer as its own tab in the bytes pane, with the name as title. Items added to the buffer are
+    /// highlighted in that tab.
+    pub fn add_new_data_source(self: &mut Self, name: &str, tvb: &TVB) {
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => unsafe {
+                let name = util::c_string(name);
+                packet::add_new_data_source(info, tvb.ptr(), name.as_ptr());
+            },
+            InfoBackend::Mock(info) => info.add_data_source(name, tvb.mock()),
+        }
+    }
+
+    /// Retrieve a copy of the value of type T attached to this packet under the key, None if there is no such value.
+    /// Fails if the data under this key is being accessed already, for example by the `Clone` implementation of T.
+    pub fn get_proto_data<T: 'static + Clone>(self: &Self, key: u32) -> Result<Option<T>, DataError> {
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => unsafe {
+                match PacketInfo::proto_data(info, key, false) {
+                    Some(data) => return data.with(|v: &mut T| v.clone()),
+                    None => return Ok(None),
+                }
+            },
+            InfoBackend::Mock(info) => info.packet_data(key).with(|v: &mut T| v.clone()),
+        }
+    }
+
+    /// The container with our data for this key, created if asked for. It's stored under the protocol id of the
+    /// dissector that is running.
+    #[cfg(feature = "wireshark")]
+    unsafe fn proto_data(info: *mut packet_info::packet_info, key: u32, create: bool) -> Option<&'static ProtoData> {
+        let proto_id = crate::plugin::proto_id();
+        let scope = wmem::wmem_file_scope();
+        let mut data = proto_data::p_get_proto_data(scope, info, proto_id, key) as *mut ProtoData;
+        if data.is_null() && create {
+            data = ProtoData::file_scoped();
+            proto_data::p_add_proto_data(scope, info, proto_id, key, data as *mut libc::c_void);
+        }
+        return data.as_ref();
+    }
+
+    /// The value in the dissector table that caused this dissector to be called, for example the port for
+    /// [`crate::dissector::Registration::UInt`].
+    pub fn match_uint(self: &Self) -> u32 {
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => unsafe { (*info).match_uint },
+            InfoBackend::Mock(info) => info.packet().match_uint,
+        }
+    }
+
+    /// The string in the dissector table that caused this dissector to be called, for
+    /// [`crate::dissector::Registration::String`]. None if the dissector wasn't called from a string table.
+    pub fn match_string(self: &Self) -> Option<String> {
+        match self.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => unsafe {
+                use std::ffi::CStr;
+                let value = (*info).match_string;
+                if value.is_null() {
+                    return None;
+                }
+                return Some(CStr::from_ptr(value).to_string_lossy().into_owned());
+            },
+            InfoBackend::Mock(info) => info.packet().match_string,
+        }
+    }
+}
+
+#[cfg(feature = "wireshark")]
+impl From<&mut PacketInfo> for *mut packet_info::packet_info {
+    /// Obtain the raw pointer, this is a null pointer for packet info from the mock backend.
+    fn from(field: &mut PacketInfo) -> Self {
+        match field.info {
+            InfoBackend::Wireshark(info) => info,
+            InfoBackend::Mock(_) => std::ptr::null_mut(),
+        }
+    }
+}
+
+/// The value of a preference, see [`dissector::PreferenceKind`] for what each type is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreferenceValue {
+    UInt(u32),
+    Bool(bool),
+    String(String),
+    Enum(i32),
+    Range(Vec<(u32, u32)>),
+    Filename(String),
+}
+
+impl From<&dissector::PreferenceKind> for PreferenceValue {
+    /// The default value of the preference.
+    fn from(kind: &dissector::PreferenceKind) -> Self {
+        match kind {
+            dissector::PreferenceKind::UInt { default, .. } => PreferenceValue::UInt(*default),
+            dissector::PreferenceKind::Bool { default } => PreferenceValue::Bool(*default),
+            dissector::PreferenceKind::String { default } => PreferenceValue::String(default.clone()),
+            dissector::PreferenceKind::Enum { default, .. } => PreferenceValue::Enum(*default),
+            dissector::PreferenceKind::Range { default, .. } => PreferenceValue::Range(default.clone()),
+            dissector::PreferenceKind::Filename { default, .. } => PreferenceValue::Filename(default.clone()),
+        }
+    }
+}
+
+/// The variable wireshark writes the value of a preference to. These are allocated once during registration and live
+/// as long as the plugin.
+#[cfg(feature = "wireshark")]
+enum PreferenceVariable {
+    UInt(*mut libc::c_uint),
+    // A gboolean before 4.2, a bool from then on.
+    #[cfg(not(ws_4_2))]
+    Bool(*mut libc::c_int),
+    #[cfg(ws_4_2)]
+    Bool(*mut bool),
+    String(*mut *const libc::c_char),
+    Enum(*mut libc::c_int),
+    Range(*mut *mut range::range_t),
+    Filename(*mut *const libc::c_char),
+}
+
+#[cfg(feature = "wireshark")]
+impl PreferenceVariable {
+    unsafe fn string(ptr: *const libc::c_char) -> String {
+        if ptr.is_null() {
+            return String::new();
+        }
+        return std::ffi::CStr::from_ptr(ptr).to_string_lossy().into_owned();
+    }
+
+    /// Read the current value.
+    unsafe fn value(self: &Self) -> PreferenceValue {
+        match *self {
+            PreferenceVariable::UInt(var) => PreferenceValue::UInt(*var),
+            #[cfg(not(ws_4_2))]
+            PreferenceVariable::Bool(var) => PreferenceValue::Bool(*var != 0),
+            #[cfg(ws_4_2)]
+            PreferenceVariable::Bool(var) => PreferenceValue::Bool(*var),
+            PreferenceVariable::String(var) => PreferenceValue::String(PreferenceVariable::string(*var)),
+            PreferenceVariable::Enum(var) => PreferenceValue::Enum(*var),
+            PreferenceVariable::Range(var) => {
+                let range = *var;
+                if range.is_null() {
+                    return PreferenceValue::Range(Vec::new());
+                }
+                let ranges = std::slice::from_raw_parts((*range).ranges.as_ptr(), (*range).nranges as usize);
+                PreferenceValue::Range(ranges.iter().map(|r| (r.low, r.high)).collect())
+            }
+            PreferenceVariable::Filename(var) => PreferenceValue::Filename(PreferenceVariable::string(*var)),
+        }
+    }
+}
+
+/// The backend a [`Preferences`] operates on, either wireshark itself, or the values from the [`crate::mock`] module.
+#[derive(Clone)]
+enum PreferencesBackend {
+    #[cfg(feature = "wireshark")]
+    Wireshark(std::rc::Rc<Vec<(String, PreferenceVariable)>>),
+    Mock(mock::PreferencesRef),
+}
+
+/// Provides the current values of the preferences a dissector registered, these are looked up by name.
+#[derive(Clone)]
+pub struct Preferences {
+    prefs: PreferencesBackend,
+}
+
+impl Preferences {
+    /// Register the preferences with the module, the returned object reads the values wireshark stores.
+    #[cfg(feature = "wireshark")]
+    pub(crate) unsafe fn register(module: *mut prefs::module_t, preferences: &[dissector::Preference]) -> Preferences {
+        // Wireshark keeps pointers to everything we pass in, so the names and variables are never freed.
+        fn var<T>(value: T) -> *mut T {
+            return Box::into_raw(Box::new(value));
+        }
+        let mut variables = Vec::new();
+        for preference in preferences.iter() {
+            let name = util::perm_string_ptr(preference.name.as_str());
+            let title = util::perm_string_ptr(preference.title.as_str());
+            let description = util::perm_string_ptr(preference.description.as_str());
+            let variable = match &preference.kind {
+                dissector::PreferenceKind::UInt { default, base } => {
+                    let v = var(*default as libc::c_uint);
+                    prefs::prefs_register_uint_preference(module, name, title, description, *base, v);
+                    PreferenceVariable::UInt(v)
+                }
+                dissector::PreferenceKind::Bool { default } => {
+                    #[cfg(not(ws_4_2))]
+                    let v = var(*default as libc::c_int);
+                    #[cfg(ws_4_2)]
+                    let v = var(*default);
+                    prefs::prefs_register_bool_preference(module, name, title, description, v);
+                    PreferenceVariable::Bool(v)
+                }
+                dissector::PreferenceKind::String { default } => {
+                    let v = var(util::perm_string_ptr(default));
+                    prefs::prefs_register_string_preference(module, name, title, description, v);
+                    PreferenceVariable::String(v)
+                }
+                dissector::PreferenceKind::Enum {
+                    default,
+                    values,
+                    radio_buttons,
+                } => {
+                    let mut entries: Vec<prefs::enum_val_t> = values
+                        .iter()
+                        .map(|(name, description, value)| prefs::enum_val_t {
+                            name: util::perm_string_ptr(name),
+                            description: util::perm_string_ptr(description),
+                            value: *value,
+                        })
+                        .collect();
+                    // Needs to be terminated with a null entry
+                    entries.push(Default::default());
+                    let entries = Box::leak(entries.into_boxed_slice());
+                    let v = var(*default as libc::c_int);
+                    prefs::prefs_register_enum_preference(
+                        module,
+                        name,
+                        title,
+                        description,
+                        v,
+                        entries.as_ptr(),
+                        *radio_buttons as libc::c_int,
+                    );
+                    PreferenceVariable::Enum(v)
+                }
+                dissector::PreferenceKind::Range { default, max_value } => {
+                    // Wireshark frees the range when the preference changes, so it must come from its allocator.
+                    let text = default
+                        .iter()
+                        .map(|(low, high)| {
+                            if low == high {
+                                format!("{}", low)
+                            } else {
+                                format!("{}-{}", low, high)
+                            }
+                        })
+                        .collect::<Vec<String>>()
+                        .join(",");
+                    let text = util::c_string(&text);
+                    let v = var(std::ptr::null_mut());
+                    range::range_convert_str(wmem::wmem_epan_scope(), v, text.as_ptr(), *max_value);
+                    prefs::prefs_register_range_preference(module, name, title, description, v, *max_value);
+                    PreferenceVariable::Range(v)
+                }
+                dissector::PreferenceKind::Filename { default, for_writing } => {
+                    let v = var(util::perm_string_ptr(default));
+                    prefs::prefs_register_filename_preference(
+                        module,
+                        name,
+                        title,
+                        description,
+                        v,
+                        *for_writing as libc::c_int,
+                    );
+                    PreferenceVariable::Filename(v)
+                }
+            };
+            variables.push((preference.name.as_str().to_owned(), variable));
+        }
+        return Preferences {
+            prefs: PreferencesBackend::Wireshark(std::rc::Rc::new(variables)),
+        };
+    }
+
+    /// Make the preferences from the values of the mock backend.
+    pub(crate) fn from_mock(values: mock::PreferencesRef) -> Preferences {
+        return Preferences {
+            prefs: PreferencesBackend::Mock(values),
+        };
+    }
+
+    /// The current value of the preference with this name, None if there is no such preference.
+    pub fn get(self: &Self, name: &str) -> Option<PreferenceValue> {
+        match &self.prefs {
+            #[cfg(feature = "wireshark")]
+            PreferencesBackend::Wireshark(variables) => {
+                let (_, variable) = variables.iter().find(|(n, _)| n == name)?;
+                return Some(unsafe { variable.value() });
+            }
+            PreferencesBackend::Mock(values) => return values.get(name),
+        }
+    }
+
+    /// The value of an unsigned integer preference.
+    pub fn uint(self: &Self, name: &str) -> Option<u32> {
+        match self.get(name) {
+            Some(PreferenceValue::UInt(v)) => return Some(v),
+            _ => return None,
+        }
+    }
+
+    /// The value of a boolean preference.
+    pub fn boolean(self: &Self, name: &str) -> Option<bool> {
+        match self.get(name) {
+            Some(PreferenceValue::Bool(v)) => return Some(v),
+            _ => return None,
+        }
+    }
+
+    /// The value of a string or filename preference.
+    pub fn string(self: &Self, name: &str) -> Option<String> {
+        match self.get(name) {
+            Some(PreferenceValue::String(v)) | Some(PreferenceValue::Filename(v)) => return Some(v),
+            _ => return None,
+        }
+    }
+
+    /// The value of an enum preference.
+    pub fn enumeration(self: &Self, name: &str) -> Option<i32> {
+        match self.get(name) {
+            Some(PreferenceValue::Enum(v)) => return Some(v),
+            _ => return None,
+        }
+    }
+
+    /// The (low, high) pairs of a range preference, both ends are inclusive.
+    pub fn range(self: &Self, name: &str) -> Option<Vec<(u32, u32)>> {
+        match self.get(name) {
+            Some(PreferenceValue::Range(v)) => return Some(v),
+            _ => return None,
+        }
+    }
+}
+
+/// Errors from accessing the data attached to a packet or conversation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataError {
+    /// The data is already being accessed, for example from the closure passed to [`Conversation::with_data()`].
+    Borrowed,
+}
+
+impl std::fmt::Display for DataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataError::Borrowed => write!(f, "the attached data is already borrowed"),
+        }
+    }
+}
+
+impl std::error::Error for DataError {}
+
+/// Typed Rust data that is attached to something in wireshark, like a conversation. Wireshark only holds a pointer to
+/// this, the values are dropped when the memory it is attached to is released. There is one value per type, such that
+/// helpers like [`crate::transaction::TransactionTracker`] don't interfere with the data of the dissector.
+pub(crate) struct ProtoData {
+    values: std::cell::RefCell<std::collections::HashMap<std::any::TypeId, Box<dyn std::any::Any>>>,
+}
+
+impl ProtoData {
+    pub(crate) fn new() -> ProtoData {
+        return ProtoData {
+            values: Default::default(),
+        };
+    }
+
+    /// Allocate the container, it is dropped when wireshark frees the memory of the capture file.
+    #[cfg(feature = "wireshark")]
+    unsafe fn file_scoped() -> *mut ProtoData {
+        extern "C" fn release(
+            _allocator: *mut wmem::wmem_allocator_t,
+            _event: wmem::wmem_cb_event_t,
+            user_data: *mut libc::c_void,
+        ) -> libc::c_int {
+            unsafe {
+                drop(Box::from_raw(user_data as *mut ProtoData));
+            }
+            // Only called once, this unregisters the callback.
+            return 0;
+        }
+        let data = Box::into_raw(Box::new(ProtoData::new()));
+        wmem::wmem_register_callback(wmem::wmem_file_scope(), Some(release), data as *mut libc::c_void);
+        return data;
+    }
+
+    /// Replace the value of this type, fails if the values are borrowed by [`ProtoData::with()`].
+    pub(crate) fn set<T: 'static>(self: &Self, value: T) -> Result<(), DataError> {
+        let old = self
+            .values
+            .try_borrow_mut()
+            .map_err(|_| DataError::Borrowed)?
+            .insert(std::any::TypeId::of::<T>(), Box::new(value));
+        // Drop the old value after the borrow ends, its destructor may look at the data again.
+        drop(old);
+        return Ok(());
+    }
+
+    /// Call f with the value of type T, None if there is no such value. Fails if the values are already borrowed, for
+    /// example by calling this from f.
+    pub(crate) fn with<T: 'static, R>(self: &Self, f: impl FnOnce(&mut T) -> R) -> Result<Option<R>, DataError> {
+        let mut values = self.values.try_borrow_mut().map_err(|_| DataError::Borrowed)?;
+        match values
+            .get_mut(&std::any::TypeId::of::<T>())
+            .and_then(|v| v.downcast_mut::<T>())
+        {
+            Some(value) => return Ok(Some(f(value))),
+            None => return Ok(None),
+        }
+    }
+}
+
+/// The backend a [`Conversation`] operates on, either wireshark itself, or the conversations from the [`crate::mock`]
+/// module.
+#[derive(Clone)]
+enum ConversationBackend {
+    #[cfg(feature = "wireshark")]
+    Wireshark(*mut conversation::conversation_t),
+    Mock(mock::ConversationRef),
+}
+
+/// Struct to represent a conversation, the packets between two endpoints, serves as a wrapper around the
+/// `conversation_*` C functions. Rust data can be attached to the conversation to keep state between its packets, this
+/// lives as long as the capture file.
+#[derive(Clone)]
+pub struct Conversation {
+    conv: ConversationBackend,
+}
+
+impl Conversation {
+    /// Find the conversation this packet belongs to by its addresses and ports, it is created if there is none yet.
+    pub fn find_or_create(pinfo: &mut PacketInfo) -> Conversation {
+        match pinfo.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(info) => {
+                return Conversation {
+                    conv: ConversationBackend::Wireshark(unsafe { conversation::find_or_create_conversation(info) }),
+                };
+            }
+            InfoBackend::Mock(info) => {
+                return Conversation {
+                    conv: ConversationBackend::Mock(info.conversation()),
+                };
+            }
+        }
+    }
+
+    /// The container with our data, if we attached one before.
+    #[cfg(feature = "wireshark")]
+    unsafe fn proto_data(conv: *mut conversation::conversation_t, create: bool) -> Option<&'static ProtoData> {
+        let proto_id = crate::plugin::proto_id();
+        let mut data = conversation::conversation_get_proto_data(conv, proto_id) as *mut ProtoData;
+        if data.is_null() && create {
+            data = ProtoData::file_scoped();
+            conversation::conversation_add_proto_data(conv, proto_id, data as *mut libc::c_void);
+        }
+        return data.as_ref();
+    }
+
+    /// Attach the value to the conversation, this replaces the value of the same type attached before. Fails if the
+    /// data of the conversation is being accessed already, for example from the closure passed to
+    /// [`Conversation::with_data()`].
+    pub fn set_data<T: 'static>(self: &mut Self, value: T) -> Result<(), DataError> {
+        match &self.conv {
+            #[cfg(feature = "wireshark")]
+            ConversationBackend::Wireshark(conv) => unsafe {
+                match Conversation::proto_data(*conv, true) {
+                    Some(data) => return data.set(value),
+                    None => return Ok(()),
+                }
+            },
+            ConversationBackend::Mock(conv) => conv.data().set(value),
+        }
+    }
+
+    /// Call f with the value of type T attached to the conversation, returns None if there is no such value. Fails if
+    /// the data of the conversation is being accessed already, like when this is called from f.
+    pub fn with_data<T: 'static, R>(self: &Self, f: impl FnOnce(&mut T) -> R) -> Result<Option<R>, DataError> {
+        match &self.conv {
+            #[cfg(feature = "wireshark")]
+            ConversationBackend::Wireshark(conv) => unsafe {
+                match Conversation::proto_data(*conv, false) {
+                    Some(data) => return data.with(f),
+                    None => return Ok(None),
+                }
+            },
+            ConversationBackend::Mock(conv) => conv.data().with(f),
+        }
+    }
+
+    /// Retrieve a copy of the value attached to the conversation, see [`Conversation::with_data()`].
+    pub fn get_data<T: 'static + Clone>(self: &Self) -> Result<Option<T>, DataError> {
+        return self.with_data(|v: &mut T| v.clone());
+    }
+
+    /// Make the running dissector dissect all following packets of this conversation, regardless of how they would
+    /// be dispatched otherwise.
+    pub fn set_dissector(self: &mut Self) {
+        match &self.conv {
+            #[cfg(feature = "wireshark")]
+            ConversationBackend::Wireshark(conv) => unsafe {
+                conversation::conversation_set_dissector(*conv, crate::plugin::handle());
+            },
+            ConversationBackend::Mock(conv) => conv.set_dissector(),
+        }
+    }
+}
+
+/// The closures handed to `tcp_dissect_pdus`, through its data pointer.
+#[cfg(feature = "wireshark")]
+struct PduFunctions<L, D> {
+    pdu_len: L,
+    dissect: D,
+}
+
+/// Length function for `tcp_dissect_pdus`, calls the pdu_len closure. A panic is made pending and asks for more data.
+#[cfg(feature = "wireshark")]
+extern "C" fn get_pdu_len_function<L: Fn(&TVB, usize) -> Option<usize>, D>(
+    _packet_info: *mut packet_info::packet_info,
+    tvb: *mut tvbuff::tvbuff_t,
+    offset: libc::c_int,
+    data: *mut libc::c_void,
+) -> u32 {
+    let functions = unsafe { &*(data as *const PduFunctions<L, D>) };
+    let tvb = unsafe { TVB::from_ptr(tvb) };
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        (functions.pdu_len)(&tvb, offset as usize)
+    })) {
+        Ok(length) => return length.map_or(0, |l| l.min(u32::MAX as usize) as u32),
+        Err(payload) => {
+            exceptions::set_pending(Exception::DissectorError(crate::plugin::panic_message(
+                payload.as_ref(),
+            )));
+            return 0;
+        }
+    }
+}
+
+/// Dissection function for `tcp_dissect_pdus`, calls the dissect closure for one PDU. Like for C dissectors, an
+/// exception or panic is reported for this PDU and the following PDUs are still dissected.
+#[cfg(feature = "wireshark")]
+extern "C" fn dissect_pdu_function<L, D: FnMut(&mut ProtoTree, &mut TVB, &mut PacketInfo) -> usize>(
+    tvb: *mut tvbuff::tvbuff_t,
+    packet_info: *mut packet_info::packet_info,
+    tree: *mut proto::proto_tree,
+    data: *mut libc::c_void,
+) -> i32 {
+    let functions = unsafe { &mut *(data as *mut PduFunctions<L, D>) };
+    let mut proto = unsafe { ProtoTree::from_ptr(tree) };
+    let mut tvb_wrapper = unsafe { TVB::from_ptr(tvb) };
+    let mut pinfo = unsafe { PacketInfo::from_ptr(packet_info) };
+    let dissect = std::panic::AssertUnwindSafe(|| (functions.dissect)(&mut proto, &mut tvb_wrapper, &mut pinfo));
+    let used_bytes = match std::panic::catch_unwind(dissect) {
+        Ok(v) => v,
+        Err(payload) => {
+            exceptions::set_pending(Exception::DissectorError(crate::plugin::panic_message(
+                payload.as_ref(),
+            )));
+            tvb_wrapper.reported_length()
+        }
+    };
+    if let Some(exception) = exceptions::take_pending() {
+        unsafe { exceptions::report(tvb, packet_info, tree, &exception) };
+    }
+    return used_bytes as i32;
+}
+
+/// Dissect a TCP payload that holds PDUs, which may be split over segments, wraps `tcp_dissect_pdus`. The pdu_len
+/// closure returns the length of the PDU at the offset, including the header it is read from; at least fixed_len bytes
+/// are available when it's called. The dissect closure is called with a buffer that holds one PDU, multiple PDUs in
+/// a segment each get their own call.
+///
+/// With desegment, a PDU that continues in the next segments is reassembled by TCP before it is dissected, this is
+/// usually a preference of the protocol. In that case pdu_len may return None if it needs more data to determine the
+/// length, 0 is treated the same. Without desegment, pdu_len must always return the length: wireshark asserts that it
+/// can ask for more data, so None or 0 throws and ends the dissection as dissector bug. A last PDU that continues past
+/// the segment is then dissected from the data there is, reading beyond that throws.
+///
+/// An exception in a PDU is reported in the tree and the next PDU is dissected. A length below fixed_len is reported
+/// as malformed packet and ends the dissection of the segment, without throwing.
+///
+/// Returns the number of bytes in the buffer, which is what the dissector should return.
+pub fn tcp_dissect_pdus<L, D>(
+    tree: &mut ProtoTree,
+    tvb: &TVB,
+    pinfo: &mut PacketInfo,
+    desegment: bool,
+    fixed_len: usize,
+    pdu_len: L,
+    mut dissect: D,
+) -> usize
+where
+    L: Fn(&TVB, usize) -> Option<usize>,
+    D: FnMut(&mut ProtoTree, &mut TVB, &mut PacketInfo) -> usize,
+{
+    match tree.tree {
+        #[cfg(feature = "wireshark")]
+        TreeBackend::Wireshark(tree_ptr) => {
+            let mut functions = PduFunctions { pdu_len, dissect };
+            let data = &mut functions as *mut PduFunctions<L, D> as *mut libc::c_void;
+            let (tvb_ptr, info_ptr): (_, *mut packet_info::packet_info) = (tvb.ptr(), pinfo.into());
+            exceptions::contain(|| unsafe {
+                packet_tcp::tcp_dissect_pdus(
+                    tvb_ptr,
+                    info_ptr,
+                    tree_ptr,
+                    desegment,
+                    fixed_len as u32,
+                    Some(get_pdu_len_function::<L, D>),
+                    Some(dissect_pdu_function::<L, D>),
+                    data,
+                )
+            });
+        }
+        TreeBackend::Mock(_) => {
+            let info = pinfo.mock();
+            let buffer = tvb.mock();
+            let desegment = desegment && info.can_desegment();
+            let mut offset = 0;
+            while offset < buffer.len() && exceptions::pending().is_none() {
+                let remaining = buffer.len() - offset;
+                if desegment && remaining < fixed_len {
+                    info.set_desegment(offset, fixed_len - remaining);
+                    break;
+                }
+                let length = match pdu_len(tvb, offset) {
+                    _ if exceptions::pending().is_some() => break,
+                    Some(length) if length > 0 => length,
+                    _ if desegment => {
+                        info.set_desegment(offset, packet_tcp::DESEGMENT_ONE_MORE_SEGMENT as usize);
+                        break;
+                    }
+                    _ => {
+                        // Wireshark asserts that it can ask for more data, which throws.
+                        let message = "failed assertion \"proto_desegment && pinfo->can_desegment\"";
+                        exceptions::set_pending(Exception::DissectorError(message.to_owned()));
+                        break;
+                    }
+                };
+                if length < fixed_len {
+                    // Wireshark shows the malformed packet, but doesn't throw.
+                    info.report_exception(Exception::ReportedBoundsError);
+                    break;
+                }
+                if desegment && remaining < length {
+                    info.set_desegment(offset, length - remaining);
+                    break;
+                }
+                let mut pdu = TVB::from_mock(buffer.subset(offset, length));
+                dissect(tree, &mut pdu, pinfo);
+                // Like dissect_pdu_function, the exception is reported for this PDU and the next one is dissected.
+                if let Some(exception) = exceptions::take_pending() {
+                    info.report_exception(exception);
+                }
+                offset += length;
+            }
+        }
+    }
+    return tvb.reported_length();
+}
+
+/// A reassembly table as registered with wireshark, with the fields and subtrees the fragments are shown with.
+/// Wireshark holds pointers into this until it exits, so the plugin keeps it together with the dissector.
+#[cfg(feature = "wireshark")]
+pub(crate) struct RegisteredReassembly {
+    table: reassemble::reassembly_table,
+    items: reassemble::fragment_items,
+    hf_entries: Vec<proto::hf_register_info>,
+    hf: [proto::HFIndex; reassemble::hf::COUNT],
+    ett: [proto::ETTIndex; reassemble::ett::COUNT],
+    name: CString,
+}
+
+/// The backend a [`ReassemblyTable`] operates on, either wireshark itself, or the fragments of the [`crate::mock`]
+/// module.
+#[derive(Clone)]
+enum ReassemblyBackend {
+    #[cfg(feature = "wireshark")]
+    Wireshark(*mut RegisteredReassembly),
+    Mock(mock::ReassemblyRef),
+}
+
+/// Collects fragments until the message they belong to is complete, serves as a wrapper around `reassembly_table`
+/// with `fragment_add_seq_check` and `process_reassembled_data`. Obtained from
+/// [`dissector::Dissector::set_reassembly_tables()`].
+#[derive(Clone)]
+pub struct ReassemblyTable {
+    table: ReassemblyBackend,
+}
+
+impl ReassemblyTable {
+    /// Register the table for the protocol, with its fields and subtrees. The returned registration must be kept for as
+    /// long as wireshark runs, the table points into it.
+    #[cfg(feature = "wireshark")]
+    pub(crate) unsafe fn register(
+        proto_id: i32,
+        reassembly: &dissector::Reassembly,
+    ) -> (ReassemblyTable, Box<RegisteredReassembly>) {
+        let mut registered = Box::new(RegisteredReassembly {
+            table: Default::default(),
+            items: std::mem::zeroed(),
+            hf_entries: Vec::new(),
+            hf: [proto::HFIndex(-1); reassemble::hf::COUNT],
+            ett: [proto::ETTIndex(-1); reassemble::ett::COUNT],
+            name: util::c_string(&format!("Reassembled {}", reassembly.name.as_str())),
+        });
+
+        let fields = reassembly.fields();
+        for (field, hf) in fields.iter().zip(registered.hf.iter_mut()) {
+            registered.hf_entries.push(proto::hf_register_info {
+                p_id: (hf as *mut proto::HFIndex).cast(),
+                hfinfo: (&field.as_boxed()).into(),
+            });
+        }
+        proto::proto_register_field_array(
+            proto_id,
+            registered.hf_entries.as_mut_ptr(),
+            registered.hf_entries.len() as i32,
+        );
+        let mut ett: Vec<*mut proto::ETTIndex> = registered.ett.iter_mut().map(|e| e as *mut proto::ETTIndex).collect();
+        proto::proto_register_subtree_array(ett.as_mut_ptr(), ett.len() as i32);
+
+        use reassemble::{ett, hf};
+        let (hfs, etts) = (&registered.hf, &registered.ett);
+        registered.items = reassemble::fragment_items {
+            ett_fragment: &etts[ett::FRAGMENT],
+            ett_fragments: &etts[ett::FRAGMENTS],
+            hf_fragments: &hfs[hf::FRAGMENTS],
+            hf_fragment: &hfs[hf::FRAGMENT],
+            hf_fragment_overlap: &hfs[hf::FRAGMENT_OVERLAP],
+            hf_fragment_overlap_conflict: &hfs[hf::FRAGMENT_OVERLAP_CONFLICT],
+            hf_fragment_multiple_tails: &hfs[hf::FRAGMENT_MULTIPLE_TAILS],
+            hf_fragment_too_long_fragment: &hfs[hf::FRAGMENT_TOO_LONG_FRAGMENT],
+            hf_fragment_error: &hfs[hf::FRAGMENT_ERROR],
+            hf_fragment_count: &hfs[hf::FRAGMENT_COUNT],
+            hf_reassembled_in: &hfs[hf::REASSEMBLED_IN],
+            hf_reassembled_length: &hfs[hf::REASSEMBLED_LENGTH],
+            hf_reassembled_data: &hfs[hf::REASSEMBLED_DATA],
+            tag: util::perm_string_ptr(&format!("{} fragments", reassembly.name.as_str())),
+        };
+
+        let functions = match reassembly.key {
+            dissector::ReassemblyKey::Addresses => &reassemble::addresses_reassembly_table_functions,
+            dissector::ReassemblyKey::AddressesPorts => &reassemble::addresses_ports_reassembly_table_functions,
+        };
+        reassemble::reassembly_table_register(&mut registered.table, functions);
+
+        // Moving the box doesn't move the registration, so the pointers wireshark holds stay valid.
+        let table = ReassemblyTable {
+            table: ReassemblyBackend::Wireshark(&mut *registered),
+        };
+        return (table, registered);
+    }
+
+    /// Make a table that collects the fragments in the [`crate::mock`] backend.
+    pub(crate) fn from_mock(table: mock::ReassemblyRef) -> ReassemblyTable {
+        return ReassemblyTable {
+            table: ReassemblyBackend::Mock(table),
+        };
+    }
+
+    /// Add the fragment, with its sequence number in the message that has the id. The fragment is the whole buffer,
+    /// use [`TVB::subset()`] to select it from the packet. The fragment without more_frags is the last one, sequence
+    /// numbers start at 0 and the fragments may arrive in any order. If a fragment arrives twice, the data of the first
+    /// is used.
+    ///
+    /// Returns a buffer with the reassembled message when this packet completes it, the message can then be
+    /// dissected from it; the fragments are shown in the tree and the buffer gets its own tab in the bytes pane. The
+    /// other fragments get an item that points to the packet the message was reassembled in, once that is known.
+    pub fn add_fragment<'a>(
+        self: &Self,
+        tree: &mut ProtoTree,
+        fragment: &TVB<'a>,
+        pinfo: &mut PacketInfo,
+        id: u32,
+        frag_number: u32,
+        more_frags: bool,
+    ) -> Option<TVB<'a>> {
+        match &self.table {
+            #[cfg(feature = "wireshark")]
+            ReassemblyBackend::Wireshark(registered) => unsafe {
+                let registered = *registered;
+                let (tvb_ptr, info_ptr): (_, *mut packet_info::packet_info) = (fragment.ptr(), pinfo.into());
+                let length = fragment.reported_length();
+                let tree_ptr = tree.ptr();
+                let head = exceptions::contain(|| {
+                    reassemble::fragment_add_seq_check(
+                        &mut (*registered).table,
+                        tvb_ptr,
+                        0,
+                        info_ptr,
+                        id,
+                        std::ptr::null(),
+                        frag_number,
+                        length as u32,
+                        more_frags,
+                    )
+                })?;
+                let mut update_col_info = false;
+                let reassembled = exceptions::contain(|| {
+                    reassemble::process_reassembled_data(
+                        tvb_ptr,
+                        0,
+                        info_ptr,
+                        (*registered).name.as_ptr(),
+                        head,
+                        &(*registered).items,
+                        &mut update_col_info,
+                        tree_ptr,
+                    )
+                })?;
+                if reassembled.is_null() {
+                    return None;
+                }
+                return Some(TVB::from_ptr(reassembled));
+            },
+            ReassemblyBackend::Mock(table) => {
+                use reassemble::{ett, hf};
+                let data = fragment.get_mem(0, fragment.reported_length());
+                if exceptions::pending().is_some() {
+                    return None;
+                }
+                let message = pinfo.mock().add_fragment(table, id, frag_number, &data, more_frags)?;
+                if message.reassembled_in != pinfo.number() {
+                    tree.add_uint(table.hf[hf::REASSEMBLED_IN], fragment, 0, 0, message.reassembled_in)
+                        .set_generated();
+                    return None;
+                }
+
+                // The same items as wireshark's show_fragment_seq_tree.
+                // Like wireshark, the reassembled message is shown in its own tab.
+                let reassembled = fragment.new_child_real_data(message.data.clone());
+                pinfo.add_new_data_source(&format!("Reassembled {}", table.name), &reassembled);
+                let total = message.data.len();
+                let mut item = tree.add_item(table.hf[hf::FRAGMENTS], &reassembled, 0, total, proto::Encoding::NA);
+                let frames: Vec<String> = message
+                    .fragments
+                    .iter()
+                    .map(|(f, l)| format!("#{}({})", f, l))
+                    .collect();
+                item.set_text(&format!(
+                    "{} {} fragments ({} bytes): {}",
+                    message.fragments.len(),
+                    table.name,
+                    total,
+                    frames.join(", ")
+                ));
+                item.set_generated();
+                let mut subtree = item.add_subtree(table.ett[ett::FRAGMENTS]);
+                let mut fragment_offset = 0;
+                for (frame, fragment_length) in message.fragments.iter() {
+                    let mut fragment = subtree.add_uint(
+                        table.hf[hf::FRAGMENT],
+                        &reassembled,
+                        fragment_offset,
+                        *fragment_length,
+                        *frame,
+                    );
+                    fragment.set_text(&format!(
+                        "Frame: {}, payload: {}-{} ({} bytes)",
+                        frame,
+                        fragment_offset,
+                        (fragment_offset + fragment_length).max(1) - 1,
+                        fragment_length
+                    ));
+                    fragment_offset += fragment_length;
+                }
+                let count = message.fragments.len() as u32;
+                subtree
+                    .add_uint(table.hf[hf::FRAGMENT_COUNT], &reassembled, 0, 0, count)
+                    .set_generated();
+                subtree
+                    .add_uint(table.hf[hf::REASSEMBLED_LENGTH], &reassembled, 0, 0, total as u32)
+                    .set_generated();
+                subtree
+                    .add_item(
+                        table.hf[hf::REASSEMBLED_DATA],
+                        &reassembled,
+                        0,
+                        total,
+                        proto::Encoding::NA,
+                    )
+                    .set_generated();
+                return Some(reassembled);
+            }
+        }
+    }
+}
+
+/// The backend a [`DissectorHandle`] operates on, either a wireshark handle, or a dissector stand-in from the
+/// [`crate::mock`] module, by name.
+#[derive(Clone)]
+enum HandleBackend {
+    #[cfg(feature = "wireshark")]
+    Wireshark(packet::dissector_handle_t),
+    Mock(String),
+}
+
+/// Handle to another dissector, like json or usbhid, serves as a wrapper around `dissector_handle_t` and the
+/// `call_dissector*` C functions. The called dissector adds its items to the tree and may change the columns.
+///
+/// An exception thrown by the called dissector is made pending, like it propagates to the caller in C.
+#[derive(Clone)]
+pub struct DissectorHandle {
+    handle: HandleBackend,
+}
+
+impl DissectorHandle {
+    /// Find a dissector by the name it was registered with, for example `"json"`, None if there is no such dissector.
+    pub fn find(pinfo: &PacketInfo, name: &str) -> Option<DissectorHandle> {
+        match pinfo.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(_) => {
+                let name = util::c_string(name);
+                let handle = unsafe { packet::find_dissector(name.as_ptr()) };
+                if handle.is_null() {
+                    return None;
+                }
+                return Some(DissectorHandle {
+                    handle: HandleBackend::Wireshark(handle),
+                });
+            }
+            InfoBackend::Mock(info) => {
+                if !info.has_dissector(name) {
+                    return None;
+                }
+                return Some(DissectorHandle {
+                    handle: HandleBackend::Mock(name.to_owned()),
+                });
+            }
+        }
+    }
+
+    /// Call the dissector on the buffer, if it rejects the data the data dissector is called instead. Returns the
+    /// number of bytes that were consumed.
+    pub fn call(self: &Self, tree: &mut ProtoTree, tvb: &TVB, pinfo: &mut PacketInfo) -> usize {
+        match &self.handle {
+            #[cfg(feature = "wireshark")]
+            HandleBackend::Wireshark(handle) => {
+                let (handle, tvb_ptr, info_ptr, tree_ptr) = (*handle, tvb.ptr(), pinfo.into(), tree.ptr());
+                let consumed =
+                    exceptions::contain(|| unsafe { packet::call_dissector(handle, tvb_ptr, info_ptr, tree_ptr) });
+                return consumed.unwrap_or(0).max(0) as usize;
+            }
+            HandleBackend::Mock(_) => match self.call_only(tree, tvb, pinfo) {
+                0 => return call_data_dissector(tree, tvb, pinfo),
+                consumed => return consumed,
+            },
+        }
+    }
+
+    /// Call the dissector on the buffer, without falling back to the data dissector. Returns the number of bytes that
+    /// were consumed, 0 if the dissector rejected the data.
+    pub fn call_only(self: &Self, tree: &mut ProtoTree, tvb: &TVB, pinfo: &mut PacketInfo) -> usize {
+        match &self.handle {
+            #[cfg(feature = "wireshark")]
+            HandleBackend::Wireshark(handle) => {
+                let (handle, tvb_ptr, info_ptr, tree_ptr) = (*handle, tvb.ptr(), pinfo.into(), tree.ptr());
+                let consumed = exceptions::contain(|| unsafe {
+                    packet::call_dissector_only(handle, tvb_ptr, info_ptr, tree_ptr, std::ptr::null_mut())
+                });
+                return consumed.unwrap_or(0).max(0) as usize;
+            }
+            HandleBackend::Mock(name) => {
+                let _ = pinfo;
+                return tree.mock().call_dissector(name, tvb.mock());
+            }
+        }
+    }
+}
+
+/// Show the buffer as raw data, the fallback for data no other dissector takes. Returns the number of bytes consumed,
+/// which is all of them.
+pub fn call_data_dissector(tree: &mut ProtoTree, tvb: &TVB, pinfo: &mut PacketInfo) -> usize {
+    match tree.tree {
+        #[cfg(feature = "wireshark")]
+        TreeBackend::Wireshark(tree_ptr) => {
+            let (tvb_ptr, info_ptr) = (tvb.ptr(), pinfo.into());
+            let consumed = exceptions::contain(|| unsafe { packet::call_data_dissector(tvb_ptr, info_ptr, tree_ptr) });
+            return consumed.unwrap_or(0).max(0) as usize;
+        }
+        TreeBackend::Mock(node) => {
+            let _ = pinfo;
+            return node.call_dissector("data", tvb.mock());
+        }
+    }
+}
+
+/// The backend a [`DissectorTable`] operates on, either a wireshark table, or a table of the [`crate::mock`] module,
+/// by name.
+#[derive(Clone)]
+enum TableBackend {
+    #[cfg(feature = "wireshark")]
+    Wireshark(packet::dissector_table_t),
+    Mock(String),
+}
+
+/// A dissector table, like `"ethertype"` or `"usb.bulk"`, serves as a wrapper around `dissector_table_t` and the
+/// `dissector_try_*` C functions. Dissectors register in a table for a value, trying the table calls the dissector
+/// registered for the value. The tables of our own protocol are obtained from
+/// [`dissector::Dissector::set_dissector_tables()`].
+///
+/// An exception thrown by the called dissector is made pending, like it propagates to the caller in C.
+#[derive(Clone)]
+pub struct DissectorTable {
+    table: TableBackend,
+}
+
+impl DissectorTable {
+    /// Find a table by its name, None if there is no such table.
+    pub fn find(pinfo: &PacketInfo, name: &str) -> Option<DissectorTable> {
+        match pinfo.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(_) => {
+                let name = util::c_string(name);
+                let table = unsafe { packet::find_dissector_table(name.as_ptr()) };
+                if table.is_null() {
+                    return None;
+                }
+                return Some(DissectorTable {
+                    table: TableBackend::Wireshark(table),
+                });
+            }
+            InfoBackend::Mock(info) => {
+                if !info.has_table(name) {
+                    return None;
+                }
+                return Some(DissectorTable {
+                    table: TableBackend::Mock(name.to_owned()),
+                });
+            }
+        }
+    }
+
+    /// Register the table for the protocol.
+    #[cfg(feature = "wireshark")]
+    pub(crate) unsafe fn register(proto_id: i32, info: &dissector::DissectorTableInfo) -> DissectorTable {
+        let (field_type, param) = match info.kind {
+            dissector::DissectorTableKind::UInt { field_type, display } => (field_type, display as libc::c_int),
+            dissector::DissectorTableKind::String { case_sensitive: true } => {
+                (ftypes::ftenum::STRING, packet::STRING_CASE_SENSITIVE)
+            }
+            dissector::DissectorTableKind::String { case_sensitive: false } => {
+                (ftypes::ftenum::STRING, packet::STRING_CASE_INSENSITIVE)
+            }
+        };
+        let table = packet::register_dissector_table(
+            util::perm_string_ptr(info.name.as_str()),
+            util::perm_string_ptr(info.ui_name.as_str()),
+            proto_id,
+            field_type,
+            param,
+        );
+        return DissectorTable {
+            table: TableBackend::Wireshark(table),
+        };
+    }
+
+    /// Make a handle for the table with this name in the [`crate::mock`] backend.
+    pub(crate) fn from_mock(name: &str) -> DissectorTable {
+        return DissectorTable {
+            table: TableBackend::Mock(name.to_owned()),
+        };
+    }
+
+    /// Call the dissector registered in this integer table for the value. Returns the number of bytes that were
+    /// consumed, 0 if there is no dissector for the value or if it rejected the data.
+    pub fn try_uint(self: &Self, value: u32, tree: &mut ProtoTree, tvb: &TVB, pinfo: &mut PacketInfo) -> usize {
+        match &self.table {
+            #[cfg(feature = "wireshark")]
+            TableBackend::Wireshark(table) => {
+                let (table, tvb_ptr, info_ptr, tree_ptr) = (*table, tvb.ptr(), pinfo.into(), tree.ptr());
+                let consumed = exceptions::contain(|| unsafe {
+                    packet::dissector_try_uint_new(
+                        table,
+                        value,
+                        tvb_ptr,
+                        info_ptr,
+                        tree_ptr,
+                        true,
+                        std::ptr::null_mut(),
+                    )
+                });
+                return consumed.unwrap_or(0).max(0) as usize;
+            }
+            TableBackend::Mock(name) => {
+                let _ = pinfo;
+                let node = tree.mock();
+                match node.table_uint(name, value) {
+                    Some(dissector) => return node.call_dissector(&dissector, tvb.mock()),
+                    None => return 0,
+                }
+            }
+        }
+    }
+
+    /// Call the dissector registered in this integer table for the value, if there is none or it rejects the data, the
+    /// data dissector is called instead. Returns the number of bytes that were consumed.
+    pub fn call_uint(self: &Self, value: u32, tree: &mut ProtoTree, tvb: &TVB, pinfo: &mut PacketInfo) -> usize {
+        match self.try_uint(value, tree, tvb, pinfo) {
+            0 if exceptions::pending().is_none() => return call_data_dissector(tree, tvb, pinfo),
+            consumed => return consumed,
+        }
+    }
+
+    /// Like [`DissectorTable::call_uint()`], for string tables.
+    pub fn call_string(self: &Self, value: &str, tree: &mut ProtoTree, tvb: &TVB, pinfo: &mut PacketInfo) -> usize {
+        match self.try_string(value, tree, tvb, pinfo) {
+            0 if exceptions::pending().is_none() => return call_data_dissector(tree, tvb, pinfo),
+            consumed => return consumed,
+        }
+    }
+
+    /// Call the dissector registered in this string table for the value, like [`DissectorTable::try_uint()`].
+    pub fn try_string(self: &Self, value: &str, tree: &mut ProtoTree, tvb: &TVB, pinfo: &mut PacketInfo) -> usize {
+        match &self.table {
+            #[cfg(feature = "wireshark")]
+            TableBackend::Wireshark(table) => {
+                let value = util::c_string(value);
+                let (table, value_ptr, tvb_ptr, info_ptr, tree_ptr) =
+                    (*table, value.as_ptr(), tvb.ptr(), pinfo.into(), tree.ptr());
+                let consumed = exceptions::contain(|| unsafe {
+                    packet::dissector_try_string(table, value_ptr, tvb_ptr, info_ptr, tree_ptr, std::ptr::null_mut())
+                });
+                return consumed.unwrap_or(0).max(0) as usize;
+            }
+            TableBackend::Mock(name) => {
+                let _ = pinfo;
+                let node = tree.mock();
+                match node.table_string(name, value) {
+                    Some(dissector) => return node.call_dissector(&dissector, tvb.mock()),
+                    None => return 0,
+                }
+            }
+        }
+    }
+}
+
+/// The backend a [`HeuristicList`] operates on, either a wireshark list, or a list of the [`crate::mock`] module, by
+/// name.
+#[derive(Clone)]
+enum HeuristicBackend {
+    #[cfg(feature = "wireshark")]
+    Wireshark(packet::heur_dissector_list_t),
+    Mock(String),
+}
+
+/// A list of heuristic dissectors, like `"udp"` or `"usb.bulk"`, serves as a wrapper around `heur_dissector_list_t`
+/// and `dissector_try_heuristic`.
+///
+/// An exception thrown by the called dissector is made pending, like it propagates to the caller in C.
+#[derive(Clone)]
+pub struct HeuristicList {
+    list: HeuristicBackend,
+}
+
+impl HeuristicList {
+    /// Find a heuristic list by its name, None if there is no such list.
+    pub fn find(pinfo: &PacketInfo, name: &str) -> Option<HeuristicList> {
+        match pinfo.info {
+            #[cfg(feature = "wireshark")]
+            InfoBackend::Wireshark(_) => {
+                let name = util::c_string(name);
+                let list = unsafe { packet::find_heur_dissector_list(name.as_ptr()) };
+                if list.is_null() {
+                    return None;
+                }
+                return Some(HeuristicList {
+                    list: HeuristicBackend::Wireshark(list),
+                });
+            }
+            InfoBackend::Mock(info) => {
+                if !info.has_heuristic_list(name) {
+                    return None;
+                }
+                return Some(HeuristicList {
+                    list: HeuristicBackend::Mock(name.to_owned()),
+                });
+            }
+        }
+    }
+
+    /// Try the enabled heuristic dissectors in the list, until one accepts the data. Heuristic dissectors don't report
+    /// how much they used, so this returns the length of the buffer if one accepted it, 0 otherwise.
+    pub fn try_dissect(self: &Self, tree: &mut ProtoTree, tvb: &TVB, pinfo: &mut PacketInfo) -> usize {
+        let accepted = match &self.list {
+            #[cfg(feature = "wireshark")]
+            HeuristicBackend::Wireshark(list) => {
+                let (list, tvb_ptr, info_ptr, tree_ptr) = (*list, tvb.ptr(), pinfo.into(), tree.ptr());
+                let mut entry: *mut packet::heur_dtbl_entry_t = std::ptr::null_mut();
+                let entry_ptr = &mut entry as *mut *mut packet::heur_dtbl_entry_t;
+                exceptions::contain(|| unsafe {
+                    packet::dissector_try_heuristic(list, tvb_ptr, info_ptr, tree_ptr, entry_ptr, std::ptr::null_mut())
+                })
+                .unwrap_or(false)
+            }
+            HeuristicBackend::Mock(name) => {
+                let _ = pinfo;
+                let node = tree.mock();
+                node.heuristics(name)
+                    .iter()
+                    .any(|dissector| node.call_dissector(dissector, tvb.mock()) != 0)
+            }
+        };
+        match accepted {
+            true => return tvb.reported_length(),
+            false => return 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dissector::{BasicHeaderFieldInfo, Reassembly, ReassemblyKey, StringContainer};
+    use crate::mock::tests::TestDissector;
+    use crate::mock::{Dissection, Harness, Packet, Value};
+
+    fn field(
+        name: &'static str,
+        abbrev: &'static str,
+        field_type: FieldType,
+        display: FieldDisplay,
+    ) -> BasicHeaderFieldInfo {
+        BasicHeaderFieldInfo::simple(name, abbrev, field_type, display)
+    }
+
+    #[test]
+    fn value_adders() {
+        let fields = [
+            field("UInt", "test.uint", FieldType::UINT16, FieldDisplay::BASE_DEC),
+            field("Int", "test.int", FieldType::INT32, FieldDisplay::BASE_DEC),
+            field("UInt64", "test.uint64", FieldType::UINT64, FieldDisplay::BASE_HEX),
+            field("Int64", "test.int64", FieldType::INT64, FieldDisplay::BASE_DEC),
+            field("Bool", "test.bool", FieldType::BOOLEAN, FieldDisplay::BASE_NONE),
+            field("Double", "test.double", FieldType::DOUBLE, FieldDisplay::BASE_NONE),
+            field("String", "test.string", FieldType::STRING, FieldDisplay::BASE_NONE),
+            field("Bytes", "test.bytes", FieldType::BYTES, FieldDisplay::SEP_COLON),
+            field("IPv4", "test.ipv4", FieldType::IPv4, FieldDisplay::BASE_NONE),
+            field("IPv6", "test.ipv6", FieldType::IPv6, FieldDisplay::BASE_NONE),
+            field("Ether", "test.ether", FieldType::ETHER, FieldDisplay::BASE_NONE),
+            field("GUID", "test.guid", FieldType::GUID, FieldDisplay::BASE_NONE),
+            field("Time", "test.time", FieldType::RELATIVE_TIME, FieldDisplay::BASE_NONE),
+        ];
+        let mut d = TestDissector::new(&fields, |d, proto, tvb, _| {
+            proto.add_uint(d.hf[0], tvb, 0, 2, 1234);
+            proto.add_int(d.hf[1], tvb, 0, 4, -5);
+            proto.add_uint64(d.hf[2], tvb, 0, 8, 0xabcd);
+            proto.add_int64(d.hf[3], tvb, 0, 8, -7);
+            proto.add_boolean(d.hf[4], tvb, 0, 1, true);
+            proto.add_double(d.hf[5], tvb, 0, 8, 1.5);
+            proto.add_string(d.hf[6], tvb, 0, 3, "abc");
+            proto.add_bytes(d.hf[7], tvb, 0, 2, &[0xde, 0xad]);
+            proto.add_ipv4(d.hf[8], tvb, 0, 4, std::net::Ipv4Addr::new(192, 168, 0, 1));
+            proto.add_ipv6(d.hf[9], tvb, 0, 16, std::net::Ipv6Addr::LOCALHOST);
+            proto.add_ether(d.hf[10], tvb, 0, 6, &[0, 1, 2, 3, 4, 0xff]);
+            let guid = guid_utils::e_guid_t {
+                data1: 0x01020304,
+                data2: 0x0506,
+                data3: 0x0708,
+                data4: [9, 10, 11, 12, 13, 14, 15, 16],
+            };
+            proto.add_guid(d.hf[11], tvb, 0, 16, &guid);
+            proto.add_time(d.hf[12], tvb, 0, 8, &nstime::nstime_t { secs: 3, nsecs: 500 });
+            16
+        });
+        let harness = Harness::new(&mut d);
+        let dissection = harness.dissect(&d, &[0; 16]);
+        assert_eq!(dissection.exception, None);
+        let labels: Vec<String> = dissection.items.iter().map(|i| i.label()).collect();
+        assert_eq!(
+            labels,
+            vec![
+                "UInt: 1234",
+                "Int: -5",
+                "UInt64: 0x000000000000abcd",
+                "Int64: -7",
+                "Bool: True",
+                "Double: 1.5",
+                "String: abc",
+                "Bytes: de:ad",
+                "IPv4: 192.168.0.1",
+                "IPv6: ::1",
+                "Ether: 00:01:02:03:04:ff",
+                "GUID: 01020304-0506-0708-090a-0b0c0d0e0f10",
+                "Time: 3.000000500 seconds",
+            ]
+        );
+        assert_eq!(dissection.find("test.int").unwrap().value, Value::Int(-5));
+        assert_eq!(
+            dissection.find("test.bytes").unwrap().value,
+            Value::Bytes(vec![0xde, 0xad])
+        );
+        // The values don't come from the buffer, but the range still marks the bytes.
+        let ipv6 = dissection.find("test.ipv6").unwrap();
+        assert_eq!((ipv6.offset, ipv6.length), (0, 16));
+    }
+
+    #[test]
+    fn format_value_adders() {
+        let fields = [
+            field("UInt", "test.uint", FieldType::UINT8, FieldDisplay::BASE_DEC),
+            field("Bool", "test.bool", FieldType::BOOLEAN, FieldDisplay::BASE_NONE),
+            field("String", "test.string", FieldType::STRING, FieldDisplay::BASE_NONE),
+            field("Bytes", "test.bytes", FieldType::BYTES, FieldDisplay::BASE_NONE),
+            field("Time", "test.time", FieldType::ABSOLUTE_TIME, FieldDisplay::BASE_NONE),
+        ];
+        let mut d = TestDissector::new(&fields, |d, proto, tvb, _| {
+            proto.add_uint_format_value(d.hf[0], tvb, 0, 1, 3, "three");
+            proto.add_boolean_format_value(d.hf[1], tvb, 0, 1, false, "off");
+            // Text from packets can hold null characters, these are dropped instead of panicking.
+            proto.add_string_format_value(d.hf[2], tvb, 0, 1, "a\0b", "quoted \"a\0b\"");
+            proto.add_bytes_format_value(d.hf[3], tvb, 0, 2, &[1, 2, 3], "two bytes");
+            proto.add_time_format_value(d.hf[4], tvb, 0, 4, &nstime::nstime_t { secs: 0, nsecs: 0 }, "epoch");
+            4
+        });
+        let harness = Harness::new(&mut d);
+        let dissection = harness.dissect(&d, &[0; 4]);
+        let labels: Vec<String> = dissection.items.iter().map(|i| i.label()).collect();
+        assert_eq!(
+            labels,
+            vec![
+                "UInt: three",
+                "Bool: off",
+                "String: quoted \"ab\"",
+                "Bytes: two bytes",
+                "Time: epoch",
+            ]
+        );
+        assert_eq!(dissection.find("test.uint").unwrap().value, Value::UInt(3));
+        assert_eq!(dissection.find("test.bool").unwrap().value, Value::Bool(false));
+        assert_eq!(
+            dissection.find("test.string").unwrap().value,
+            Value::String("ab".to_owned())
+        );
+        // Only the bytes the item covers are shown, like wireshark.
+        assert_eq!(dissection.find("test.bytes").unwrap().value, Value::Bytes(vec![1, 2]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Value has 1 bytes, need at least 2.")]
+    fn bytes_format_value_needs_length_bytes() {
+        let fields = [field("Bytes", "test.bytes", FieldType::BYTES, FieldDisplay::BASE_NONE)];
+        let mut d = TestDissector::new(&fields, |d, proto, tvb, _| {
+            proto.add_bytes_format_value(d.hf[0], tvb, 0, 2, &[1], "short");
+            2
+        });
+        let harness = Harness::new(&mut d);
+        harness.dissect(&d, &[0; 2]);
+    }
+
+    #[test]
+    fn value_outside_of_buffer_throws() {
+        let fields = [field("UInt", "test.uint", FieldType::UINT32, FieldDisplay::BASE_DEC)];
+        let mut d = TestDissector::new(&fields, |d, proto, tvb, _| {
+            proto.add_uint(d.hf[0], tvb, 2, 4, 1);
+            6
+        });
+        let harness = Harness::new(&mut d);
+        let dissection = harness.dissect(&d, &[0; 4]);
+        assert_eq!(dissection.exception, Some(Exception::ReportedBoundsError));
+        assert!(dissection.items.is_empty());
+    }
+
+    /// Packets hold a fragment of a message, preceded by the message id, the sequence number and whether more
+    /// fragments follow. The message is shown once it's complete.
+    fn fragment_dissector() -> TestDissector {
+        let fields = [field(
+            "Message",
+            "test.message",
+            FieldType::STRING,
+            FieldDisplay::BASE_NONE,
+        )];
+        let reassembly = Reassembly {
+            name: StringContainer::StaticStr("Message"),
+            abbrev: StringContainer::StaticStr("test.msg"),
+            key: ReassemblyKey::Addresses,
+        };
+        TestDissector::new(&fields, |d, proto, tvb, pinfo| {
+            let header = tvb.get_mem(0, 3);
+            let fragment = tvb.subset_remaining(3).unwrap();
+            let id = header[0] as u32;
+            if let Some(message) =
+                d.tables[0].add_fragment(proto, &fragment, pinfo, id, header[1] as u32, header[2] != 0)
+            {
+                proto.add_item(d.hf[0], &message, 0, message.reported_length(), proto::Encoding::UTF_8);
+            }
+            tvb.reported_length()
+        })
+        .with_reassemblies(&[reassembly])
+    }
+
+    /// Dissect the packets in order as frame 1, 2 and so on, then again like wireshark's second pass.
+    fn dissect_twice(d: &mut TestDissector, packets: &[&[u8]]) -> (Vec<Dissection>, Vec<Dissection>) {
+        let mut harness = Harness::new(d);
+        let mut run = |visited: bool| -> Vec<Dissection> {
+            let mut dissections = Vec::new();
+            for (i, packet) in packets.iter().enumerate() {
+                harness.set_packet(Packet {
+                    number: i as u32 + 1,
+                    visited,
+                    ..Default::default()
+                });
+                dissections.push(harness.dissect(d, packet));
+            }
+            dissections
+        };
+        let first = run(false);
+        let second = run(true);
+        (first, second)
+    }
+
+    fn message(dissection: &Dissection) -> Option<Value> {
+        dissection.find("test.message").map(|item| item.value.clone())
+    }
+
+    fn reassembled_in(dissection: &Dissection) -> Option<Value> {
+        dissection
+            .find("test.msg.reassembled.in")
+            .map(|item| item.value.clone())
+    }
+
+    #[test]
+    fn fragments_in_order() {
+        let mut d = fragment_dissector();
+        let (first, second) = dissect_twice(&mut d, &[b"\x07\x00\x01ab", b"\x07\x01\x01cd", b"\x07\x02\x00e"]);
+        assert_eq!(message(&first[0]), None);
+        assert_eq!(message(&first[1]), None);
+        // Where the message is reassembled isn't known yet on the first pass.
+        assert_eq!(reassembled_in(&first[0]), None);
+
+        let last = &first[2];
+        assert_eq!(message(last), Some(Value::String("abcde".to_owned())));
+        assert_eq!(
+            last.data_sources,
+            vec![("Reassembled Message".to_owned(), b"abcde".to_vec())]
+        );
+        let fragments = last.find("test.msg.fragments").unwrap();
+        assert_eq!(
+            fragments.label(),
+            "[3 Message fragments (5 bytes): #1(2), #2(2), #3(1)]"
+        );
+        let offsets: Vec<(usize, usize)> = last
+            .find_all("test.msg.fragment")
+            .iter()
+            .map(|f| (f.offset, f.length))
+            .collect();
+        assert_eq!(offsets, vec![(0, 2), (2, 2), (4, 1)]);
+        assert_eq!(last.find("test.msg.fragment.count").unwrap().value, Value::UInt(3));
+        assert_eq!(last.find("test.msg.reassembled.length").unwrap().value, Value::UInt(5));
+
+        assert_eq!(reassembled_in(&second[0]), Some(Value::UInt(3)));
+        assert_eq!(reassembled_in(&second[1]), Some(Value::UInt(3)));
+        assert_eq!(message(&second[2]), Some(Value::String("abcde".to_owned())));
+    }
+
+    #[test]
+    fn fragments_out_of_order() {
+        let mut d = fragment_dissector();
+        let (first, second) = dissect_twice(&mut d, &[b"\x07\x02\x00e", b"\x07\x00\x01ab", b"\x07\x01\x01cd"]);
+        assert_eq!(message(&first[0]), None);
+        assert_eq!(message(&first[1]), None);
+        let last = &first[2];
+        assert_eq!(message(last), Some(Value::String("abcde".to_owned())));
+        // The fragments are listed by sequence number, not by frame.
+        assert_eq!(
+            last.find("test.msg.fragments").unwrap().label(),
+            "[3 Message fragments (5 bytes): #2(2), #3(2), #1(1)]"
+        );
+        assert_eq!(reassembled_in(&second[0]), Some(Value::UInt(3)));
+        assert_eq!(reassembled_in(&second[1]), Some(Value::UInt(3)));
+    }
+
+    #[test]
+    fn duplicate_fragments() {
+        let mut d = fragment_dissector();
+        let (first, second) = dissect_twice(
+            &mut d,
+            &[
+                b"\x07\x00\x01ab",
+                b"\x07\x00\x01XX",
+                b"\x07\x01\x00cd",
+                // The same id after the message is complete starts a new one.
+                b"\x07\x00\x00f",
+            ],
+        );
+        assert_eq!(message(&first[1]), None);
+        let complete = &first[2];
+        assert_eq!(message(complete), Some(Value::String("abcd".to_owned())));
+        assert_eq!(
+            complete.find("test.msg.fragments").unwrap().label(),
+            "[2 Message fragments (4 bytes): #1(2), #3(2)]"
+        );
+        assert_eq!(message(&first[3]), Some(Value::String("f".to_owned())));
+
+        // The retransmission points to the message as well.
+        assert_eq!(reassembled_in(&second[1]), Some(Value::UInt(3)));
+        assert_eq!(message(&second[2]), Some(Value::String("abcd".to_owned())));
+        assert_eq!(message(&second[3]), Some(Value::String("f".to_owned())));
+    }
+
+    /// PDUs of a length byte followed by that many bytes of text, each shown as a message.
+    fn pdu_dissector(desegment: bool) -> TestDissector {
+        let fields = [field(
+            "Message",
+            "test.message",
+            FieldType::STRING,
+            FieldDisplay::BASE_NONE,
+        )];
+        TestDissector::new(&fields, move |d, proto, tvb, pinfo| {
+            tcp_dissect_pdus(
+                proto,
+                tvb,
+                pinfo,
+                desegment,
+                1,
+                |tvb, offset| Some(1 + tvb.get_u8(offset).ok()? as usize),
+                |proto, pdu, _| {
+                    let length = pdu.get_u8(0).unwrap() as usize;
+                    proto.add_item(d.hf[0], pdu, 1, length, proto::Encoding::UTF_8);
+                    pdu.reported_length()
+                },
+            )
+        })
+    }
+
+    fn messages(dissection: &Dissection) -> Vec<Value> {
+        dissection
+            .find_all("test.message")
+            .iter()
+            .map(|item| item.value.clone())
+            .collect()
+    }
+
+    fn text(v: &str) -> Value {
+        Value::String(v.to_owned())
+    }
+
+    #[test]
+    fn pdu_split_over_segments() {
+        let mut d = pdu_dissector(true);
+        let harness = Harness::new(&mut d);
+        let dissections = harness.dissect_segments(&d, &[b"\x05ab", b"cde", b"\x02x", b"y"]);
+        assert_eq!(dissections[0].desegment, Some((0, 3)));
+        assert!(messages(&dissections[0]).is_empty());
+        assert_eq!(messages(&dissections[1]), vec![text("abcde")]);
+        assert_eq!(dissections[1].desegment, None);
+        assert_eq!(dissections[2].desegment, Some((0, 1)));
+        assert_eq!(messages(&dissections[3]), vec![text("xy")]);
+        assert!(dissections.iter().all(|d| d.exception.is_none()));
+    }
+
+    #[test]
+    fn pdus_coalesced_in_segment() {
+        let mut d = pdu_dissector(true);
+        let harness = Harness::new(&mut d);
+        let dissections = harness.dissect_segments(&d, &[b"\x01a\x02bc\x03d", b"ef"]);
+        assert_eq!(messages(&dissections[0]), vec![text("a"), text("bc")]);
+        // The unfinished PDU starts at offset 5 and misses two bytes.
+        assert_eq!(dissections[0].desegment, Some((5, 2)));
+        assert_eq!(dissections[0].consumed, 7);
+        assert_eq!(messages(&dissections[1]), vec![text("def")]);
+    }
+
+    #[test]
+    fn pdu_exception_continues_with_next_pdu() {
+        let fields = [field(
+            "Message",
+            "test.message",
+            FieldType::STRING,
+            FieldDisplay::BASE_NONE,
+        )];
+        let mut d = TestDissector::new(&fields, |d, proto, tvb, pinfo| {
+            tcp_dissect_pdus(
+                proto,
+                tvb,
+                pinfo,
+                true,
+                1,
+                |tvb, offset| Some(1 + tvb.get_u8(offset).ok()? as usize),
+                |proto, pdu, _| {
+                    // Reads one byte too many for PDUs that start with an x.
+                    let extra = (pdu.get_u8(1) == Ok(b'x')) as usize;
+                    proto.add_item(
+                        d.hf[0],
+                        pdu,
+                        1,
+                        pdu.reported_length() - 1 + extra,
+                        proto::Encoding::UTF_8,
+                    );
+                    pdu.reported_length()
+                },
+            )
+        });
+        let harness = Harness::new(&mut d);
+        let dissection = harness.dissect_segments(&d, &[b"\x02xy\x02ab"]).remove(0);
+        assert_eq!(dissection.exception, None);
+        assert_eq!(dissection.reported_exceptions, vec![Exception::ReportedBoundsError]);
+        assert_eq!(messages(&dissection), vec![text("ab")]);
+    }
+
+    #[test]
+    fn pdu_without_length_needs_desegment() {
+        let mut d = TestDissector::new(&[], |_, proto, tvb, pinfo| {
+            tcp_dissect_pdus(
+                proto,
+                tvb,
+                pinfo,
+                false,
+                1,
+                |_, _| None,
+                |_, pdu, _| pdu.reported_length(),
+            )
+        });
+        let harness = Harness::new(&mut d);
+        let dissection = harness.dissect_segments(&d, &[b"\x02ab"]).remove(0);
+        assert!(matches!(dissection.exception, Some(Exception::DissectorError(_))));
+        assert_eq!(dissection.desegment, None);
+    }
+
+    #[test]
+    fn match_string_of_string_table() {
+        let mut d = TestDissector::new(&[], |_, _, tvb, pinfo| {
+            let media_type = pinfo.match_string().unwrap_or_else(|| "none".to_owned());
+            pinfo.col_add_str(Column::Info, &media_type);
+            tvb.reported_length()
+        });
+        let mut harness = Harness::new(&mut d);
+        let dissection = harness.dissect(&d, b"data");
+        assert_eq!(dissection.columns[&Column::Info], "none");
+
+        harness.set_packet(Packet {
+            match_string: Some("application/x-dummy".to_owned()),
+            ..Default::default()
+        });
+        let dissection = harness.dissect(&d, b"data");
+        assert_eq!(dissection.columns[&Column::Info], "application/x-dummy");
+    }
+
+    #[test]
+    fn reentrant_conversation_data_fails() {
+        let mut d = TestDissector::new(&[], |_, _, tvb, pinfo| {
+            let mut conversation = Conversation::find_or_create(pinfo);
+            conversation.set_data(1u32).unwrap();
+            let inner = conversation.with_data(|_: &mut u32| {
+                let mut again = conversation.clone();
+                (again.get_data::<u32>(), again.set_data(2u32))
+            });
+            assert_eq!(inner, Ok(Some((Err(DataError::Borrowed), Err(DataError::Borrowed)))));
+            assert_eq!(conversation.get_data::<u32>(), Ok(Some(1)));
+            tvb.reported_length()
+        });
+        let harness = Harness::new(&mut d);
+        let dissection = harness.dissect(&d, b"data");
+        assert_eq!(dissection.consumed, 4);
+    }
+
+    /// Run the checks on a buffer with the data.
+    fn with_buffer(data: &[u8], check: impl Fn(&TVB) + 'static) {
+        let mut d = TestDissector::new(&[], move |_, _, tvb, _| {
+            check(tvb);
+            tvb.reported_length()
+        });
+        let harness = Harness::new(&mut d);
+        assert_eq!(harness.dissect(&d, data).consumed, data.len());
+    }
+
+    #[test]
+    fn tvb_integer_getters() {
+        with_buffer(b"\x01\x02\x03\x04\x05\x06\x07\x08\xff\xfe", |tvb| {
+            use proto::Encoding::{BIG_ENDIAN, LITTLE_ENDIAN};
+            assert_eq!(tvb.get_u8(0), Ok(0x01));
+            assert_eq!(tvb.get_u16(0, BIG_ENDIAN), Ok(0x0102));
+            assert_eq!(tvb.get_u16(0, LITTLE_ENDIAN), Ok(0x0201));
+            assert_eq!(tvb.get_u24(1, BIG_ENDIAN), Ok(0x020304));
+            assert_eq!(tvb.get_u24(1, LITTLE_ENDIAN), Ok(0x040302));
+            assert_eq!(tvb.get_u32(0, BIG_ENDIAN), Ok(0x01020304));
+            assert_eq!(tvb.get_u32(0, LITTLE_ENDIAN), Ok(0x04030201));
+            assert_eq!(tvb.get_u64(0, BIG_ENDIAN), Ok(0x0102030405060708));
+            assert_eq!(tvb.get_u64(0, LITTLE_ENDIAN), Ok(0x0807060504030201));
+
+            assert_eq!(tvb.get_i8(8), Ok(-1));
+            assert_eq!(tvb.get_i16(8, BIG_ENDIAN), Ok(-2));
+            assert_eq!(tvb.get_i16(8, LITTLE_ENDIAN), Ok(-257));
+            // The 24 bit value is sign extended.
+            assert_eq!(tvb.get_i24(7, BIG_ENDIAN), Ok(0x08fffe));
+            assert_eq!(tvb.get_i24(7, LITTLE_ENDIAN), Ok(-0x0100f8));
+            assert_eq!(tvb.get_i32(6, LITTLE_ENDIAN), Ok(-0x0100f7f9));
+            assert_eq!(tvb.get_i64(2, LITTLE_ENDIAN), Ok(-0x0100f7f8f9fafbfd));
+        });
+    }
+
+    #[test]
+    fn tvb_float_guid_and_string_getters() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&1.5f32.to_be_bytes());
+        data.extend_from_slice(&(-0.25f64).to_le_bytes());
+        data.extend_from_slice(b"\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e\x0f");
+        data.extend_from_slice(b"abc\0de");
+        with_buffer(&data, |tvb| {
+            use proto::Encoding::{BIG_ENDIAN, LITTLE_ENDIAN};
+            assert_eq!(tvb.get_f32(0, BIG_ENDIAN), Ok(1.5));
+            assert_eq!(tvb.get_f64(4, LITTLE_ENDIAN), Ok(-0.25));
+
+            let guid = tvb.get_guid(12, BIG_ENDIAN).unwrap();
+            assert_eq!(guid.data1, 0x00010203);
+            assert_eq!(guid.data2, 0x0405);
+            assert_eq!(guid.data3, 0x0607);
+            assert_eq!(guid.data4, [0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f]);
+            let guid = tvb.get_guid(12, LITTLE_ENDIAN).unwrap();
+            assert_eq!((guid.data1, guid.data2, guid.data3), (0x03020100, 0x0504, 0x0706));
+
+            // The string ends at the null character.
+            assert_eq!(tvb.get_string(28, 6, proto::Encoding::UTF_8), Ok("abc".to_owned()));
+            assert_eq!(tvb.get_string(32, 2, proto::Encoding::ASCII), Ok("de".to_owned()));
+            assert_eq!(tvb.get_mem(28, 3), b"abc".to_vec());
+
+            // Variable length integers aren't a character encoding.
+            assert_eq!(
+                tvb.get_string(28, 6, proto::Encoding::VARINT_SDNV),
+                Err(TVBError::DecodeError)
+            );
+        });
+    }
+
+    #[test]
+    fn tvb_getters_out_of_bounds() {
+        with_buffer(b"\x01\x02\x03", |tvb| {
+            assert_eq!(tvb.get_u8(3), Err(TVBError::ReportedBoundsError));
+            assert_eq!(
+                tvb.get_u32(0, proto::Encoding::BIG_ENDIAN),
+                Err(TVBError::ReportedBoundsError)
+            );
+            assert_eq!(
+                tvb.get_string(2, 2, proto::Encoding::UTF_8),
+                Err(TVBError::ReportedBoundsError)
+            );
+            assert_eq!(tvb.ensure_bytes_exist(0, 3), Ok(()));
+            assert_eq!(
+                tvb.ensure_bytes_exist(usize::MAX, 2),
+                Err(TVBError::ReportedBoundsError)
+            );
+            assert_eq!(tvb.reported_length_remaining(1), 2);
+            assert_eq!(tvb.reported_length_remaining(5), 0);
+
+            // An out of bounds read with get_mem leaves the exception pending.
+            assert!(tvb.get_mem(2, 2).is_empty());
+            assert_eq!(exceptions::take_pending(), Some(Exception::ReportedBoundsError));
+            assert_eq!(tvb.try_get_mem(1, 2), Ok(vec![0x02, 0x03]));
+        });
+    }
+
+    #[test]
+    fn experts_added_to_tree_and_items() {
+        use crate::dissector::ExpertField;
+        use expert::{Group, Severity};
+        let fields = [field("Length", "test.length", FieldType::UINT8, FieldDisplay::BASE_DEC)];
+        let experts = [
+            ExpertField::simple("test.too_long", Group::MALFORMED, Severity::ERROR, "Length too long"),
+            ExpertField::simple("test.reserved", Group::PROTOCOL, Severity::WARN, "Reserved value"),
+        ];
+        let mut d = TestDissector::new(&fields, |d, proto, tvb, pinfo| {
+            let (mut item, length) = proto.add_item_ret_uint(d.hf[0], tvb, 0, 1, proto::Encoding::NA);
+            if length as usize >= tvb.reported_length() {
+                item.add_expert(pinfo, d.ei[0]);
+            }
+            proto.add_expert(pinfo, d.ei[1], tvb, 1, 1);
+            proto.add_expert_format(pinfo, d.ei[1], tvb, 2, 1, "Reserved value 0x55");
+            tvb.reported_length()
+        })
+        .with_experts(&experts);
+        let harness = Harness::new(&mut d);
+
+        // Each expert info is registered with a field of its own, after those of the dissector.
+        assert_eq!(d.ei[0].ei, 0);
+        assert_eq!(d.ei[1].ei, 1);
+        assert_eq!(harness.fields()[d.ei[1].hf as usize].abbrev, "test.reserved");
+        assert_eq!(harness.experts()[0].severity, Severity::ERROR);
+
+        let dissection = harness.dissect(&d, b"\x09\x00\x55");
+        let reported: Vec<(&str, &str)> = dissection
+            .experts
+            .iter()
+            .map(|e| (e.abbrev.as_str(), e.text.as_str()))
+            .collect();
+        assert_eq!(
+            reported,
+            vec![
+                ("test.too_long", "Length too long"),
+                ("test.reserved", "Reserved value"),
+                ("test.reserved", "Reserved value 0x55"),
+            ]
+        );
+        assert_eq!(dissection.experts[0].group, Group::MALFORMED);
+
+        // Attached to the item it flags, the others get an item of their own.
+        let length = dissection.find("test.length").unwrap();
+        assert_eq!(length.experts.len(), 1);
+        assert_eq!(length.experts[0].abbrev, "test.too_long");
+        let reserved = dissection.find_all("test.reserved");
+        assert_eq!(reserved.len(), 2);
+        assert_eq!((reserved[0].offset, reserved[0].length), (1, 1));
+        assert_eq!(reserved[1].experts[0].text, "Reserved value 0x55");
+
+        // Nothing is reported when the length fits.
+        let dissection = harness.dissect(&d, b"\x02\x00\x55");
+        assert_eq!(dissection.experts.len(), 2);
+        assert!(dissection.find("test.length").unwrap().experts.is_empty());
+    }
+
+    /// A packet from the source to the destination, with their addresses and ports.
+    fn between(number: u32, src: ([u8; 4], u32), dst: ([u8; 4], u32)) -> Packet {
+        Packet {
+            number,
+            src: Address::IPv4(src.0.into()),
+            src_port: src.1,
+            dst: Address::IPv4(dst.0.into()),
+            dst_port: dst.1,
+            ..Default::default()
+        }
+    }
+
+    /// Counts the packets of each conversation, the count of the packet is put in the info column.
+    fn counting_dissector() -> TestDissector {
+        TestDissector::new(&[], |_, _, tvb, pinfo| {
+            let mut conversation = Conversation::find_or_create(pinfo);
+            let count = match conversation.with_data(|count: &mut u32| {
+                *count += 1;
+                *count
+            }) {
+                Ok(Some(count)) => count,
+                _ => {
+                    conversation.set_data(1u32).unwrap();
+                    1
+                }
+            };
+            pinfo.col_add_str(Column::Info, &count.to_string());
+            tvb.reported_length()
+        })
+    }
+
+    #[test]
+    fn conversation_holds_data_of_both_directions() {
+        let mut d = counting_dissector();
+        let mut harness = Harness::new(&mut d);
+        let client = ([10, 0, 0, 1], 40000);
+        let server = ([10, 0, 0, 2], 80);
+        let other = ([10, 0, 0, 3], 40000);
+        let mut counts = Vec::new();
+        for packet in [
+            between(1, client, server),
+            between(2, server, client),
+            between(3, other, server),
+            between(4, client, server),
+        ] {
+            harness.set_packet(packet);
+            counts.push(harness.dissect(&d, b"data").columns[&Column::Info].clone());
+        }
+        assert_eq!(counts, vec!["1", "2", "1", "3"]);
+
+        // Data of another type is kept apart.
+        let mut d = TestDissector::new(&[], |_, _, tvb, pinfo| {
+            let mut conversation = Conversation::find_or_create(pinfo);
+            assert_eq!(conversation.get_data::<String>(), Ok(None));
+            conversation.set_data(String::from("text")).unwrap();
+            assert_eq!(conversation.get_data::<String>(), Ok(Some("text".to_owned())));
+            assert_eq!(conversation.get_data::<u32>(), Ok(None));
+            tvb.reported_length()
+        });
+        let harness = Harness::new(&mut d);
+        assert_eq!(harness.dissect(&d, b"data").consumed, 4);
+    }
+
+    #[test]
+    fn conversations_dropped_with_file() {
+        let mut d = counting_dissector();
+        let mut harness = Harness::new(&mut d);
+        harness.set_packet(between(1, ([10, 0, 0, 1], 40000), ([10, 0, 0, 2], 80)));
+        assert_eq!(harness.dissect(&d, b"data").columns[&Column::Info], "1");
+        assert_eq!(harness.dissect(&d, b"data").columns[&Column::Info], "2");
+        harness.new_file(&d);
+        assert_eq!(harness.dissect(&d, b"data").columns[&Column::Info], "1");
+    }
+
+    #[test]
+    fn proto_data_kept_per_packet_and_key() {
+        // Keeps a running total on the first pass, which depends on the order the packets are dissected in.
+        let total = std::rc::Rc::new(std::cell::Cell::new(0u32));
+        let running = total.clone();
+        let mut d = TestDissector::new(&[], move |_, _, tvb, pinfo| {
+            if !pinfo.visited() {
+                running.set(running.get() + tvb.get_u8(0).unwrap() as u32);
+                pinfo.set_proto_data(0, running.get()).unwrap();
+                pinfo.set_proto_data(1, 100 + running.get()).unwrap();
+                pinfo.set_proto_data(1, String::from("other type")).unwrap();
+            }
+            let stored = pinfo.get_proto_data::<u32>(0).unwrap();
+            let other_key = pinfo.get_proto_data::<u32>(1).unwrap();
+            pinfo.col_add_str(Column::Info, &format!("{:?} {:?}", stored, other_key));
+            tvb.reported_length()
+        });
+        let mut harness = Harness::new(&mut d);
+        let packets: [&[u8]; 3] = [b"\x01", b"\x02", b"\x03"];
+        let dissect = |harness: &mut Harness, number: usize, visited: bool| {
+            harness.set_packet(Packet {
+                number: number as u32 + 1,
+                visited,
+                ..Default::default()
+            });
+            harness.dissect(&d, packets[number]).columns[&Column::Info].clone()
+        };
+        for number in 0..3 {
+            dissect(&mut harness, number, false);
+        }
+        // Dissected again in another order, the values are those of the first pass.
+        assert_eq!(dissect(&mut harness, 2, true), "Some(6) Some(106)");
+        assert_eq!(dissect(&mut harness, 0, true), "Some(1) Some(101)");
+        assert_eq!(dissect(&mut harness, 1, true), "Some(3) Some(103)");
+        assert_eq!(total.get(), 6);
+
+        harness.new_file(&d);
+        assert_eq!(dissect(&mut harness, 2, true), "None None");
+    }
+
+    /// The other dissectors that were called, by name, with the offset and length of the data they consumed.
+    fn calls(dissection: &Dissection) -> Vec<(&str, usize, usize)> {
+        dissection
+            .events
+            .iter()
+            .filter_map(|e| match e {
+                mock::Event::CallDissector {
+                    dissector,
+                    offset,
+                    length,
+                } => Some((dissector.as_str(), *offset, *length)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Consumes the data if it starts with the byte.
+    fn starts_with(byte: u8) -> impl Fn(&[u8]) -> usize {
+        move |data: &[u8]| match data.first() {
+            Some(b) if *b == byte => data.len(),
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn call_dissector_by_name() {
+        let mut d = TestDissector::new(&[], |_, proto, tvb, pinfo| {
+            assert!(DissectorHandle::find(pinfo, "missing").is_none());
+            let json = DissectorHandle::find(pinfo, "json").unwrap();
+            let payload = tvb.subset_remaining(1).unwrap();
+            let only = json.call_only(proto, &payload, pinfo);
+            let called = json.call(proto, &payload, pinfo);
+            pinfo.col_add_str(Column::Info, &format!("{} {}", only, called));
+            1 + called
+        });
+        let mut harness = Harness::new(&mut d);
+        harness.add_dissector("json", starts_with(b'{'));
+
+        let dissection = harness.dissect(&d, b"x{}");
+        assert_eq!(dissection.columns[&Column::Info], "2 2");
+        assert_eq!(calls(&dissection), vec![("json", 1, 2), ("json", 1, 2)]);
+        assert_eq!(dissection.find("json").unwrap().offset, 1);
+
+        // Rejected data falls back to the data dissector, unless only the dissector itself is called.
+        let dissection = harness.dissect(&d, b"x[]");
+        assert_eq!(dissection.columns[&Column::Info], "0 2");
+        assert_eq!(calls(&dissection), vec![("data", 1, 2)]);
+        assert_eq!(dissection.consumed, 3);
+    }
+
+    #[test]
+    fn call_dissector_from_table() {
+        let mut d = TestDissector::new(&[], |_, proto, tvb, pinfo| {
+            assert!(DissectorTable::find(pinfo, "missing.table").is_none());
+            let ports = DissectorTable::find(pinfo, "udp.port").unwrap();
+            let media_types = DissectorTable::find(pinfo, "media_type").unwrap();
+            let payload = tvb.subset_remaining(1).unwrap();
+            let port = tvb.get_u8(0).unwrap() as u32;
+            let results = [
+                ports.try_uint(port, proto, &payload, pinfo),
+                ports.call_uint(port, proto, &payload, pinfo),
+                media_types.try_string("application/dns", proto, &payload, pinfo),
+                media_types.call_string("text/plain", proto, &payload, pinfo),
+            ];
+            pinfo.col_add_str(Column::Info, &format!("{:?}", results));
+            tvb.reported_length()
+        });
+        let mut harness = Harness::new(&mut d);
+        harness.add_dissector("dns", |data: &[u8]| data.len());
+        harness.add_table_uint("udp.port", 53, "dns");
+        harness.add_table_string("media_type", "application/dns", "dns");
+
+        let dissection = harness.dissect(&d, b"\x35abc");
+        assert_eq!(dissection.columns[&Column::Info], "[3, 3, 3, 3]");
+        assert_eq!(
+            calls(&dissection),
+            vec![("dns", 1, 3), ("dns", 1, 3), ("dns", 1, 3), ("data", 1, 3)]
+        );
+
+        // Nothing is registered for this port, only the call falls back to the data dissector.
+        let dissection = harness.dissect(&d, b"\x36abc");
+        assert_eq!(dissection.columns[&Column::Info], "[0, 3, 3, 3]");
+        assert_eq!(calls(&dissection)[0], ("data", 1, 3));
+    }
+
+    #[test]
+    fn try_heuristic_dissectors_in_order() {
+        let mut d = TestDissector::new(&[], |_, proto, tvb, pinfo| {
+            assert!(HeuristicList::find(pinfo, "tcp").is_none());
+            let list = HeuristicList::find(pinfo, "udp").unwrap();
+            list.try_dissect(proto, tvb, pinfo)
+        });
+        let mut harness = Harness::new(&mut d);
+        harness.add_dissector("first", starts_with(b'a'));
+        harness.add_dissector("second", |data: &[u8]| data.len());
+        harness.add_dissector("third", |data: &[u8]| data.len());
+        harness.add_heuristic("udp", "first");
+        harness.add_heuristic("udp", "second");
+        harness.add_heuristic("udp", "third");
+
+        let dissection = harness.dissect(&d, b"abc");
+        assert_eq!(calls(&dissection), vec![("first", 0, 3)]);
+        assert_eq!(dissection.consumed, 3);
+        let dissection = harness.dissect(&d, b"bc");
+        assert_eq!(calls(&dissection), vec![("second", 0, 2)]);
+    }
+
+    #[test]
+    fn dispatch_into_owned_tables() {
+        use crate::dissector::{DissectorTableInfo, DissectorTableKind};
+        let tables = [
+            DissectorTableInfo {
+                name: StringContainer::StaticStr("test.opcode"),
+                ui_name: StringContainer::StaticStr("Test opcode"),
+                kind: DissectorTableKind::UInt {
+                    field_type: FieldType::UINT8,
+                    display: FieldDisplay::BASE_HEX,
+                },
+            },
+            DissectorTableInfo {
+                name: StringContainer::StaticStr("test.content_type"),
+                ui_name: StringContainer::StaticStr("Test content type"),
+                kind: DissectorTableKind::String { case_sensitive: false },
+            },
+        ];
+        // An opcode byte, then a content type up to a space, then the payload.
+        let mut d = TestDissector::new(&[], |d, proto, tvb, pinfo| {
+            let payload = tvb.subset_remaining(1).unwrap();
+            let by_opcode = d.dissector_tables[0].try_uint(tvb.get_u8(0).unwrap() as u32, proto, &payload, pinfo);
+            let data = payload.get_mem(0, payload.reported_length());
+            let space = data.iter().position(|b| *b == b' ').unwrap();
+            let content_type = String::from_utf8(data[..space].to_vec()).unwrap();
+            let content = payload.subset_remaining(space + 1).unwrap();
+            let by_type = d.dissector_tables[1].call_string(&content_type, proto, &content, pinfo);
+            pinfo.col_add_str(Column::Info, &format!("{} {}", by_opcode, by_type));
+            tvb.reported_length()
+        })
+        .with_dissector_tables(&tables);
+        let mut harness = Harness::new(&mut d);
+        assert_eq!(d.dissector_tables.len(), 2);
+
+        harness.add_dissector("opcode_one", |data: &[u8]| data.len());
+        harness.add_dissector("text", |data: &[u8]| data.len());
+        harness.add_table_uint("test.opcode", 1, "opcode_one");
+        harness.add_table_string("test.content_type", "text/plain", "text");
+
+        let dissection = harness.dissect(&d, b"\x01text/plain abc");
+        assert_eq!(dissection.columns[&Column::Info], "14 3");
+        assert_eq!(calls(&dissection), vec![("opcode_one", 1, 14), ("text", 12, 3)]);
+
+        // The content type table is case insensitive, nothing is registered for opcode 2.
+        let dissection = harness.dissect(&d, b"\x02Text/Plain abc");
+        assert_eq!(dissection.columns[&Column::Info], "0 3");
+        assert_eq!(calls(&dissection), vec![("text", 12, 3)]);
+
+        // Unknown content types go to the data dissector.
+        let dissection = harness.dissect(&d, b"\x02image/png abc");
+        assert_eq!(calls(&dissection), vec![("data", 11, 3)]);
+    }
+
+    #[test]
+    fn register_in_string_tables() {
+        use crate::dissector::Registration;
+        let mut d = TestDissector::new(&[], |_, _, tvb, pinfo| {
+            let found = DissectorTable::find(pinfo, "media_type").is_some();
+            pinfo.col_add_str(Column::Info, &format!("{}", found));
+            tvb.reported_length()
+        })
+        .with_registrations(&[
+            Registration::String {
+                table: "media_type",
+                pattern: "application/x-test",
+            },
+            Registration::StringDecodeAs { abbrev: "ws.protocol" },
+        ]);
+        let harness = Harness::new(&mut d);
+        assert_eq!(
+            harness.table_string("media_type", "application/x-test"),
+            Some("test".to_owned())
+        );
+        assert_eq!(harness.table_string("media_type", "application/x-other"), None);
+        assert_eq!(harness.decode_as("ws.protocol"), vec!["test".to_owned()]);
+        assert!(harness.decode_as("media_type").is_empty());
+
+        // The tables it registers in exist for the dissector as well.
+        let dissection = harness.dissect(&d, b"data");
+        assert_eq!(dissection.columns[&Column::Info], "true");
     }
 }
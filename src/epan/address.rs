@@ -0,0 +1,24 @@
+// Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+// Follows address.h, with the built in address types from address_types.h.
+
+#![allow(non_upper_case_globals)]
+
+pub const AT_NONE: libc::c_int = 0;
+pub const AT_ETHER: libc::c_int = 1;
+pub const AT_IPv4: libc::c_int = 2;
+pub const AT_IPv6: libc::c_int = 3;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct address {
+    /// One of the AT_* address types.
+    pub type_: libc::c_int,
+    /// Length of the data.
+    pub len: libc::c_int,
+    /// Pointer to the address itself.
+    pub data: *const libc::c_void,
+    /// Only used by the address functions to hold allocated data.
+    pub priv_: *mut libc::c_void,
+}
@@ -0,0 +1,13 @@
+/*
+ * Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+ * SPDX-License-Identifier: GPL-2.0-or-later
+ *
+ * The column ids are an enum that changes between releases, export the values from the installed headers such that
+ * column_utils.rs doesn't have to hardcode them.
+ */
+
+#include <glib.h>
+#include <epan/column-info.h>
+
+const int wsrs_col_protocol = COL_PROTOCOL;
+const int wsrs_col_info = COL_INFO;
@@ -0,0 +1,46 @@
+// Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+// Follows column-utils.h, with the column ids from column-info.h.
+
+#[repr(C)]
+pub struct column_info {
+    _private: [u8; 0],
+}
+
+// The values of the column enum differ between versions, the shim exports the ones from the installed headers. The
+// build fails without the headers, so these always match the wireshark that is built against.
+#[cfg(feature = "wireshark")]
+extern "C" {
+    static wsrs_col_protocol: libc::c_int;
+    static wsrs_col_info: libc::c_int;
+}
+
+/// Id of the protocol column, `COL_PROTOCOL`.
+#[cfg(feature = "wireshark")]
+pub fn col_protocol() -> libc::c_int {
+    return unsafe { wsrs_col_protocol };
+}
+
+/// Id of the info column, `COL_INFO`.
+#[cfg(feature = "wireshark")]
+pub fn col_info() -> libc::c_int {
+    return unsafe { wsrs_col_info };
+}
+
+#[cfg(feature = "wireshark")]
+#[link(name = "wireshark")]
+extern "C" {
+    /// Set the column to a string that must outlive the packet, it's not copied.
+    pub fn col_set_str(cinfo: *mut column_info, col: libc::c_int, str: *const libc::c_char);
+    pub fn col_add_str(cinfo: *mut column_info, col: libc::c_int, str: *const libc::c_char);
+    pub fn col_append_str(cinfo: *mut column_info, col: libc::c_int, str: *const libc::c_char);
+    pub fn col_append_sep_str(
+        cinfo: *mut column_info,
+        col: libc::c_int,
+        sep: *const libc::c_char,
+        str: *const libc::c_char,
+    );
+    pub fn col_clear(cinfo: *mut column_info, col: libc::c_int);
+    pub fn col_set_fence(cinfo: *mut column_info, col: libc::c_int);
+}
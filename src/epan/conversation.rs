@@ -0,0 +1,28 @@
+// Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+// Follows conversation.h.
+
+#![allow(non_camel_case_types)]
+
+#[cfg(feature = "wireshark")]
+use super::packet::dissector_handle_t;
+#[cfg(feature = "wireshark")]
+use super::packet_info::packet_info;
+
+/// Opaque conversation_t struct
+#[repr(C)]
+pub struct conversation_t {
+    _private: [u8; 0],
+}
+
+#[cfg(feature = "wireshark")]
+#[link(name = "wireshark")]
+extern "C" {
+    /// Find the conversation by the addresses and ports of the packet, creating it if there is none yet.
+    pub fn find_or_create_conversation(pinfo: *mut packet_info) -> *mut conversation_t;
+    /// Replaces the data the protocol attached before, wireshark doesn't free it.
+    pub fn conversation_add_proto_data(conv: *mut conversation_t, proto: libc::c_int, proto_data: *mut libc::c_void);
+    pub fn conversation_get_proto_data(conv: *const conversation_t, proto: libc::c_int) -> *mut libc::c_void;
+    pub fn conversation_set_dissector(conversation: *mut conversation_t, handle: dissector_handle_t);
+}
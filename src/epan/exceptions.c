@@ -0,0 +1,32 @@
+/*
+ * Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+ * SPDX-License-Identifier: GPL-2.0-or-later
+ *
+ * Wireshark reports errors by longjmp'ing to the closest TRY block, which must not happen across Rust frames that
+ * still have to be dropped. Rust can't set up a TRY block itself, as it's a setjmp macro, so this runs a callback
+ * inside one and turns the exception into a return value. See exceptions.rs for the Rust side.
+ */
+
+#include <glib.h>
+#include <epan/exceptions.h>
+
+/*
+ * Run callback(data) inside TRY/CATCH_ALL. Returns the exception code, or zero if nothing was thrown. If the exception
+ * carried a message a copy is stored in message, which must be freed with g_free.
+ */
+unsigned long wsrs_try(void (*callback)(void *), void *data, char **message)
+{
+    volatile unsigned long code = 0;
+    *message = NULL;
+    TRY {
+        callback(data);
+    }
+    CATCH_ALL {
+        code = EXCEPT_CODE;
+        if (GET_MESSAGE != NULL) {
+            *message = g_strdup(GET_MESSAGE);
+        }
+    }
+    ENDTRY;
+    return code;
+}
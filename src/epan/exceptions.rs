@@ -0,0 +1,256 @@
+// Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+// Follows exceptions.h. Wireshark throws exceptions with longjmp, which skips the destructors of any Rust frames in
+// between, so every call that may throw is run through the TRY/CATCH in exceptions.c instead. The exception is then
+// either returned as error, or kept as pending exception; the dissector is told about it once it returns and any tree
+// operations in the meantime are ignored, like they would never have happened if the exception had unwound the stack.
+
+use std::cell::RefCell;
+
+#[cfg(feature = "wireshark")]
+use super::packet_info::packet_info;
+#[cfg(feature = "wireshark")]
+use super::proto::proto_tree;
+#[cfg(feature = "wireshark")]
+use super::tvbuff::tvbuff_t;
+
+// The exception codes from exceptions.h.
+const BOUNDS_ERROR: u64 = 1;
+const CONTAINED_BOUNDS_ERROR: u64 = 2;
+const REPORTED_BOUNDS_ERROR: u64 = 3;
+const TYPE_ERROR: u64 = 4;
+const DISSECTOR_ERROR: u64 = 5;
+const SCSU_ERROR: u64 = 6;
+const OUT_OF_MEMORY_ERROR: u64 = 7;
+const REASSEMBLY_ERROR: u64 = 8;
+
+/// An exception thrown by wireshark, or the equivalent from the mock backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Exception {
+    /// Read past the end of the captured data, but not past the reported length; the packet was cut short.
+    BoundsError,
+    /// Read past the end of a subset of the buffer, but not past the end of the packet.
+    ContainedBoundsError,
+    /// Read past the reported length; the packet is malformed.
+    ReportedBoundsError,
+    /// A field was used with a function that doesn't support its type.
+    TypeError,
+    /// Something is wrong in the dissector, also used for panics in Rust dissectors.
+    DissectorError(String),
+    /// Invalid character in a SCSU compressed string.
+    ScsuError,
+    /// Wireshark ran out of memory.
+    OutOfMemoryError,
+    /// Reassembly of fragments failed.
+    ReassemblyError(String),
+    /// Any other exception code.
+    Other(u64),
+}
+
+impl Exception {
+    /// Make the exception from the code and message wireshark threw.
+    fn from_code(code: u64, message: Option<String>) -> Exception {
+        let message = message.unwrap_or_default();
+        return match code {
+            BOUNDS_ERROR => Exception::BoundsError,
+            CONTAINED_BOUNDS_ERROR => Exception::ContainedBoundsError,
+            REPORTED_BOUNDS_ERROR => Exception::ReportedBoundsError,
+            TYPE_ERROR => Exception::TypeError,
+            DISSECTOR_ERROR => Exception::DissectorError(message),
+            SCSU_ERROR => Exception::ScsuError,
+            OUT_OF_MEMORY_ERROR => Exception::OutOfMemoryError,
+            REASSEMBLY_ERROR => Exception::ReassemblyError(message),
+            other => Exception::Other(other),
+        };
+    }
+
+    /// The exception code as defined in exceptions.h.
+    pub fn code(&self) -> u64 {
+        return match self {
+            Exception::BoundsError => BOUNDS_ERROR,
+            Exception::ContainedBoundsError => CONTAINED_BOUNDS_ERROR,
+            Exception::ReportedBoundsError => REPORTED_BOUNDS_ERROR,
+            Exception::TypeError => TYPE_ERROR,
+            Exception::DissectorError(_) => DISSECTOR_ERROR,
+            Exception::ScsuError => SCSU_ERROR,
+            Exception::OutOfMemoryError => OUT_OF_MEMORY_ERROR,
+            Exception::ReassemblyError(_) => REASSEMBLY_ERROR,
+            Exception::Other(code) => *code,
+        };
+    }
+
+    /// The message that goes with the exception, if any.
+    pub fn message(&self) -> Option<&str> {
+        return match self {
+            Exception::DissectorError(message) | Exception::ReassemblyError(message) => Some(message),
+            _ => None,
+        };
+    }
+}
+
+impl std::fmt::Display for Exception {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Exception::BoundsError => write!(f, "read past the end of the captured data"),
+            Exception::ContainedBoundsError => write!(f, "read past the end of a subset of the packet"),
+            Exception::ReportedBoundsError => write!(f, "read past the end of the packet"),
+            Exception::TypeError => write!(f, "field type not supported by this function"),
+            Exception::DissectorError(message) => write!(f, "dissector bug: {}", message),
+            Exception::ScsuError => write!(f, "invalid SCSU string"),
+            Exception::OutOfMemoryError => write!(f, "out of memory"),
+            Exception::ReassemblyError(message) => write!(f, "reassembly error: {}", message),
+            Exception::Other(code) => write!(f, "exception {}", code),
+        }
+    }
+}
+
+impl std::error::Error for Exception {}
+
+impl From<super::TVBError> for Exception {
+    fn from(error: super::TVBError) -> Self {
+        match error {
+            super::TVBError::BoundsError => Exception::BoundsError,
+            super::TVBError::ReportedBoundsError => Exception::ReportedBoundsError,
+            super::TVBError::DecodeError => Exception::DissectorError(error.to_string()),
+        }
+    }
+}
+
+impl From<Exception> for super::TVBError {
+    /// Reads that stay within the subset are treated as reads past the packet, exceptions that aren't about the
+    /// bounds mean the data couldn't be decoded.
+    fn from(exception: Exception) -> Self {
+        match exception {
+            Exception::BoundsError => super::TVBError::BoundsError,
+            Exception::ContainedBoundsError | Exception::ReportedBoundsError => super::TVBError::ReportedBoundsError,
+            _ => super::TVBError::DecodeError,
+        }
+    }
+}
+
+thread_local! {
+    /// The exception that stopped the current dissection, if any.
+    static PENDING: RefCell<Option<Exception>> = const { RefCell::new(None) };
+}
+
+/// The exception that is pending for the current dissection, if any. Once an exception is pending nothing is added to
+/// the tree anymore, and the exception is reported when the dissector returns.
+pub fn pending() -> Option<Exception> {
+    return PENDING.with(|p| p.borrow().clone());
+}
+
+/// Make the exception pending, unless there already is one; only the first exception is reported.
+pub(crate) fn set_pending(exception: Exception) {
+    PENDING.with(|p| {
+        p.borrow_mut().get_or_insert(exception);
+    });
+}
+
+/// Remove the pending exception and return it.
+pub(crate) fn take_pending() -> Option<Exception> {
+    return PENDING.with(|p| p.borrow_mut().take());
+}
+
+// Built from exceptions.c by the build script, which also takes care of linking it. The build fails if it can't be
+// built, so exceptions are always caught when wireshark is used.
+#[cfg(feature = "wireshark")]
+extern "C" {
+    fn wsrs_try(
+        callback: extern "C" fn(*mut libc::c_void),
+        data: *mut libc::c_void,
+        message: *mut *mut libc::c_char,
+    ) -> libc::c_ulong;
+}
+
+/// Without wireshark, only the mock backend is available, which never throws, so there is nothing to catch.
+#[cfg(not(feature = "wireshark"))]
+unsafe fn wsrs_try(
+    callback: extern "C" fn(*mut libc::c_void),
+    data: *mut libc::c_void,
+    message: *mut *mut libc::c_char,
+) -> libc::c_ulong {
+    *message = std::ptr::null_mut();
+    callback(data);
+    return 0;
+}
+
+/// Run f with wireshark's exception handler in place, an exception thrown by f is returned as error. If an exception
+/// is already pending f is not run, and that exception is returned instead.
+///
+/// Throwing an exception skips the rest of f without running destructors, so neither f nor its result may need to be
+/// dropped; this is checked at compile time. The function should be little more than the call into wireshark.
+pub(crate) fn catch<R, F: FnOnce() -> R>(f: F) -> Result<R, Exception> {
+    const {
+        assert!(!std::mem::needs_drop::<F>() && !std::mem::needs_drop::<R>());
+    }
+    if let Some(exception) = pending() {
+        return Err(exception);
+    }
+
+    struct Call<F, R> {
+        f: Option<F>,
+        result: Option<R>,
+    }
+    extern "C" fn trampoline<R, F: FnOnce() -> R>(data: *mut libc::c_void) {
+        let call = unsafe { &mut *(data as *mut Call<F, R>) };
+        if let Some(f) = call.f.take() {
+            call.result = Some(f());
+        }
+    }
+
+    let mut call = Call {
+        f: Some(f),
+        result: None,
+    };
+    let mut message: *mut libc::c_char = std::ptr::null_mut();
+    let code = unsafe {
+        wsrs_try(
+            trampoline::<R, F>,
+            &mut call as *mut Call<F, R> as *mut libc::c_void,
+            &mut message as *mut *mut libc::c_char,
+        )
+    };
+    if code == 0 {
+        if let Some(result) = call.result {
+            return Ok(result);
+        }
+    }
+
+    let mut text = None;
+    if !message.is_null() {
+        unsafe {
+            text = Some(std::ffi::CStr::from_ptr(message).to_string_lossy().into_owned());
+            #[cfg(feature = "wireshark")]
+            super::glib::g_free(message as *mut libc::c_void);
+        }
+    }
+    return Err(Exception::from_code(code as u64, text));
+}
+
+/// Like [`catch()`], but the exception is made pending instead of returned, None is returned in that case.
+pub(crate) fn contain<R, F: FnOnce() -> R>(f: F) -> Option<R> {
+    match catch(f) {
+        Ok(v) => return Some(v),
+        Err(exception) => {
+            set_pending(exception);
+            return None;
+        }
+    }
+}
+
+/// Report the exception like wireshark does when it reaches the dissector that called ours, this adds the malformed
+/// packet item and the expert info to the tree.
+#[cfg(feature = "wireshark")]
+pub(crate) unsafe fn report(tvb: *mut tvbuff_t, pinfo: *mut packet_info, tree: *mut proto_tree, exception: &Exception) {
+    let message = exception
+        .message()
+        .map(|m| std::ffi::CString::new(m.replace('\0', "")).unwrap());
+    super::show_exception::show_exception(
+        tvb,
+        pinfo,
+        tree,
+        exception.code() as libc::c_ulong,
+        message.as_ref().map_or(std::ptr::null(), |m| m.as_ptr()),
+    );
+}
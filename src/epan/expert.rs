@@ -0,0 +1,144 @@
+// Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+// Follows expert.h, with the severities and groups from proto.h.
+
+#![allow(non_camel_case_types)]
+
+#[cfg(feature = "wireshark")]
+use super::packet_info::packet_info;
+use super::proto::hf_register_info;
+#[cfg(feature = "wireshark")]
+use super::proto::{proto_item, proto_tree};
+#[cfg(feature = "wireshark")]
+use super::tvbuff::tvbuff_t;
+
+/// How bad the problem is that the expert info points out, `PI_SEVERITY_MASK` in proto.h.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Packet comment, normally only used by wireshark itself.
+    COMMENT = 0x00100000,
+    /// Usual workflow, for example a TCP connection being established.
+    CHAT = 0x00200000,
+    /// Notable but not unusual, for example an HTTP 404.
+    NOTE = 0x00400000,
+    /// Possible problem, for example a response code indicating an error.
+    WARN = 0x00600000,
+    /// Serious problem, for example a malformed packet.
+    ERROR = 0x00800000,
+}
+
+/// The category of the expert info, `PI_GROUP_MASK` in proto.h.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Group {
+    /// A checksum was invalid.
+    CHECKSUM = 0x01000000,
+    /// Sequence number problem, for example a retransmission or a missing packet.
+    SEQUENCE = 0x02000000,
+    /// A response code that is noteworthy.
+    RESPONSE_CODE = 0x03000000,
+    /// A request code that is noteworthy.
+    REQUEST_CODE = 0x04000000,
+    /// Data that the dissector doesn't decode (yet).
+    UNDECODED = 0x05000000,
+    /// A problem while reassembling.
+    REASSEMBLE = 0x06000000,
+    /// The packet is malformed, the dissector can't continue.
+    MALFORMED = 0x07000000,
+    /// Debugging information from the dissector, shouldn't show up in releases.
+    DEBUG = 0x08000000,
+    /// The packet violates the protocol specification.
+    PROTOCOL = 0x09000000,
+    /// A security problem, for example an insecure implementation.
+    SECURITY = 0x0a000000,
+    /// A comment on the packet.
+    COMMENTS_GROUP = 0x0b000000,
+    /// A problem while decrypting.
+    DECRYPTION = 0x0c000000,
+    /// The dissector made an assumption, for example because the data was ambiguous.
+    ASSUMPTION = 0x0d000000,
+    /// The packet uses a deprecated feature of the protocol.
+    DEPRECATED = 0x0e000000,
+}
+
+/// EI index, this should NEVER be instantiated by the user, they are returned by expert_register_field_array. This is
+/// the `expert_field` struct, which holds the expert id as well as the header field that makes it filterable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub struct EIIndex {
+    pub ei: i32,
+    pub hf: i32,
+}
+
+impl Default for EIIndex {
+    /// The unregistered value, `EI_INIT`.
+    fn default() -> Self {
+        EIIndex { ei: -1, hf: -1 }
+    }
+}
+
+#[repr(C)]
+pub struct expert_field_info {
+    // Set by the dissector.
+    pub name: *const libc::c_char,
+    pub group: libc::c_int,
+    pub severity: libc::c_int,
+    pub summary: *const libc::c_char,
+
+    // Set by the registration, prefilled by EXPFILL.
+    pub id: libc::c_int,
+    pub protocol: *const libc::c_char,
+    pub orig_severity: libc::c_int,
+    pub hf_info: hf_register_info,
+}
+
+#[repr(C)]
+pub struct ei_register_info {
+    pub ids: *mut EIIndex,         // written to by register() function
+    pub eiinfo: expert_field_info, // the expert info to be registered
+}
+
+#[repr(C)]
+pub struct expert_module_t {
+    _private: [u8; 0],
+}
+
+#[cfg(feature = "wireshark")]
+#[link(name = "wireshark")]
+extern "C" {
+    pub fn expert_register_protocol(id: libc::c_int) -> *mut expert_module_t;
+    pub fn expert_register_field_array(
+        module: *mut expert_module_t,
+        ei: *mut ei_register_info,
+        num_records: libc::c_int,
+    );
+
+    pub fn expert_add_info(pinfo: *mut packet_info, pi: *mut proto_item, eiindex: *const EIIndex);
+    pub fn expert_add_info_format(
+        pinfo: *mut packet_info,
+        pi: *mut proto_item,
+        eiindex: *const EIIndex,
+        format: *const libc::c_char,
+        ...
+    );
+    pub fn proto_tree_add_expert(
+        tree: *mut proto_tree,
+        pinfo: *mut packet_info,
+        eiindex: *const EIIndex,
+        tvb: *mut tvbuff_t,
+        start: libc::c_int,
+        length: libc::c_int,
+    ) -> *mut proto_item;
+    pub fn proto_tree_add_expert_format(
+        tree: *mut proto_tree,
+        pinfo: *mut packet_info,
+        eiindex: *const EIIndex,
+        tvb: *mut tvbuff_t,
+        start: libc::c_int,
+        length: libc::c_int,
+        format: *const libc::c_char,
+        ...
+    ) -> *mut proto_item;
+}
@@ -0,0 +1,15 @@
+/*
+ * Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+ * SPDX-License-Identifier: GPL-2.0-or-later
+ *
+ * The flags of frame_data are bitfields, which can't be described on the Rust side. See frame_data.rs.
+ */
+
+#include <glib.h>
+#include <epan/packet_info.h>
+#include <epan/frame_data.h>
+
+int wsrs_pinfo_fd_visited(const packet_info *pinfo)
+{
+    return pinfo->fd->visited;
+}
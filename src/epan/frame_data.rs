@@ -0,0 +1,27 @@
+// Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+// Follows frame_data.h. The flags are bitfields that moved around between releases, so they are read through the shim.
+
+#[cfg(feature = "wireshark")]
+use super::packet_info::packet_info;
+
+#[repr(C)]
+pub struct frame_data {
+    _private: [u8; 0],
+}
+
+// Built from frame_data.c by the build script, which fails without the wireshark headers.
+#[cfg(feature = "wireshark")]
+extern "C" {
+    fn wsrs_pinfo_fd_visited(pinfo: *const packet_info) -> libc::c_int;
+}
+
+/// Whether the frame of this packet was dissected before, `PINFO_FD_VISITED`.
+///
+/// # Safety
+/// The pointer must point to the valid packet_info of the packet that is being dissected.
+#[cfg(feature = "wireshark")]
+pub unsafe fn pinfo_fd_visited(pinfo: *const packet_info) -> bool {
+    return wsrs_pinfo_fd_visited(pinfo) != 0;
+}
@@ -5,7 +5,7 @@ use core::fmt::Debug;
 
 #[repr(C)]
 #[allow(dead_code)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ftenum {
     NONE, /* used for text labels with no value */
     PROTOCOL,
@@ -64,11 +64,29 @@ impl Default for ftenum {
 
 unsafe impl Send for ftenum {}
 
+#[cfg(feature = "bindgen")]
+impl ftenum {
+    /// Convert the value of the C enum as found in the generated structs, those hold it as a plain integer.
+    pub(crate) fn from_raw(value: u32) -> ftenum {
+        if value >= ftenum::NUM_TYPES as u32 {
+            return ftenum::NONE;
+        }
+        return unsafe { std::mem::transmute::<u32, ftenum>(value) };
+    }
+}
+
+// The generated fvalue_t with the bindgen feature, the hand written one below is the fallback for builds without
+// libclang.
+#[cfg(feature = "bindgen")]
+pub use super::sys::fvalue_t;
+
+#[cfg(not(feature = "bindgen"))]
 #[repr(C)]
 pub struct ftype_t {
     _private: [u8; 0],
 }
 
+#[cfg(not(any(ws_4_0, feature = "bindgen")))]
 #[repr(C)]
 pub union fvalue_t_value_union {
     /* Put a few basic types in here */
@@ -93,12 +111,14 @@ pub union fvalue_t_value_union {
     _size: [u8; 24], // determined with sizeof from C.
 }
 
+#[cfg(not(any(ws_4_0, feature = "bindgen")))]
 impl Debug for fvalue_t_value_union {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "fvalue_t_value_union")
     }
 }
 
+#[cfg(not(any(ws_4_0, feature = "bindgen")))]
 #[derive(Debug)]
 #[repr(C)]
 pub struct fvalue_t {
@@ -110,6 +130,14 @@ pub struct fvalue_t {
     fvalue_gboolean1: bool,
 }
 
+/// From 4.0 onwards the fvalue_t struct is private, it can only be used through a pointer.
+#[cfg(all(ws_4_0, not(feature = "bindgen")))]
+#[repr(C)]
+pub struct fvalue_t {
+    _private: [u8; 0],
+}
+
+#[cfg(feature = "wireshark")]
 #[link(name = "wireshark")]
 extern "C" {
     pub fn fvalue_type_ftenum(fv: *const fvalue_t) -> ftenum;
@@ -120,6 +148,7 @@ extern "C" {
     pub fn fvalue_get_sinteger64(fv: *const fvalue_t) -> i64;
     pub fn fvalue_get_floating(fv: *const fvalue_t) -> f64;
 
+    #[cfg(not(ws_4_0))]
     pub fn fvalue_get(fv: *const fvalue_t) -> *const libc::c_void;
 
     //~ pub fn fvalue_type_name(fv: *const fvalue_t) -> *const libc::c_char;  // Doesn't exist after linking...?
@@ -20,7 +20,14 @@ impl GPtrArray {
     }
 }
 
+/// Log level for warnings, `G_LOG_LEVEL_WARNING` in gmessages.h.
+pub const G_LOG_LEVEL_WARNING: libc::c_int = 1 << 4;
+
+#[cfg(feature = "wireshark")]
 #[link(name = "glib-2.0")]
 extern "C" {
     pub fn g_ptr_array_free(array: *mut GPtrArray, free_seg: bool);
+    pub fn g_free(mem: *mut libc::c_void);
+    pub fn g_malloc(n_bytes: usize) -> *mut libc::c_void;
+    pub fn g_log(log_domain: *const libc::c_char, log_level: libc::c_int, format: *const libc::c_char, ...);
 }
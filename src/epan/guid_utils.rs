@@ -0,0 +1,38 @@
+// Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+/// A GUID, the integers are in host byte order.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct e_guid_t {
+    pub data1: u32,
+    pub data2: u16,
+    pub data3: u16,
+    pub data4: [u8; 8],
+}
+
+impl e_guid_t {
+    /// The GUID in the order it is normally written, the integers in big endian.
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[0..4].copy_from_slice(&self.data1.to_be_bytes());
+        bytes[4..6].copy_from_slice(&self.data2.to_be_bytes());
+        bytes[6..8].copy_from_slice(&self.data3.to_be_bytes());
+        bytes[8..16].copy_from_slice(&self.data4);
+        return bytes;
+    }
+}
+
+impl From<[u8; 16]> for e_guid_t {
+    /// Create the GUID from the order it is normally written in, the integers in big endian.
+    fn from(bytes: [u8; 16]) -> Self {
+        let mut data4 = [0; 8];
+        data4.copy_from_slice(&bytes[8..16]);
+        e_guid_t {
+            data1: u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            data2: u16::from_be_bytes([bytes[4], bytes[5]]),
+            data3: u16::from_be_bytes([bytes[6], bytes[7]]),
+            data4,
+        }
+    }
+}
@@ -0,0 +1,58 @@
+// Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+// This one lives in wsutil/nstime.h, but it's used throughout epan.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Time with nanosecond resolution, either relative or since the epoch.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct nstime_t {
+    pub secs: libc::time_t,
+    pub nsecs: libc::c_int,
+}
+
+impl std::ops::Sub for nstime_t {
+    type Output = nstime_t;
+
+    /// The relative time between two times, like `nstime_delta`.
+    fn sub(self, other: nstime_t) -> nstime_t {
+        let mut secs = self.secs - other.secs;
+        let mut nsecs = self.nsecs - other.nsecs;
+        if nsecs < 0 {
+            secs -= 1;
+            nsecs += 1_000_000_000;
+        }
+        nstime_t { secs, nsecs }
+    }
+}
+
+impl From<Duration> for nstime_t {
+    /// A relative time.
+    fn from(duration: Duration) -> Self {
+        nstime_t {
+            secs: duration.as_secs() as libc::time_t,
+            nsecs: duration.subsec_nanos() as libc::c_int,
+        }
+    }
+}
+
+impl From<SystemTime> for nstime_t {
+    /// An absolute time, times before the epoch have negative seconds and positive nanoseconds.
+    fn from(time: SystemTime) -> Self {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(since) => since.into(),
+            Err(e) => {
+                let before = e.duration();
+                let mut secs = -(before.as_secs() as libc::time_t);
+                let mut nsecs = before.subsec_nanos() as libc::c_int;
+                if nsecs != 0 {
+                    secs -= 1;
+                    nsecs = 1_000_000_000 - nsecs;
+                }
+                nstime_t { secs, nsecs }
+            }
+        }
+    }
+}
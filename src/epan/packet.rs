@@ -1,19 +1,52 @@
 // Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
 // SPDX-License-Identifier: GPL-2.0-or-later
 
+#[cfg(feature = "wireshark")]
+use super::ftypes::ftenum;
 use super::packet_info::packet_info;
 use super::proto::proto_tree;
 use super::proto::protocol_t;
+#[cfg(feature = "wireshark")]
 use super::range::epan_range;
 use super::tvbuff::tvbuff_t;
 
+// With the bindgen feature the handles are the generated ones from the sys module, the hand written ones below are the
+// fallback for builds without libclang.
+#[cfg(feature = "bindgen")]
+pub use super::sys::{
+    dissector_handle, dissector_handle_t, dissector_table, dissector_table_t, heur_dissector_list,
+    heur_dissector_list_t,
+};
+
+#[cfg(not(feature = "bindgen"))]
 #[repr(C)]
 pub struct dissector_handle {
     _private: [u8; 0],
 }
-type dissector_handle_t = *mut dissector_handle;
+#[cfg(not(feature = "bindgen"))]
+pub type dissector_handle_t = *mut dissector_handle;
+
+pub const STRING_CASE_SENSITIVE: libc::c_int = 0;
+pub const STRING_CASE_INSENSITIVE: libc::c_int = 1;
 
-type dissector_t = Option<extern "C" fn(*mut tvbuff_t, *mut packet_info, *mut proto_tree, *mut libc::c_void) -> i32>;
+#[cfg(not(feature = "bindgen"))]
+#[repr(C)]
+pub struct dissector_table {
+    _private: [u8; 0],
+}
+#[cfg(not(feature = "bindgen"))]
+pub type dissector_table_t = *mut dissector_table;
+
+#[cfg(not(feature = "bindgen"))]
+#[repr(C)]
+pub struct heur_dissector_list {
+    _private: [u8; 0],
+}
+#[cfg(not(feature = "bindgen"))]
+pub type heur_dissector_list_t = *mut heur_dissector_list;
+
+pub type dissector_t =
+    Option<extern "C" fn(*mut tvbuff_t, *mut packet_info, *mut proto_tree, *mut libc::c_void) -> i32>;
 
 type heur_dissector_t =
     Option<extern "C" fn(*mut tvbuff_t, *mut packet_info, *mut proto_tree, *mut libc::c_void) -> bool>;
@@ -36,12 +69,13 @@ pub struct heur_dtbl_entry_t {
     pub enabled: bool,
 }
 
+#[cfg(feature = "wireshark")]
 #[link(name = "wireshark")]
 extern "C" {
     pub fn register_postdissector(handle: dissector_handle_t);
     pub fn dissector_add_uint(abbrev: *const libc::c_char, pattern: u32, handle: dissector_handle_t);
     pub fn dissector_add_uint_range(abbrev: *const libc::c_char, range: *const epan_range, handle: dissector_handle_t);
-    //~ pub fn dissector_add_string(name: *const libc::c_char, pattern: *const libc::c_char, handle: dissector_handle_t);
+    pub fn dissector_add_string(name: *const libc::c_char, pattern: *const libc::c_char, handle: dissector_handle_t);
     pub fn dissector_add_for_decode_as(name: *const libc::c_char, handle: dissector_handle_t);
 
     pub fn create_dissector_handle(dissector: dissector_t, proto: i32) -> dissector_handle_t;
@@ -54,5 +88,68 @@ extern "C" {
         proto: i32,
         enable: heuristic_enable_e,
     );
+    /// Show the buffer as its own tab in the bytes pane, the name is copied.
+    pub fn add_new_data_source(pinfo: *mut packet_info, tvb: *mut tvbuff_t, name: *const libc::c_char);
+
     pub fn find_heur_dissector_by_unique_short_name(short_name: *const libc::c_char) -> *mut heur_dtbl_entry_t;
+
+    // Calling other dissectors, these return the number of bytes the dissector took, 0 if it rejected the data.
+    pub fn find_dissector(name: *const libc::c_char) -> dissector_handle_t;
+    /// Falls back to the data dissector if the dissector rejects the data.
+    pub fn call_dissector(
+        handle: dissector_handle_t,
+        tvb: *mut tvbuff_t,
+        pinfo: *mut packet_info,
+        tree: *mut proto_tree,
+    ) -> libc::c_int;
+    pub fn call_dissector_only(
+        handle: dissector_handle_t,
+        tvb: *mut tvbuff_t,
+        pinfo: *mut packet_info,
+        tree: *mut proto_tree,
+        data: *mut libc::c_void,
+    ) -> libc::c_int;
+    pub fn call_data_dissector(tvb: *mut tvbuff_t, pinfo: *mut packet_info, tree: *mut proto_tree) -> libc::c_int;
+
+    pub fn find_dissector_table(name: *const libc::c_char) -> dissector_table_t;
+    /// The param is the display base for integer tables, or one of the STRING_CASE_* values for string tables.
+    pub fn register_dissector_table(
+        name: *const libc::c_char,
+        ui_name: *const libc::c_char,
+        proto: i32,
+        field_type: ftenum,
+        param: libc::c_int,
+    ) -> dissector_table_t;
+    pub fn dissector_try_uint_new(
+        sub_dissectors: dissector_table_t,
+        uint_val: u32,
+        tvb: *mut tvbuff_t,
+        pinfo: *mut packet_info,
+        tree: *mut proto_tree,
+        add_proto_name: bool,
+        data: *mut libc::c_void,
+    ) -> libc::c_int;
+    pub fn dissector_try_string(
+        sub_dissectors: dissector_table_t,
+        string: *const libc::c_char,
+        tvb: *mut tvbuff_t,
+        pinfo: *mut packet_info,
+        tree: *mut proto_tree,
+        data: *mut libc::c_void,
+    ) -> libc::c_int;
+
+    pub fn find_heur_dissector_list(name: *const libc::c_char) -> heur_dissector_list_t;
+    pub fn dissector_try_heuristic(
+        sub_dissectors: heur_dissector_list_t,
+        tvb: *mut tvbuff_t,
+        pinfo: *mut packet_info,
+        tree: *mut proto_tree,
+        hdtbl_entry: *mut *mut heur_dtbl_entry_t,
+        data: *mut libc::c_void,
+    ) -> bool;
+
+    /// Called before a capture file is dissected, and when it is dissected again.
+    pub fn register_init_routine(func: Option<extern "C" fn()>);
+    /// Called when the dissection of a capture file is done.
+    pub fn register_cleanup_routine(func: Option<extern "C" fn()>);
 }
@@ -1,8 +1,69 @@
 // Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
 // SPDX-License-Identifier: GPL-2.0-or-later
 
+use super::address::address;
+use super::column_utils::column_info;
+use super::frame_data::frame_data;
+use super::nstime::nstime_t;
+
+pub const P2P_DIR_UNKNOWN: libc::c_int = -1;
+pub const P2P_DIR_SENT: libc::c_int = 0;
+pub const P2P_DIR_RECV: libc::c_int = 1;
+
+// packet_info is enormous, only the first part of it, up to p2p_dir, is mirrored here. It's only ever accessed through
+// a pointer handed to us by wireshark, so the remainder can be left out.
 #[repr(C)]
 pub struct packet_info {
-    _private: [u8; 0],
+    /// Name of the protocol currently being dissected.
+    pub current_proto: *const libc::c_char,
+    /// The columns of the packet list, may be null if no columns are needed.
+    pub cinfo: *mut column_info,
+    pub presence_flags: u32,
+    /// Frame number.
+    pub num: u32,
+    /// Absolute timestamp of the packet.
+    pub abs_ts: nstime_t,
+    /// Timestamp relative to the first packet, can be negative.
+    pub rel_ts: nstime_t,
+    pub fd: *mut frame_data,
+    pub pseudo_header: *mut libc::c_void,
+    pub rec: *mut libc::c_void,
+    pub data_src: *mut libc::c_void,
+    pub dl_src: address,
+    pub dl_dst: address,
+    pub net_src: address,
+    pub net_dst: address,
+    /// Source address, the network layer address if there is one, the link layer address otherwise.
+    pub src: address,
+    /// Destination address, like src.
+    pub dst: address,
+    pub vlan_id: u32,
+    pub noreassembly_reason: *const libc::c_char,
+    pub fragmented: libc::c_int,
+    pub flags: u32,
+    /// Type of the ports, port_type enum.
+    pub ptype: libc::c_int,
+    pub srcport: u32,
+    pub destport: u32,
+    /// The value in the dissector table that caused this dissector to be called.
+    pub match_uint: u32,
+    pub match_string: *const libc::c_char,
+    #[cfg(not(ws_4_0))]
+    pub use_endpoint: libc::c_int,
+    #[cfg(not(ws_4_0))]
+    pub conv_endpoint: *mut libc::c_void,
+    #[cfg(ws_4_0)]
+    pub use_conv_addr_port_endpoints: libc::c_int,
+    #[cfg(ws_4_0)]
+    pub conv_addr_port_endpoints: *mut libc::c_void,
+    #[cfg(ws_4_0)]
+    pub conv_elements: *mut libc::c_void,
+    pub can_desegment: u16,
+    pub saved_can_desegment: u16,
+    pub desegment_offset: libc::c_int,
+    pub desegment_len: u32,
+    pub want_pdu_tracking: u16,
+    pub bytes_until_next_pdu: u32,
+    /// Direction of peer to peer traffic, one of the P2P_DIR_* values.
+    pub p2p_dir: libc::c_int,
 }
-// Hmm, packet_info is enormous, but we have to reach into it for column info. Let skip that for now.
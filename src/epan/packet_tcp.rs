@@ -0,0 +1,36 @@
+// Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+// Follows dissectors/packet-tcp.h, the helper for protocols that send their PDUs over a TCP stream.
+
+#![allow(non_camel_case_types)]
+
+#[cfg(feature = "wireshark")]
+use super::packet::dissector_t;
+use super::packet_info::packet_info;
+#[cfg(feature = "wireshark")]
+use super::proto::proto_tree;
+use super::tvbuff::tvbuff_t;
+
+/// Value for `desegment_len` to ask for one more segment, when it is not known how many bytes are missing.
+pub const DESEGMENT_ONE_MORE_SEGMENT: u32 = 0x0fffffff;
+/// Value for `desegment_len` to ask for all data until the connection is closed.
+pub const DESEGMENT_UNTIL_FIN: u32 = 0x0ffffffe;
+
+/// Returns the length of the PDU at the offset, 0 if more data is needed to determine it.
+pub type get_pdu_len_t = Option<extern "C" fn(*mut packet_info, *mut tvbuff_t, libc::c_int, *mut libc::c_void) -> u32>;
+
+#[cfg(feature = "wireshark")]
+#[link(name = "wireshark")]
+extern "C" {
+    pub fn tcp_dissect_pdus(
+        tvb: *mut tvbuff_t,
+        pinfo: *mut packet_info,
+        tree: *mut proto_tree,
+        proto_desegment: bool,
+        fixed_len: u32,
+        get_pdu_len: get_pdu_len_t,
+        dissect_pdu: dissector_t,
+        dissector_data: *mut libc::c_void,
+    );
+}
@@ -0,0 +1,98 @@
+// Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+// Follows prefs.h. Wireshark writes the values of the preferences into the variables that are registered with them, so
+// these must live as long as the plugin does.
+
+#![allow(non_camel_case_types)]
+
+#[cfg(feature = "wireshark")]
+use super::range::range_t;
+
+#[repr(C)]
+pub struct module_t {
+    _private: [u8; 0],
+}
+
+/// An entry of an enum preference, the list is terminated by an entry with a null name.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct enum_val_t {
+    pub name: *const libc::c_char,
+    pub description: *const libc::c_char,
+    pub value: libc::c_int,
+}
+impl Default for enum_val_t {
+    fn default() -> Self {
+        enum_val_t {
+            name: std::ptr::null(),
+            description: std::ptr::null(),
+            value: 0,
+        }
+    }
+}
+
+#[cfg(feature = "wireshark")]
+#[link(name = "wireshark")]
+extern "C" {
+    pub fn prefs_register_protocol(id: libc::c_int, apply_cb: Option<extern "C" fn()>) -> *mut module_t;
+
+    pub fn prefs_register_uint_preference(
+        module: *mut module_t,
+        name: *const libc::c_char,
+        title: *const libc::c_char,
+        description: *const libc::c_char,
+        base: libc::c_uint,
+        var: *mut libc::c_uint,
+    );
+    #[cfg(not(ws_4_2))]
+    pub fn prefs_register_bool_preference(
+        module: *mut module_t,
+        name: *const libc::c_char,
+        title: *const libc::c_char,
+        description: *const libc::c_char,
+        var: *mut libc::c_int,
+    );
+    #[cfg(ws_4_2)]
+    pub fn prefs_register_bool_preference(
+        module: *mut module_t,
+        name: *const libc::c_char,
+        title: *const libc::c_char,
+        description: *const libc::c_char,
+        var: *mut bool,
+    );
+    /// The string is copied into memory owned by wireshark, which replaces it when the preference changes.
+    pub fn prefs_register_string_preference(
+        module: *mut module_t,
+        name: *const libc::c_char,
+        title: *const libc::c_char,
+        description: *const libc::c_char,
+        var: *mut *const libc::c_char,
+    );
+    pub fn prefs_register_enum_preference(
+        module: *mut module_t,
+        name: *const libc::c_char,
+        title: *const libc::c_char,
+        description: *const libc::c_char,
+        var: *mut libc::c_int,
+        enumvals: *const enum_val_t,
+        radio_buttons: libc::c_int,
+    );
+    /// The range must be allocated in the epan scope, wireshark frees it when the preference changes.
+    pub fn prefs_register_range_preference(
+        module: *mut module_t,
+        name: *const libc::c_char,
+        title: *const libc::c_char,
+        description: *const libc::c_char,
+        var: *mut *mut range_t,
+        max_value: u32,
+    );
+    pub fn prefs_register_filename_preference(
+        module: *mut module_t,
+        name: *const libc::c_char,
+        title: *const libc::c_char,
+        description: *const libc::c_char,
+        var: *mut *const libc::c_char,
+        for_writing: libc::c_int,
+    );
+}
@@ -4,10 +4,19 @@
 #![allow(non_camel_case_types)]
 #![allow(dead_code)]
 
+#[cfg(not(feature = "bindgen"))]
 use super::ftypes::ftenum;
+#[cfg(not(feature = "bindgen"))]
 use super::ftypes::fvalue_t;
+#[cfg(feature = "wireshark")]
 use super::glib::GPtrArray;
+#[cfg(feature = "wireshark")]
+use super::guid_utils::e_guid_t;
+#[cfg(feature = "wireshark")]
+use super::nstime::nstime_t;
 use super::tvbuff::tvbuff_t;
+#[cfg(feature = "wireshark")]
+use super::wmem::wmem_allocator_t;
 
 #[repr(u32)]
 #[derive(Clone, Copy, Debug)]
@@ -18,6 +27,24 @@ pub enum Encoding {
     STR_HEX = 0x02000000,
     STRING = 0x03000000,
     STR_MASK = 0x0000FFFE,
+
+    /* Character encodings for strings */
+    UTF_8 = 0x00000002,
+    UTF_16 = 0x00000004,
+    UCS_2 = 0x00000006,
+    UCS_4 = 0x00000008,
+    ISO_8859_1 = 0x0000000A,
+
+    /* Variable length integers, the others share values with the character encodings */
+    VARINT_SDNV = 0x00000010,
+}
+impl Encoding {
+    /// For items that don't have an encoding, like byte arrays or values that don't come from the buffer.
+    pub const NA: Encoding = Encoding::BIG_ENDIAN;
+    pub const ASCII: Encoding = Encoding::BIG_ENDIAN;
+    pub const VARINT_PROTOBUF: Encoding = Encoding::UTF_8;
+    pub const VARINT_QUIC: Encoding = Encoding::UTF_16;
+    pub const VARINT_ZIGZAG: Encoding = Encoding::UCS_4;
 }
 
 #[repr(i32)]
@@ -59,7 +86,7 @@ pub enum FieldDisplay {
 }
 impl FieldDisplay {
     pub const BASE_FLOAT: FieldDisplay = FieldDisplay::BASE_NONE;
-    pub const STR_UNICODE: FieldDisplay = FieldDisplay::BASE_NONE;
+    pub const STR_ASCII: FieldDisplay = FieldDisplay::BASE_NONE;
 }
 impl From<&FieldDisplay> for i32 {
     fn from(z: &FieldDisplay) -> Self {
@@ -108,6 +135,14 @@ pub enum FieldDisplayFlags {
     SPECIAL_VALS = 0x8000,  /*< field will not display "Unknown" if value_string match is not found */
 }
 
+// With the bindgen feature the structs shared with wireshark are the generated ones from the sys module, the hand
+// written ones below are the fallback for builds without libclang.
+#[cfg(feature = "bindgen")]
+pub use super::sys::{
+    field_info, header_field_info, hf_register_info, proto_item, proto_plugin, proto_tree, protocol_t,
+};
+
+#[cfg(not(feature = "bindgen"))]
 #[repr(C)]
 #[allow(dead_code)]
 #[derive(Debug)]
@@ -116,30 +151,36 @@ pub enum hf_ref_type {
     INDIRECT,
     DIRECT,
 }
+#[cfg(not(feature = "bindgen"))]
 impl Default for hf_ref_type {
     fn default() -> Self {
         hf_ref_type::NONE
     }
 }
+#[cfg(not(feature = "bindgen"))]
 unsafe impl Send for hf_ref_type {}
 
+#[cfg(not(feature = "bindgen"))]
 /// Opaque proto_tree struct
 #[repr(C)]
 pub struct proto_tree {
     _private: [u8; 0],
 }
+#[cfg(not(feature = "bindgen"))]
 /// Opaque proto_item struct
 #[repr(C)]
 pub struct proto_item {
     _private: [u8; 0],
 }
 
+#[cfg(not(feature = "bindgen"))]
 /// Opaque protocol_t struct
 #[repr(C)]
 pub struct protocol_t {
     _private: [u8; 0],
 }
 
+#[cfg(not(feature = "bindgen"))]
 #[repr(C)]
 pub struct header_field_info {
     pub name: *const libc::c_char,
@@ -154,9 +195,10 @@ pub struct header_field_info {
     pub id: i32,
     pub parent: i32,
     pub ref_type: hf_ref_type,
-    pub same_name_pref_id: i32,
+    pub same_name_prev_id: i32,
     pub same_name_next: *mut header_field_info,
 }
+#[cfg(not(feature = "bindgen"))]
 impl Default for header_field_info {
     fn default() -> Self {
         header_field_info {
@@ -170,14 +212,16 @@ impl Default for header_field_info {
             id: -1,
             parent: 0,
             ref_type: hf_ref_type::NONE,
-            same_name_pref_id: -1,
+            same_name_prev_id: -1,
             same_name_next: 0 as *mut header_field_info,
         }
     }
 }
 
 // printing everything causes segfaults?? :/
+#[cfg(not(feature = "bindgen"))]
 use core::fmt::Debug;
+#[cfg(not(feature = "bindgen"))]
 impl Debug for header_field_info {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         use std::ffi::CStr;
@@ -205,7 +249,7 @@ impl Debug for header_field_info {
         //~ write!(f, "blurb: {:?}", self.blurb);
         write!(f, "id: {:?}, ", self.id)?;
         write!(f, "parent: {:?}", self.parent)?;
-        //~ write!(f, "same_name_pref_id: {:?}", self.same_name_pref_id);
+        //~ write!(f, "same_name_prev_id: {:?}", self.same_name_prev_id);
         //~ write!(f, "same_name_next: {:?}", self.same_name_next);
         write!(f, "}}")
     }
@@ -213,17 +257,19 @@ impl Debug for header_field_info {
 /*
 */
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(transparent)]
 /// HF index, this should NEVER be instantiated by the user, they are returned by proto_register_field_array.
 pub struct HFIndex(pub i32);
 
+#[cfg(not(feature = "bindgen"))]
 #[derive(Debug)]
 #[repr(C)]
 pub struct hf_register_info {
     pub p_id: *mut HFIndex,        // written to by register() function
     pub hfinfo: header_field_info, // < the field info to be registered
 }
+#[cfg(not(feature = "bindgen"))]
 impl Default for hf_register_info {
     fn default() -> Self {
         hf_register_info {
@@ -233,13 +279,16 @@ impl Default for hf_register_info {
     }
 }
 
+#[cfg(not(feature = "bindgen"))]
 const ITEM_LABEL_LENGTH: usize = 240;
+#[cfg(not(feature = "bindgen"))]
 #[derive(Debug)]
 #[repr(C)]
 pub struct item_label_t {
     representation: [libc::c_char; ITEM_LABEL_LENGTH],
 }
 
+#[cfg(not(feature = "bindgen"))]
 #[derive(Debug)]
 #[repr(C)]
 pub struct field_info {
@@ -261,15 +310,21 @@ pub struct field_info {
     /**< string for GUI tree */
     pub ds_tvb: *mut tvbuff_t,
     /**< data source tvbuff */
+    #[cfg(not(ws_4_0))]
     pub value: fvalue_t,
+    /**< the value, it's behind a pointer from 4.0 onwards */
+    #[cfg(ws_4_0)]
+    pub value: *mut fvalue_t,
 }
 
+#[cfg(not(feature = "bindgen"))]
 #[repr(C)]
 pub struct proto_plugin {
     pub register_protoinfo: Option<extern "C" fn()>, /* routine to call to register protocol information */
     pub register_handoff: Option<extern "C" fn()>,   /* routine to call to register protocol information */
 }
 
+#[cfg(not(feature = "bindgen"))]
 impl Default for proto_plugin {
     fn default() -> Self {
         proto_plugin {
@@ -280,10 +335,11 @@ impl Default for proto_plugin {
 }
 
 /// ETT index, this should NEVER be instantiated by the user, they are returned by proto_register_subtree_array.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(transparent)]
 pub struct ETTIndex(pub i32);
 
+#[cfg(feature = "wireshark")]
 #[link(name = "wireshark")]
 extern "C" {
 
@@ -348,6 +404,71 @@ extern "C" {
         retval: *mut u32,
     ) -> *mut proto_item;
 
+    pub fn proto_tree_add_item_ret_int64(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        encoding: Encoding,
+        retval: *mut i64,
+    ) -> *mut proto_item;
+
+    #[cfg(not(ws_4_2))]
+    pub fn proto_tree_add_item_ret_boolean(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        encoding: Encoding,
+        retval: *mut i32,
+    ) -> *mut proto_item;
+
+    #[cfg(ws_4_2)]
+    pub fn proto_tree_add_item_ret_boolean(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        encoding: Encoding,
+        retval: *mut bool,
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_item_ret_string_and_length(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        encoding: Encoding,
+        scope: *mut wmem_allocator_t,
+        retval: *mut *const u8,
+        lenretval: *mut i32,
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_item_ret_length(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        encoding: Encoding,
+        retval: *mut i32,
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_item_ret_varint(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        encoding: Encoding,
+        retval: *mut u64,
+        lenretval: *mut i32,
+    ) -> *mut proto_item;
+
     pub fn proto_tree_add_bits_item(
         tree: *mut proto_tree,
         hfindex: HFIndex,
@@ -357,10 +478,307 @@ extern "C" {
         encoding: Encoding,
     ) -> *mut proto_item;
 
+    // Adding values that don't come straight from the buffer, the _format_value variants replace the value text.
+
+    pub fn proto_tree_add_uint(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: u32,
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_uint_format_value(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: u32,
+        format: *const libc::c_char,
+        ...
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_int(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: i32,
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_int_format_value(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: i32,
+        format: *const libc::c_char,
+        ...
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_uint64(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: u64,
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_uint64_format_value(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: u64,
+        format: *const libc::c_char,
+        ...
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_int64(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: i64,
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_int64_format_value(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: i64,
+        format: *const libc::c_char,
+        ...
+    ) -> *mut proto_item;
+
+    #[cfg(not(ws_4_2))]
+    pub fn proto_tree_add_boolean(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: u32,
+    ) -> *mut proto_item;
+
+    #[cfg(ws_4_2)]
+    pub fn proto_tree_add_boolean(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: u64,
+    ) -> *mut proto_item;
+
+    #[cfg(not(ws_4_2))]
+    pub fn proto_tree_add_boolean_format_value(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: u32,
+        format: *const libc::c_char,
+        ...
+    ) -> *mut proto_item;
+
+    #[cfg(ws_4_2)]
+    pub fn proto_tree_add_boolean_format_value(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: u64,
+        format: *const libc::c_char,
+        ...
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_double(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: f64,
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_double_format_value(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: f64,
+        format: *const libc::c_char,
+        ...
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_string(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: *const libc::c_char,
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_string_format_value(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: *const libc::c_char,
+        format: *const libc::c_char,
+        ...
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_bytes(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        start_ptr: *const u8,
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_bytes_with_length(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        tvb_length: i32,
+        start_ptr: *const u8,
+        ptr_length: i32,
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_bytes_format_value(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        start_ptr: *const u8,
+        format: *const libc::c_char,
+        ...
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_ipv4(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: u32,
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_ipv4_format_value(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: u32,
+        format: *const libc::c_char,
+        ...
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_ipv6(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value_ptr: *const u8,
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_ipv6_format_value(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value_ptr: *const u8,
+        format: *const libc::c_char,
+        ...
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_ether(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: *const u8,
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_ether_format_value(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value: *const u8,
+        format: *const libc::c_char,
+        ...
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_guid(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value_ptr: *const e_guid_t,
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_guid_format_value(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value_ptr: *const e_guid_t,
+        format: *const libc::c_char,
+        ...
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_time(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value_ptr: *const nstime_t,
+    ) -> *mut proto_item;
+
+    pub fn proto_tree_add_time_format_value(
+        tree: *mut proto_tree,
+        hfindex: HFIndex,
+        tvb: *const tvbuff_t,
+        start: i32,
+        length: i32,
+        value_ptr: *const nstime_t,
+        format: *const libc::c_char,
+        ...
+    ) -> *mut proto_item;
+
     // Proto item functions below
-    pub fn proto_item_set_text(ti: *mut proto_item, text: *const libc::c_char);
-    pub fn proto_item_append_text(ti: *mut proto_item, text: *const libc::c_char);
-    pub fn proto_item_prepend_text(ti: *mut proto_item, text: *const libc::c_char);
+    pub fn proto_item_set_text(ti: *mut proto_item, format: *const libc::c_char, ...);
+    pub fn proto_item_append_text(ti: *mut proto_item, format: *const libc::c_char, ...);
+    pub fn proto_item_set_generated(ti: *mut proto_item);
+    pub fn proto_item_prepend_text(ti: *mut proto_item, format: *const libc::c_char, ...);
     pub fn proto_item_add_subtree(ti: *mut proto_item, ett_id: ETTIndex) -> *mut proto_tree;
 
     // Introspection
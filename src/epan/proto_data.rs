@@ -0,0 +1,28 @@
+// Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+// Follows proto_data.h. Data added with the file scope is kept with the frame, so it is still there when the packet is
+// dissected again.
+
+#[cfg(feature = "wireshark")]
+use super::packet_info::packet_info;
+#[cfg(feature = "wireshark")]
+use super::wmem::wmem_allocator_t;
+
+#[cfg(feature = "wireshark")]
+#[link(name = "wireshark")]
+extern "C" {
+    pub fn p_add_proto_data(
+        scope: *mut wmem_allocator_t,
+        pinfo: *mut packet_info,
+        proto: libc::c_int,
+        key: u32,
+        proto_data: *mut libc::c_void,
+    );
+    pub fn p_get_proto_data(
+        scope: *mut wmem_allocator_t,
+        pinfo: *mut packet_info,
+        proto: libc::c_int,
+        key: u32,
+    ) -> *mut libc::c_void;
+}
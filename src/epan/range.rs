@@ -1,6 +1,9 @@
 // Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
 // SPDX-License-Identifier: GPL-2.0-or-later
 
+#[cfg(feature = "wireshark")]
+use super::wmem::wmem_allocator_t;
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct range_admin_t {
@@ -22,3 +25,22 @@ impl Default for epan_range {
         }
     }
 }
+
+/// The range as wireshark allocates it, with as many entries as nranges says.
+#[repr(C)]
+pub struct range_t {
+    pub nranges: u32,
+    pub ranges: [range_admin_t; 0],
+}
+
+#[cfg(feature = "wireshark")]
+#[link(name = "wireshark")]
+extern "C" {
+    /// Parse a range like "1-10,20" into a newly allocated range, returns zero on success.
+    pub fn range_convert_str(
+        scope: *mut wmem_allocator_t,
+        range: *mut *mut range_t,
+        es: *const libc::c_char,
+        max_value: u32,
+    ) -> libc::c_int;
+}
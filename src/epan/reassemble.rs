@@ -0,0 +1,129 @@
+// Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+// Follows reassemble.h, for protocols that split their messages into fragments that are sent in separate packets.
+
+#![allow(non_camel_case_types)]
+
+#[cfg(feature = "wireshark")]
+use super::packet_info::packet_info;
+#[cfg(feature = "wireshark")]
+use super::proto::proto_tree;
+use super::proto::{ETTIndex, HFIndex};
+#[cfg(feature = "wireshark")]
+use super::tvbuff::tvbuff_t;
+
+/// The state of the fragments, filled in by wireshark when the table is registered. Must not move afterwards.
+#[repr(C)]
+pub struct reassembly_table {
+    pub fragment_table: *mut libc::c_void,
+    pub reassembled_table: *mut libc::c_void,
+    pub temporary_key_func: *mut libc::c_void,
+    pub persistent_key_func: *mut libc::c_void,
+    pub free_temporary_key_func: *mut libc::c_void,
+}
+
+impl Default for reassembly_table {
+    fn default() -> Self {
+        reassembly_table {
+            fragment_table: std::ptr::null_mut(),
+            reassembled_table: std::ptr::null_mut(),
+            temporary_key_func: std::ptr::null_mut(),
+            persistent_key_func: std::ptr::null_mut(),
+            free_temporary_key_func: std::ptr::null_mut(),
+        }
+    }
+}
+
+/// How the fragments are keyed besides their id, only used through the tables wireshark provides.
+#[repr(C)]
+pub struct reassembly_table_functions {
+    _private: [u8; 0],
+}
+
+/// The head of the fragments with the same id, holds the reassembled data once complete.
+#[repr(C)]
+pub struct fragment_head {
+    _private: [u8; 0],
+}
+
+/// The fields and subtrees that are used to show the fragments, in the order of the struct.
+#[repr(C)]
+pub struct fragment_items {
+    pub ett_fragment: *const ETTIndex,
+    pub ett_fragments: *const ETTIndex,
+
+    pub hf_fragments: *const HFIndex,
+    pub hf_fragment: *const HFIndex,
+    pub hf_fragment_overlap: *const HFIndex,
+    pub hf_fragment_overlap_conflict: *const HFIndex,
+    pub hf_fragment_multiple_tails: *const HFIndex,
+    pub hf_fragment_too_long_fragment: *const HFIndex,
+    pub hf_fragment_error: *const HFIndex,
+    pub hf_fragment_count: *const HFIndex,
+    pub hf_reassembled_in: *const HFIndex,
+    pub hf_reassembled_length: *const HFIndex,
+    pub hf_reassembled_data: *const HFIndex,
+
+    pub tag: *const libc::c_char,
+}
+
+/// The position of each field of a reassembly table, in the order of the `hf_` members of [`fragment_items`]. The fields
+/// are registered in this order.
+pub(crate) mod hf {
+    pub const FRAGMENTS: usize = 0;
+    pub const FRAGMENT: usize = 1;
+    pub const FRAGMENT_OVERLAP: usize = 2;
+    pub const FRAGMENT_OVERLAP_CONFLICT: usize = 3;
+    pub const FRAGMENT_MULTIPLE_TAILS: usize = 4;
+    pub const FRAGMENT_TOO_LONG_FRAGMENT: usize = 5;
+    pub const FRAGMENT_ERROR: usize = 6;
+    pub const FRAGMENT_COUNT: usize = 7;
+    pub const REASSEMBLED_IN: usize = 8;
+    pub const REASSEMBLED_LENGTH: usize = 9;
+    pub const REASSEMBLED_DATA: usize = 10;
+    /// The number of fields.
+    pub const COUNT: usize = 11;
+}
+
+/// The position of each subtree of a reassembly table, in the order of the `ett_` members of [`fragment_items`].
+pub(crate) mod ett {
+    pub const FRAGMENT: usize = 0;
+    pub const FRAGMENTS: usize = 1;
+    /// The number of subtrees.
+    pub const COUNT: usize = 2;
+}
+
+#[cfg(feature = "wireshark")]
+#[link(name = "wireshark")]
+extern "C" {
+    /// Fragments are matched by the source and destination address.
+    pub static addresses_reassembly_table_functions: reassembly_table_functions;
+    /// Fragments are matched by the source and destination address and port.
+    pub static addresses_ports_reassembly_table_functions: reassembly_table_functions;
+
+    pub fn reassembly_table_register(table: *mut reassembly_table, funcs: *const reassembly_table_functions);
+
+    pub fn fragment_add_seq_check(
+        table: *mut reassembly_table,
+        tvb: *mut tvbuff_t,
+        offset: libc::c_int,
+        pinfo: *const packet_info,
+        id: u32,
+        data: *const libc::c_void,
+        frag_number: u32,
+        frag_data_len: u32,
+        more_frags: bool,
+    ) -> *mut fragment_head;
+
+    pub fn process_reassembled_data(
+        tvb: *mut tvbuff_t,
+        offset: libc::c_int,
+        pinfo: *mut packet_info,
+        name: *const libc::c_char,
+        fd_head: *mut fragment_head,
+        fit: *const fragment_items,
+        update_col_infop: *mut bool,
+        tree: *mut proto_tree,
+    ) -> *mut tvbuff_t;
+}
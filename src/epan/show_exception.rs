@@ -0,0 +1,21 @@
+// Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+#![cfg(feature = "wireshark")]
+
+// Follows show_exception.h.
+
+use super::packet_info::packet_info;
+use super::proto::proto_tree;
+use super::tvbuff::tvbuff_t;
+
+#[link(name = "wireshark")]
+extern "C" {
+    pub fn show_exception(
+        tvb: *mut tvbuff_t,
+        pinfo: *mut packet_info,
+        tree: *mut proto_tree,
+        exception: libc::c_ulong,
+        exception_message: *const libc::c_char,
+    );
+}
@@ -0,0 +1,102 @@
+// Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+// Raw bindings generated by bindgen from the installed epan headers, only available with the bindgen feature. The
+// proto, ftypes, packet, tvbuff and value_string modules re-export the structs from here when the feature is enabled,
+// so the wrappers are built on these. Without it they fall back to hand written structs so that the crate doesn't need
+// libclang, the remaining hand written structs are checked against the generated ones in the tests below.
+
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+#![allow(dead_code)]
+#![allow(clippy::all)]
+
+include!(concat!(env!("OUT_DIR"), "/sys.rs"));
+
+#[cfg(test)]
+mod tests {
+    use super::super::{address, expert, packet_info, prefs};
+    use std::mem::{offset_of, size_of};
+
+    /// Assert that the hand written struct has the same size as the generated one and that the fields are at the same
+    /// offsets. Fields are given as `ours => theirs`.
+    macro_rules! assert_layout {
+        ($ours:ty, $theirs:ty, [$($field:ident => $sys_field:ident),* $(,)?]) => {
+            assert_eq!(size_of::<$ours>(), size_of::<$theirs>(), "size of {}", stringify!($ours));
+            assert_offsets!($ours, $theirs, [$($field => $sys_field),*]);
+        };
+    }
+
+    /// Like [`assert_layout`], but for structs where we only mirror the first part.
+    macro_rules! assert_offsets {
+        ($ours:ty, $theirs:ty, [$($field:ident => $sys_field:ident),* $(,)?]) => {
+            $(
+                assert_eq!(
+                    offset_of!($ours, $field),
+                    offset_of!($theirs, $sys_field),
+                    "offset of {}::{}",
+                    stringify!($ours),
+                    stringify!($field)
+                );
+            )*
+        };
+    }
+
+    #[test]
+    fn expert_layout() {
+        assert_layout!(expert::EIIndex, super::expert_field, [ei => ei, hf => hf]);
+        assert_layout!(
+            expert::expert_field_info,
+            super::expert_field_info,
+            [
+                name => name,
+                group => group,
+                severity => severity,
+                summary => summary,
+                id => id,
+                protocol => protocol,
+                orig_severity => orig_severity,
+                hf_info => hf_info,
+            ]
+        );
+        assert_layout!(expert::ei_register_info, super::ei_register_info, [ids => ids, eiinfo => eiinfo]);
+    }
+
+    #[test]
+    fn packet_info_layout() {
+        assert_offsets!(
+            packet_info::packet_info,
+            super::packet_info,
+            [
+                current_proto => current_proto,
+                cinfo => cinfo,
+                num => num,
+                abs_ts => abs_ts,
+                rel_ts => rel_ts,
+                fd => fd,
+                src => src,
+                dst => dst,
+                srcport => srcport,
+                destport => destport,
+                match_uint => match_uint,
+                can_desegment => can_desegment,
+                p2p_dir => p2p_dir,
+            ]
+        );
+        assert_layout!(
+            address::address,
+            super::address,
+            [type_ => type_, len => len, data => data, priv_ => priv_]
+        );
+    }
+
+    #[test]
+    fn enum_val_layout() {
+        assert_layout!(
+            prefs::enum_val_t,
+            super::enum_val_t,
+            [name => name, description => description, value => value]
+        );
+    }
+}
@@ -1,11 +1,26 @@
 // Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
 // SPDX-License-Identifier: GPL-2.0-or-later
 
+#[cfg(feature = "wireshark")]
+use super::proto::Encoding;
+#[cfg(feature = "wireshark")]
+use super::wmem::wmem_allocator_t;
+
+// The generated tvbuff_t with the bindgen feature, the hand written one below is the fallback for builds without
+// libclang.
+#[cfg(feature = "bindgen")]
+pub use super::sys::tvbuff_t;
+
+#[cfg(not(feature = "bindgen"))]
 #[repr(C)]
 pub struct tvbuff_t {
     _private: [u8; 0],
 }
 
+#[allow(non_camel_case_types)]
+pub type tvb_free_cb_t = Option<unsafe extern "C" fn(*mut libc::c_void)>;
+
+#[cfg(feature = "wireshark")]
 #[link(name = "wireshark")]
 extern "C" {
     // This function comes with the fatest warning ever...
@@ -13,8 +28,27 @@ extern "C" {
 
     // Get reported length of buffer:
     pub fn tvb_reported_length(tvb: *const tvbuff_t) -> u32;
+    pub fn tvb_captured_length(tvb: *const tvbuff_t) -> u32;
     pub fn tvb_reported_length_remaining(tvb: *const tvbuff_t, offset: i32) -> i32;
 
+    // Buffers that refer to a part of another buffer.
+    pub fn tvb_new_subset_length_caplen(
+        backing: *mut tvbuff_t,
+        backing_offset: i32,
+        backing_length: i32,
+        reported_length: i32,
+    ) -> *mut tvbuff_t;
+    pub fn tvb_new_subset_remaining(backing: *mut tvbuff_t, backing_offset: i32) -> *mut tvbuff_t;
+
+    /// A buffer with new data that is freed together with the parent, after calling the free callback on the data.
+    pub fn tvb_new_child_real_data(
+        parent: *mut tvbuff_t,
+        real_data: *const u8,
+        length: u32,
+        reported_length: i32,
+    ) -> *mut tvbuff_t;
+    pub fn tvb_set_free_cb(tvb: *mut tvbuff_t, func: tvb_free_cb_t);
+
     /** Returns target for convenience. Does not suffer from possible
      * expense of tvb_get_ptr(), since this routine is smart enough
      * to copy data in chunks if the request range actually exists in
@@ -23,4 +57,13 @@ extern "C" {
      * target memory. */
     pub fn tvb_memcpy(tvb: *const tvbuff_t, target: *mut libc::c_void, offset: i32, length: usize)
         -> *mut libc::c_void;
+
+    /// Returns a string converted to UTF-8, allocated with the scope, or with g_malloc if the scope is null.
+    pub fn tvb_get_string_enc(
+        scope: *mut wmem_allocator_t,
+        tvb: *const tvbuff_t,
+        offset: i32,
+        length: i32,
+        encoding: Encoding,
+    ) -> *mut u8;
 }
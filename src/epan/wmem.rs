@@ -0,0 +1,45 @@
+// Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+// Follows wmem/wmem_core.h and wmem/wmem_user_cb.h, memory passed to a null allocator is allocated with g_malloc.
+
+#[repr(C)]
+pub struct wmem_allocator_t {
+    _private: [u8; 0],
+}
+
+/// Why a user callback is called.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum wmem_cb_event_t {
+    /// The memory of the allocator is freed, the allocator remains.
+    WMEM_CB_FREE_EVENT,
+    /// The allocator itself is destroyed.
+    WMEM_CB_DESTROY_EVENT,
+}
+
+/// Called when the memory of the allocator is freed, returning false unregisters the callback.
+pub type wmem_user_cb_t = Option<
+    extern "C" fn(
+        allocator: *mut wmem_allocator_t,
+        event: wmem_cb_event_t,
+        user_data: *mut libc::c_void,
+    ) -> libc::c_int,
+>;
+
+#[cfg(feature = "wireshark")]
+#[link(name = "wireshark")]
+extern "C" {
+    pub fn wmem_free(allocator: *mut wmem_allocator_t, ptr: *mut libc::c_void);
+
+    pub fn wmem_register_callback(
+        allocator: *mut wmem_allocator_t,
+        callback: wmem_user_cb_t,
+        user_data: *mut libc::c_void,
+    ) -> libc::c_uint;
+
+    /// The scope that lives as long as the epan library, this one is from epan.h.
+    pub fn wmem_epan_scope() -> *mut wmem_allocator_t;
+    /// The scope that lives as long as the capture file, this one is from epan.h.
+    pub fn wmem_file_scope() -> *mut wmem_allocator_t;
+}
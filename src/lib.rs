@@ -11,9 +11,30 @@ pub mod dissector;
 /// for some of the types. File structure mirrors that of the wireshark headers.
 pub mod epan;
 
+/// Provides an in-memory implementation of the protocol tree and buffer, such that dissectors can be tested without
+/// wireshark. Start with [`mock::Harness`], it registers the dissector and records everything the dissector does.
+pub mod mock;
+
+/// Golden file testing on top of [`mock`], renders the dissection result as text and compares it against a snapshot
+/// file.
+pub mod snapshot;
+
 /// This module exposes a single plugin method and holds several C functions that are registered and subsequently call
-/// into the Dissector object the user provided.
+/// into the Dissector object the user provided. It also provides the [`wireshark_plugin!`] macro. Only available with
+/// the `wireshark` feature, as it needs to link against libwireshark.
+#[cfg(feature = "wireshark")]
 mod plugin;
 
+/// Helpers for common dissector tasks built on the [`epan`] wrappers, like matching requests with their responses.
+pub mod transaction;
+
+/// The version of wireshark this crate is built for, determined at build time with pkg-config. The `WIRESHARK_VERSION`
+/// environment variable overrides it.
+pub mod version;
+
 // Utility module to make persistent C strings.
 mod util;
+
+// Used by the wireshark_plugin macro to create the version symbols, not part of the public interface.
+#[doc(hidden)]
+pub use util::c_char_array;
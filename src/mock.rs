@@ -0,0 +1,2237 @@
+// Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+/*
+The wrappers in epan normally just hold pointers into wireshark. For testing we don't want to need wireshark at all, so
+they can also point into a Recording from this module, which keeps track of everything that is added to the tree. The
+Harness performs the same registration steps the plugin would do and then runs the dissector on a buffer.
+*/
+
+use crate::dissector::{Dissector, DissectorTableKind, ReassemblyKey, Registration};
+use crate::epan;
+use crate::epan::exceptions;
+use crate::epan::expert::{EIIndex, Group, Severity};
+use crate::epan::proto::{ETTIndex, Encoding, HFIndex};
+use crate::epan::{Column, Exception, FieldDisplay, FieldType, HeaderFieldStrings, PreferenceValue};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+/// A value as decoded from the buffer by the mock backend.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// The field doesn't carry a value, for example [`FieldType::NONE`] or [`FieldType::PROTOCOL`].
+    None,
+    /// Unsigned integers, characters and frame numbers.
+    UInt(u64),
+    /// Signed integers.
+    Int(i64),
+    /// Booleans.
+    Bool(bool),
+    /// Floating point values.
+    Double(f64),
+    /// Strings, decoded as UTF-8.
+    String(String),
+    /// Absolute or relative times, like [`epan::nstime::nstime_t`].
+    Time { secs: i64, nsecs: i32 },
+    /// Everything else is represented as its raw bytes.
+    Bytes(Vec<u8>),
+}
+
+impl Value {
+    /// Retrieve the value as an unsigned integer, if it is an integer or boolean.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::UInt(v) => Some(*v),
+            Value::Int(v) => Some(*v as u64),
+            Value::Bool(v) => Some(*v as u64),
+            _ => None,
+        }
+    }
+
+    /// Retrieve the value as a signed integer, if it is an integer or boolean.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::UInt(v) => Some(*v as i64),
+            Value::Int(v) => Some(*v),
+            Value::Bool(v) => Some(*v as i64),
+            _ => None,
+        }
+    }
+}
+
+/// A registered field, holds the information retrieved from [`Dissector::get_fields()`].
+#[derive(Debug, Clone)]
+pub struct Field {
+    /// The index that was handed to the dissector for this field.
+    pub index: HFIndex,
+    /// The human readable name.
+    pub name: String,
+    /// The abbreviation used for filters.
+    pub abbrev: String,
+    /// The type of the field.
+    pub field_type: FieldType,
+    /// How the field is to be displayed.
+    pub display: FieldDisplay,
+    /// The strings to look up values in.
+    pub strings: HeaderFieldStrings,
+    /// The bitmask of interesting bits.
+    pub bitmask: u64,
+}
+
+/// A registered expert info, holds the information retrieved from [`Dissector::get_expert_fields()`]. Expert info that
+/// was reported during the dissection is represented the same way.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expert {
+    /// The index that was handed to the dissector for this expert info.
+    pub index: EIIndex,
+    /// The abbreviation used for filters.
+    pub abbrev: String,
+    /// The category of the problem.
+    pub group: Group,
+    /// How serious the problem is.
+    pub severity: Severity,
+    /// The summary it was registered with, or the text it was added with for the `_format` functions.
+    pub text: String,
+}
+
+/// An item that was added to the tree by the dissector.
+#[derive(Debug, Clone)]
+pub struct Item {
+    /// The field this item was added for.
+    pub field: Field,
+    /// The byte offset in the buffer.
+    pub offset: usize,
+    /// The number of bytes in the buffer this item covers.
+    pub length: usize,
+    /// For items added with [`epan::ProtoTree::add_bits_item()`] the bit offset and number of bits.
+    pub bits: Option<(usize, usize)>,
+    /// The encoding that was used to add this item.
+    pub encoding: Encoding,
+    /// The value as decoded from the buffer.
+    pub value: Value,
+    /// The text of this item, if it was changed with `set_text`, `append_text` or `prepend_text`, or if it was added
+    /// with one of the `_format_value` functions.
+    pub text: Option<String>,
+    /// The subtree index, if a subtree was added to this item.
+    pub subtree: Option<ETTIndex>,
+    /// The items added to the subtree of this item.
+    pub children: Vec<Item>,
+    /// The expert info attached to this item.
+    pub experts: Vec<Expert>,
+    /// Whether the item was marked as generated with [`epan::ProtoItem::set_generated()`].
+    pub generated: bool,
+}
+
+impl Item {
+    /// The text as it would be shown in the tree, this is the modified text if it was changed and the default label
+    /// otherwise. Generated items are shown between square brackets.
+    pub fn label(&self) -> String {
+        let label = match &self.text {
+            Some(t) => t.clone(),
+            None => self.default_label(),
+        };
+        match self.generated {
+            true => format!("[{}]", label),
+            false => label,
+        }
+    }
+
+    /// The label wireshark would create for this item; 'Field name: value'. For bitfields this is prefixed with the
+    /// bit pattern, like '.... 0101 = Field name: 5'.
+    pub fn default_label(&self) -> String {
+        let label = if self.value == Value::None {
+            self.field.name.clone()
+        } else {
+            format!("{}: {}", self.field.name, format_value(&self.field, &self.value))
+        };
+        match self.bit_pattern() {
+            Some(pattern) => format!("{} = {}", pattern, label),
+            None => label,
+        }
+    }
+
+    /// The bit pattern for bitfields, dots for bits that are not part of this item.
+    fn bit_pattern(&self) -> Option<String> {
+        let value = self.value.as_u64()?;
+        let (width, is_set): (usize, Box<dyn Fn(usize) -> Option<bool>>) = if let Some((bit_offset, bits)) = self.bits {
+            // Bits are counted from the most significant bit of the first byte.
+            let first = bit_offset % 8;
+            let last = first + bits;
+            let f = move |i: usize| {
+                if i >= first && i < last {
+                    Some((value >> (last - 1 - i)) & 1 == 1)
+                } else {
+                    None
+                }
+            };
+            (self.length * 8, Box::new(f))
+        } else if self.field.bitmask != 0 {
+            let width = match hex_digits(self.field.field_type) {
+                0 => 64,
+                digits => digits * 4,
+            };
+            let mask = self.field.bitmask;
+            let shifted = value << mask.trailing_zeros();
+            let f = move |i: usize| {
+                let bit = width - 1 - i;
+                if (mask >> bit) & 1 == 1 {
+                    Some((shifted >> bit) & 1 == 1)
+                } else {
+                    None
+                }
+            };
+            (width, Box::new(f))
+        } else {
+            return None;
+        };
+
+        let mut pattern = String::new();
+        for i in 0..width {
+            if i != 0 && i % 4 == 0 {
+                pattern.push(' ');
+            }
+            pattern.push(match is_set(i) {
+                Some(true) => '1',
+                Some(false) => '0',
+                None => '.',
+            });
+        }
+        Some(pattern)
+    }
+
+    /// Find the first item below this one with the provided abbreviation, depth first.
+    pub fn find(&self, abbrev: &str) -> Option<&Item> {
+        find_in(&self.children, abbrev)
+    }
+}
+
+/// Everything that happened to the tree during the dissection, in order. Items are referred to by their abbreviation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// An item was added with [`epan::ProtoTree::add_item()`] or one of its flavours, or with a value that doesn't
+    /// come from the buffer, like [`epan::ProtoTree::add_uint()`].
+    AddItem {
+        abbrev: String,
+        offset: usize,
+        length: usize,
+        value: Value,
+    },
+    /// An item was added with [`epan::ProtoTree::add_bits_item()`].
+    AddBitsItem {
+        abbrev: String,
+        bit_offset: usize,
+        bits: usize,
+        value: Value,
+    },
+    /// A subtree was added to an item.
+    AddSubtree { abbrev: String, ett: ETTIndex },
+    /// The text of an item was replaced.
+    SetText { abbrev: String, text: String },
+    /// Text was appended to an item.
+    AppendText { abbrev: String, text: String },
+    /// Text was prepended to an item.
+    PrependText { abbrev: String, text: String },
+    /// Expert info was added to the tree, or attached to the item with the abbreviation.
+    AddExpert {
+        abbrev: String,
+        expert: String,
+        text: String,
+    },
+    /// Another dissector was called on the data at the offset, it consumed length bytes. Rejected calls are not
+    /// recorded.
+    CallDissector {
+        dissector: String,
+        offset: usize,
+        length: usize,
+    },
+    /// An exception was reported in the tree and the dissection continued, like [`epan::tcp_dissect_pdus()`] does for
+    /// each PDU.
+    ReportException { exception: Exception },
+}
+
+/// The result of running a dissector with the mock backend.
+#[derive(Debug, Clone)]
+pub struct Dissection {
+    /// The number of bytes the dissector reported to have used.
+    pub consumed: usize,
+    /// The items at the root of the tree.
+    pub items: Vec<Item>,
+    /// All operations on the tree, in order.
+    pub events: Vec<Event>,
+    /// The exception that stopped the dissection, wireshark would report this as malformed packet.
+    pub exception: Option<Exception>,
+    /// The exceptions that were reported during the dissection, after which it continued, in order.
+    pub reported_exceptions: Vec<Exception>,
+    /// The text of the columns that were written to.
+    pub columns: BTreeMap<Column, String>,
+    /// All expert info that was reported, in order, like the Expert Information dialog shows.
+    pub experts: Vec<Expert>,
+    /// Set if the dissector asked for more data with [`epan::tcp_dissect_pdus()`]: the offset at which the unfinished
+    /// data starts and the minimum number of bytes that are missing, see [`Harness::dissect_segments()`].
+    pub desegment: Option<(usize, usize)>,
+    /// The buffers that were added as data source, by name, in the order they were added. These are the tabs of the
+    /// bytes pane besides the frame itself.
+    pub data_sources: Vec<(String, Vec<u8>)>,
+}
+
+impl Dissection {
+    /// Find the first item with the provided abbreviation, depth first.
+    pub fn find(&self, abbrev: &str) -> Option<&Item> {
+        find_in(&self.items, abbrev)
+    }
+
+    /// Find all items with the provided abbreviation, depth first.
+    pub fn find_all(&self, abbrev: &str) -> Vec<&Item> {
+        fn recurse<'a>(items: &'a [Item], abbrev: &str, res: &mut Vec<&'a Item>) {
+            for item in items {
+                if item.field.abbrev == abbrev {
+                    res.push(item);
+                }
+                recurse(&item.children, abbrev, res);
+            }
+        }
+        let mut res = Vec::new();
+        recurse(&self.items, abbrev, &mut res);
+        res
+    }
+}
+
+fn find_in<'a>(items: &'a [Item], abbrev: &str) -> Option<&'a Item> {
+    for item in items {
+        if item.field.abbrev == abbrev {
+            return Some(item);
+        }
+        if let Some(found) = find_in(&item.children, abbrev) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+/// The metadata of the packet the [`Harness`] dissects, this is what [`epan::PacketInfo`] returns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packet {
+    /// The frame number.
+    pub number: u32,
+    /// Whether the packet was dissected before.
+    pub visited: bool,
+    /// The absolute timestamp.
+    pub abs_ts: epan::nstime::nstime_t,
+    /// The timestamp relative to the first packet.
+    pub rel_ts: epan::nstime::nstime_t,
+    /// The source address.
+    pub src: epan::Address,
+    /// The destination address.
+    pub dst: epan::Address,
+    /// The source port.
+    pub src_port: u32,
+    /// The destination port.
+    pub dst_port: u32,
+    /// The peer to peer direction.
+    pub p2p_dir: epan::P2PDirection,
+    /// The value in the dissector table that matched.
+    pub match_uint: u32,
+    /// The string in the dissector table that matched, if the dissector was registered for a string.
+    pub match_string: Option<String>,
+}
+
+impl Default for Packet {
+    /// The first packet of a capture, without addresses.
+    fn default() -> Self {
+        Packet {
+            number: 1,
+            visited: false,
+            abs_ts: Default::default(),
+            rel_ts: Default::default(),
+            src: epan::Address::None,
+            dst: epan::Address::None,
+            src_port: 0,
+            dst_port: 0,
+            p2p_dir: epan::P2PDirection::Unknown,
+            match_uint: 0,
+            match_string: None,
+        }
+    }
+}
+
+/// Stand-in for another dissector, gets the data and returns the number of bytes it consumed, 0 to reject it.
+type StandIn = Rc<dyn Fn(&[u8]) -> usize>;
+
+/// The other dissectors, tables and heuristic lists the dissector can call into, see [`Harness::add_dissector()`].
+struct Registry {
+    /// The stand-ins by name, with the protocol name their item is shown with.
+    dissectors: Vec<(String, String, StandIn)>,
+    tables: Vec<String>,
+    /// The string tables that compare their values case insensitive.
+    case_insensitive: Vec<String>,
+    uint_entries: Vec<(String, u32, String)>,
+    string_entries: Vec<(String, String, String)>,
+    /// The dissectors that can be picked with decode as, by table.
+    decode_as: Vec<(String, String)>,
+    heuristics: Vec<(String, String)>,
+}
+
+impl Default for Registry {
+    /// Only the data dissector, which consumes everything, like it does in wireshark.
+    fn default() -> Self {
+        let data: StandIn = Rc::new(|data: &[u8]| data.len());
+        Registry {
+            dissectors: vec![("data".to_owned(), "Data".to_owned(), data)],
+            tables: Vec::new(),
+            case_insensitive: Vec::new(),
+            uint_entries: Vec::new(),
+            string_entries: Vec::new(),
+            decode_as: Vec::new(),
+            heuristics: Vec::new(),
+        }
+    }
+}
+
+/// Performs the registration of a dissector like wireshark would, after which it can run the dissector on buffers.
+pub struct Harness {
+    fields: Vec<Field>,
+    experts: Vec<Expert>,
+    preferences: PreferencesRef,
+    file: Rc<RefCell<FileData>>,
+    registry: Rc<RefCell<Registry>>,
+    tree_count: usize,
+    packet: Packet,
+}
+
+impl Harness {
+    /// Register the fields and subtrees of this dissector. Fields get indices in the order they are returned by
+    /// [`Dissector::get_fields()`]. The tables from [`Dissector::get_registration()`] get the dissector as entry, see
+    /// [`Harness::table_string()`]. Like opening a capture file, this calls [`Dissector::init()`] at the end.
+    pub fn new<D: Dissector + ?Sized>(dissector: &mut D) -> Harness {
+        let mut fields = Vec::new();
+        let mut hf_indices = Vec::new();
+        for (i, hfi) in dissector.get_fields().drain(..).enumerate() {
+            let index = HFIndex(i as i32);
+            fields.push(Field {
+                index,
+                name: hfi.name(),
+                abbrev: hfi.abbrev(),
+                field_type: hfi.feature_type(),
+                display: hfi.display_type(),
+                strings: hfi.strings(),
+                bitmask: hfi.bitmask(),
+            });
+            hf_indices.push((hfi, index));
+        }
+        dissector.set_field_indices(hf_indices);
+
+        // Each expert info also gets a header field, which is what the item added for it shows.
+        let mut experts = Vec::new();
+        let mut ei_indices = Vec::new();
+        for (i, expert) in dissector.get_expert_fields().drain(..).enumerate() {
+            let index = EIIndex {
+                ei: i as i32,
+                hf: fields.len() as i32,
+            };
+            fields.push(Field {
+                index: HFIndex(index.hf),
+                name: expert.summary.as_str().to_owned(),
+                abbrev: expert.abbrev.as_str().to_owned(),
+                field_type: FieldType::NONE,
+                display: FieldDisplay::BASE_NONE,
+                strings: HeaderFieldStrings::None,
+                bitmask: 0,
+            });
+            experts.push(Expert {
+                index,
+                abbrev: expert.abbrev.as_str().to_owned(),
+                group: expert.group,
+                severity: expert.severity,
+                text: expert.summary.as_str().to_owned(),
+            });
+            ei_indices.push((expert, index));
+        }
+        if !ei_indices.is_empty() {
+            dissector.set_expert_indices(ei_indices);
+        }
+
+        // The subtrees of the reassembly tables come after those of the dissector.
+        let mut tree_count = dissector.get_tree_count();
+        let mut tables = Vec::new();
+        for (i, reassembly) in dissector.get_reassembly_tables().drain(..).enumerate() {
+            let mut hf = Vec::new();
+            for field in reassembly.fields().iter().map(|f| f.as_boxed()) {
+                hf.push(HFIndex(fields.len() as i32));
+                fields.push(Field {
+                    index: HFIndex(fields.len() as i32),
+                    name: field.name(),
+                    abbrev: field.abbrev(),
+                    field_type: field.feature_type(),
+                    display: field.display_type(),
+                    strings: field.strings(),
+                    bitmask: field.bitmask(),
+                });
+            }
+            let table = ReassemblyRef {
+                index: i,
+                key: reassembly.key,
+                name: reassembly.name.as_str().to_owned(),
+                hf,
+                ett: [ETTIndex(tree_count as i32), ETTIndex(tree_count as i32 + 1)],
+            };
+            tree_count += 2;
+            tables.push((reassembly, epan::ReassemblyTable::from_mock(table)));
+        }
+        if !tables.is_empty() {
+            dissector.set_reassembly_tables(tables);
+        }
+
+        // The tables the dissector owns, other dissectors are added to them with Harness::add_table_uint and friends.
+        let mut registry = Registry::default();
+        let mut tables = Vec::new();
+        for table in dissector.get_dissector_tables().drain(..) {
+            registry.add_table(table.name.as_str());
+            if let DissectorTableKind::String { case_sensitive: false } = table.kind {
+                registry.case_insensitive.push(table.name.as_str().to_owned());
+            }
+            let handle = epan::DissectorTable::from_mock(table.name.as_str());
+            tables.push((table, handle));
+        }
+        if !tables.is_empty() {
+            dissector.set_dissector_tables(tables);
+        }
+
+        // The dissector itself goes into the tables it registers for, under its filter name.
+        let (_, _, filter_name) = dissector.get_protocol_name();
+        registry.register(dissector.get_registration(), filter_name);
+
+        let preferences = PreferencesRef {
+            values: Rc::new(RefCell::new(
+                dissector
+                    .get_preferences()
+                    .iter()
+                    .map(|p| (p.name.as_str().to_owned(), PreferenceValue::from(&p.kind)))
+                    .collect(),
+            )),
+        };
+        dissector.set_preferences(epan::Preferences::from_mock(preferences.clone()));
+
+        let own_tree_count = dissector.get_tree_count();
+        if own_tree_count != 0 {
+            dissector.set_tree_indices((0..own_tree_count).map(|i| ETTIndex(i as i32)).collect());
+        }
+
+        dissector.init();
+
+        Harness {
+            fields,
+            experts,
+            preferences,
+            file: Default::default(),
+            registry: Rc::new(RefCell::new(registry)),
+            tree_count,
+            packet: Default::default(),
+        }
+    }
+
+    /// Act like a new capture file is opened, this calls [`Dissector::cleanup()`], drops the conversations and the
+    /// data attached to packets and then calls [`Dissector::init()`].
+    pub fn new_file<D: Dissector + ?Sized>(&self, dissector: &D) {
+        dissector.cleanup();
+        *self.file.borrow_mut() = Default::default();
+        dissector.init();
+    }
+
+    /// Set the metadata of the packets that are dissected after this.
+    pub fn set_packet(&mut self, packet: Packet) {
+        self.packet = packet;
+    }
+
+    /// Change the value of a preference, like the user would, after which [`Dissector::preferences_changed()`] is
+    /// called. Panics if there is no such preference or if the value is of another type.
+    pub fn set_preference<D: Dissector + ?Sized>(&self, dissector: &D, name: &str, value: PreferenceValue) {
+        self.preferences.set(name, value);
+        dissector.preferences_changed(&epan::Preferences::from_mock(self.preferences.clone()));
+    }
+
+    /// Add a stand-in for the dissector with this name, for [`epan::DissectorHandle::find()`]. The stand-in gets the
+    /// data it's called on and returns the number of bytes it consumed, 0 to reject the data. A call it accepts is
+    /// shown as a single item, with the name, and recorded as [`Event::CallDissector`]. The data dissector is always
+    /// there.
+    pub fn add_dissector(&mut self, name: &str, dissector: impl Fn(&[u8]) -> usize + 'static) {
+        let mut registry = self.registry.borrow_mut();
+        registry.dissectors.retain(|(n, _, _)| n != name);
+        registry
+            .dissectors
+            .push((name.to_owned(), name.to_owned(), Rc::new(dissector)));
+    }
+
+    /// Register the dissector with this name in the integer table for the value, the table is created if needed.
+    pub fn add_table_uint(&mut self, table: &str, value: u32, dissector: &str) {
+        let mut registry = self.registry.borrow_mut();
+        registry.add_table(table);
+        registry
+            .uint_entries
+            .push((table.to_owned(), value, dissector.to_owned()));
+    }
+
+    /// Register the dissector with this name in the string table for the pattern, the table is created if needed.
+    pub fn add_table_string(&mut self, table: &str, pattern: &str, dissector: &str) {
+        let mut registry = self.registry.borrow_mut();
+        registry.add_table(table);
+        registry
+            .string_entries
+            .push((table.to_owned(), pattern.to_owned(), dissector.to_owned()));
+    }
+
+    /// The dissector registered in the integer table for the value, the dissector under test is registered with its
+    /// filter name.
+    pub fn table_uint(&self, table: &str, value: u32) -> Option<String> {
+        self.registry.borrow().uint_entry(table, value)
+    }
+
+    /// The dissector registered in the string table for the pattern, the dissector under test is registered with its
+    /// filter name.
+    pub fn table_string(&self, table: &str, pattern: &str) -> Option<String> {
+        self.registry.borrow().string_entry(table, pattern)
+    }
+
+    /// The dissectors that can be picked with decode as in the table.
+    pub fn decode_as(&self, table: &str) -> Vec<String> {
+        let registry = self.registry.borrow();
+        registry
+            .decode_as
+            .iter()
+            .filter(|(t, _)| t == table)
+            .map(|(_, d)| d.clone())
+            .collect()
+    }
+
+    /// Add the dissector with this name to the heuristic list, they are tried in the order they were added.
+    pub fn add_heuristic(&mut self, list: &str, dissector: &str) {
+        let mut registry = self.registry.borrow_mut();
+        registry.heuristics.push((list.to_owned(), dissector.to_owned()));
+    }
+
+    /// The fields that were registered, followed by the fields for the expert info.
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// The expert info that was registered.
+    pub fn experts(&self) -> &[Expert] {
+        &self.experts
+    }
+
+    /// Run [`Dissector::dissect()`] on the provided data.
+    pub fn dissect<D: Dissector + ?Sized>(&self, dissector: &D, data: &[u8]) -> Dissection {
+        let (consumed, dissection) = self.run(data, false, |proto, tvb, pinfo| dissector.dissect(proto, tvb, pinfo));
+        Dissection { consumed, ..dissection }
+    }
+
+    /// Run [`Dissector::dissect()`] on the payloads of consecutive TCP segments, like TCP hands them to the dissector.
+    /// If the dissector asks for more data, the unfinished bytes are handed to it again together with the next
+    /// segment. Returns the dissection for each segment, all of them are dissected as the packet set with
+    /// [`Harness::set_packet()`].
+    pub fn dissect_segments<D: Dissector + ?Sized>(&self, dissector: &D, segments: &[&[u8]]) -> Vec<Dissection> {
+        let mut data: Vec<u8> = Vec::new();
+        let mut dissections = Vec::new();
+        for segment in segments {
+            data.extend_from_slice(segment);
+            let (consumed, dissection) =
+                self.run(&data, true, |proto, tvb, pinfo| dissector.dissect(proto, tvb, pinfo));
+            data = match dissection.desegment {
+                Some((offset, _)) => data.split_off(offset.min(data.len())),
+                None => Vec::new(),
+            };
+            dissections.push(Dissection { consumed, ..dissection });
+        }
+        dissections
+    }
+
+    /// Run [`Dissector::heuristic_dissect()`] on the provided data, returns whether the dissector accepted the data.
+    pub fn heuristic_dissect<D: Dissector + ?Sized>(&self, dissector: &D, data: &[u8]) -> (bool, Dissection) {
+        self.run(data, false, |proto, tvb, pinfo| {
+            dissector.heuristic_dissect(proto, tvb, pinfo)
+        })
+    }
+
+    fn run<R>(
+        &self,
+        data: &[u8],
+        can_desegment: bool,
+        f: impl FnOnce(&mut epan::ProtoTree, &mut epan::TVB, &mut epan::PacketInfo) -> R,
+    ) -> (R, Dissection) {
+        let recording = RefCell::new(Recording {
+            fields: self.fields.clone(),
+            experts: self.experts.clone(),
+            tree_count: self.tree_count,
+            nodes: Vec::new(),
+            roots: Vec::new(),
+            events: Vec::new(),
+            columns: BTreeMap::new(),
+            reported: Vec::new(),
+            reported_exceptions: Vec::new(),
+            file: self.file.clone(),
+            registry: self.registry.clone(),
+            packet: self.packet.clone(),
+            can_desegment,
+            desegment: None,
+            buffers: Vec::new(),
+            data_sources: Vec::new(),
+        });
+        let data = data.to_vec();
+
+        let mut proto = epan::ProtoTree::from_mock(NodeRef {
+            recording: &recording,
+            node: None,
+        });
+        let mut tvb = epan::TVB::from_mock(BufferRef {
+            recording: &recording,
+            data: data.as_ptr(),
+            len: data.len(),
+            origin: 0,
+        });
+        let mut pinfo = epan::PacketInfo::from_mock(InfoRef { recording: &recording });
+        exceptions::take_pending();
+        let result = f(&mut proto, &mut tvb, &mut pinfo);
+        let exception = exceptions::take_pending();
+
+        let recording = recording.into_inner();
+        let items = recording.roots.iter().map(|i| recording.build(*i)).collect();
+        (
+            result,
+            Dissection {
+                consumed: 0,
+                items,
+                events: recording.events,
+                exception,
+                reported_exceptions: recording.reported_exceptions,
+                columns: recording.columns.into_iter().map(|(k, v)| (k, v.text)).collect(),
+                experts: recording.reported,
+                desegment: recording.desegment,
+                data_sources: recording.data_sources,
+            },
+        )
+    }
+}
+
+/// An item in the recording, the children are indices into the recording's nodes.
+struct Node {
+    item: Item,
+    children: Vec<usize>,
+}
+
+/// The text in a column and the position of the fence, text before the fence can't be changed anymore.
+struct ColumnText {
+    text: String,
+    fence: usize,
+}
+
+/// A conversation of the mock backend, packets between the same two endpoints belong to the same conversation.
+struct Conversation {
+    endpoints: [(epan::Address, u32); 2],
+    data: Rc<epan::ProtoData>,
+}
+
+/// Everything that lives as long as the capture file, this is kept by the harness until the next file.
+#[derive(Default)]
+struct FileData {
+    conversations: Vec<Conversation>,
+    /// The data attached to packets, by frame number and key.
+    packet_data: BTreeMap<(u32, u32), Rc<epan::ProtoData>>,
+    /// Fragments of messages that are not complete yet.
+    fragments: Vec<Fragments>,
+    /// The messages that were reassembled.
+    reassembled: Vec<Rc<Reassembled>>,
+}
+
+/// What fragments are matched by, the reassembly table, the addresses, the ports if the table uses them and the id.
+type FragmentKey = (usize, epan::Address, epan::Address, Option<(u32, u32)>, u32);
+
+/// The fragments of a message so far, by sequence number, with the frame they were in.
+struct Fragments {
+    key: FragmentKey,
+    fragments: BTreeMap<u32, (u32, Vec<u8>)>,
+    /// The frames with a fragment that was already there, its data isn't used.
+    duplicates: Vec<u32>,
+    last: Option<u32>,
+}
+
+/// A message that was reassembled from its fragments.
+pub(crate) struct Reassembled {
+    key: FragmentKey,
+    /// The data of the message.
+    pub(crate) data: Vec<u8>,
+    /// The frame and length of each fragment, in order.
+    pub(crate) fragments: Vec<(u32, usize)>,
+    /// The frames with a fragment that arrived again.
+    duplicates: Vec<u32>,
+    /// The frame the message was completed in.
+    pub(crate) reassembled_in: u32,
+}
+
+/// Holds the state of the tree while the dissector is running.
+struct Recording {
+    fields: Vec<Field>,
+    experts: Vec<Expert>,
+    tree_count: usize,
+    nodes: Vec<Node>,
+    roots: Vec<usize>,
+    events: Vec<Event>,
+    columns: BTreeMap<Column, ColumnText>,
+    reported: Vec<Expert>,
+    reported_exceptions: Vec<Exception>,
+    file: Rc<RefCell<FileData>>,
+    registry: Rc<RefCell<Registry>>,
+    packet: Packet,
+    /// Whether the dissector is called like TCP does from [`Harness::dissect_segments()`].
+    can_desegment: bool,
+    desegment: Option<(usize, usize)>,
+    /// The data of buffers created during the dissection, like reassembled messages.
+    buffers: Vec<Vec<u8>>,
+    data_sources: Vec<(String, Vec<u8>)>,
+}
+
+impl Registry {
+    fn add_table(&mut self, table: &str) {
+        if !self.tables.iter().any(|t| t == table) {
+            self.tables.push(table.to_owned());
+        }
+    }
+
+    /// Add the registrations of the dissector under test with its filter name, like the handoff does in wireshark.
+    /// Only the tables are honoured, being a postdissector or a heuristic dissector has no effect in the mock.
+    fn register(&mut self, registrations: Vec<Registration>, dissector: &str) {
+        for registration in registrations {
+            match registration {
+                Registration::UInt { abbrev, pattern } => {
+                    self.add_table(abbrev);
+                    self.uint_entries
+                        .push((abbrev.to_owned(), pattern, dissector.to_owned()));
+                }
+                Registration::String { table, pattern } => {
+                    self.add_table(table);
+                    self.string_entries
+                        .push((table.to_owned(), pattern.to_owned(), dissector.to_owned()));
+                }
+                Registration::DecodeAs { abbrev } | Registration::StringDecodeAs { abbrev } => {
+                    self.add_table(abbrev);
+                    self.decode_as.push((abbrev.to_owned(), dissector.to_owned()));
+                }
+                Registration::Post | Registration::UIntRange { .. } | Registration::Heuristic { .. } => {}
+            }
+        }
+    }
+
+    /// The dissector registered in the integer table for the value.
+    fn uint_entry(&self, table: &str, value: u32) -> Option<String> {
+        let found = self
+            .uint_entries
+            .iter()
+            .rev()
+            .find(|(t, v, _)| t == table && *v == value);
+        found.map(|(_, _, dissector)| dissector.clone())
+    }
+
+    /// The dissector registered in the string table for the value.
+    fn string_entry(&self, table: &str, value: &str) -> Option<String> {
+        let case_insensitive = self.case_insensitive.iter().any(|t| t == table);
+        let found = self
+            .string_entries
+            .iter()
+            .rev()
+            .find(|(t, p, _)| t == table && (p == value || (case_insensitive && p.eq_ignore_ascii_case(value))));
+        found.map(|(_, _, dissector)| dissector.clone())
+    }
+}
+
+impl Recording {
+    fn field(&self, hfindex: HFIndex) -> Field {
+        match self.fields.get(hfindex.0 as usize) {
+            Some(f) if hfindex.0 >= 0 => f.clone(),
+            _ => panic!("Field index {:?} is not registered.", hfindex),
+        }
+    }
+
+    fn expert(&self, eiindex: EIIndex) -> Expert {
+        match self.experts.get(eiindex.ei as usize) {
+            Some(e) if eiindex.ei >= 0 => e.clone(),
+            _ => panic!("Expert info index {:?} is not registered.", eiindex),
+        }
+    }
+
+    /// Attach the expert info to the item and report it for the packet.
+    fn report(&mut self, index: usize, expert: Expert) {
+        let item = &mut self.nodes[index].item;
+        item.experts.push(expert.clone());
+        self.events.push(Event::AddExpert {
+            abbrev: item.field.abbrev.clone(),
+            expert: expert.abbrev.clone(),
+            text: expert.text.clone(),
+        });
+        self.reported.push(expert);
+    }
+
+    fn add(&mut self, parent: Option<usize>, item: Item) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            item,
+            children: Vec::new(),
+        });
+        match parent {
+            Some(p) => self.nodes[p].children.push(index),
+            None => self.roots.push(index),
+        }
+        index
+    }
+
+    fn column(&mut self, column: Column) -> &mut ColumnText {
+        self.columns.entry(column).or_insert_with(|| ColumnText {
+            text: String::new(),
+            fence: 0,
+        })
+    }
+
+    fn build(&self, index: usize) -> Item {
+        let node = &self.nodes[index];
+        let mut item = node.item.clone();
+        item.children = node.children.iter().map(|i| self.build(*i)).collect();
+        item
+    }
+}
+
+// The field types accepted by the functions that add values, these mirror the assertions wireshark does.
+pub(crate) const UINT_TYPES: &[FieldType] = &[
+    FieldType::CHAR,
+    FieldType::UINT8,
+    FieldType::UINT16,
+    FieldType::UINT24,
+    FieldType::UINT32,
+    FieldType::FRAMENUM,
+];
+pub(crate) const INT_TYPES: &[FieldType] = &[FieldType::INT8, FieldType::INT16, FieldType::INT24, FieldType::INT32];
+pub(crate) const UINT64_TYPES: &[FieldType] = &[
+    FieldType::UINT40,
+    FieldType::UINT48,
+    FieldType::UINT56,
+    FieldType::UINT64,
+];
+pub(crate) const INT64_TYPES: &[FieldType] = &[FieldType::INT40, FieldType::INT48, FieldType::INT56, FieldType::INT64];
+pub(crate) const STRING_TYPES: &[FieldType] = &[
+    FieldType::STRING,
+    FieldType::STRINGZ,
+    FieldType::UINT_STRING,
+    FieldType::STRINGZPAD,
+    FieldType::STRINGZTRUNC,
+];
+pub(crate) const BYTES_TYPES: &[FieldType] = &[FieldType::BYTES, FieldType::UINT_BYTES];
+pub(crate) const TIME_TYPES: &[FieldType] = &[FieldType::ABSOLUTE_TIME, FieldType::RELATIVE_TIME];
+
+/// Reference to the root of the tree or an item in the recording, this is what the epan wrappers hold for the mock
+/// backend. It's only valid while the [`Harness`] is running the dissector.
+#[derive(Copy, Clone)]
+pub struct NodeRef {
+    recording: *const RefCell<Recording>,
+    node: Option<usize>,
+}
+
+impl NodeRef {
+    fn with<R>(self, f: impl FnOnce(&mut Recording) -> R) -> R {
+        // The recording outlives the dissection, it's owned by Harness::run.
+        let recording = unsafe { &*self.recording };
+        f(&mut recording.borrow_mut())
+    }
+
+    fn item_ref(self, index: usize) -> NodeRef {
+        NodeRef {
+            recording: self.recording,
+            node: Some(index),
+        }
+    }
+
+    fn index(self) -> usize {
+        self.node.expect("The root of the tree is not an item.")
+    }
+
+    /// The reference to the item that was added, or if adding it failed, make the exception pending. The reference
+    /// returned in that case is never used, as everything is ignored while an exception is pending.
+    fn added(self, index: Result<usize, Exception>) -> NodeRef {
+        match index {
+            Ok(index) => self.item_ref(index),
+            Err(exception) => {
+                exceptions::set_pending(exception);
+                self
+            }
+        }
+    }
+
+    pub(crate) fn add_item(
+        self,
+        hfindex: HFIndex,
+        buffer: BufferRef,
+        start: usize,
+        length: usize,
+        encoding: Encoding,
+    ) -> NodeRef {
+        if exceptions::pending().is_some() {
+            return self;
+        }
+        let index = self.with(|r| -> Result<usize, Exception> {
+            let field = r.field(hfindex);
+            let (length, value) = match field.field_type {
+                FieldType::UINT_STRING | FieldType::UINT_BYTES => {
+                    // The length is the size of the count that precedes the data.
+                    let count = get_uint(buffer.get(start, length)?, encoding) as usize;
+                    let data = buffer.get(start + length, count)?;
+                    (length + count, decode(&field, data, encoding))
+                }
+                _ => (length, decode(&field, buffer.get(start, length)?, encoding)),
+            };
+            r.events.push(Event::AddItem {
+                abbrev: field.abbrev.clone(),
+                offset: buffer.origin + start,
+                length,
+                value: value.clone(),
+            });
+            Ok(r.add(
+                self.node,
+                Item {
+                    field,
+                    offset: buffer.origin + start,
+                    length,
+                    bits: None,
+                    encoding,
+                    value,
+                    text: None,
+                    subtree: None,
+                    children: Vec::new(),
+                    experts: Vec::new(),
+                    generated: false,
+                },
+            ))
+        });
+        self.added(index)
+    }
+
+    pub(crate) fn add_bits_item(
+        self,
+        hfindex: HFIndex,
+        buffer: BufferRef,
+        bit_offset: usize,
+        no_of_bits: usize,
+        encoding: Encoding,
+    ) -> NodeRef {
+        if exceptions::pending().is_some() {
+            return self;
+        }
+        let index = self.with(|r| -> Result<usize, Exception> {
+            let field = r.field(hfindex);
+            let start = bit_offset / 8;
+            let length = (bit_offset % 8 + no_of_bits).div_ceil(8);
+            let raw = get_bits(buffer.get(start, length)?, bit_offset % 8, no_of_bits, encoding);
+            let value = integer_value(field.field_type, raw, no_of_bits);
+            r.events.push(Event::AddBitsItem {
+                abbrev: field.abbrev.clone(),
+                bit_offset,
+                bits: no_of_bits,
+                value: value.clone(),
+            });
+            Ok(r.add(
+                self.node,
+                Item {
+                    field,
+                    offset: buffer.origin + start,
+                    length,
+                    bits: Some((bit_offset, no_of_bits)),
+                    encoding,
+                    value,
+                    text: None,
+                    subtree: None,
+                    children: Vec::new(),
+                    experts: Vec::new(),
+                    generated: false,
+                },
+            ))
+        });
+        self.added(index)
+    }
+
+    /// Add an item with a value that doesn't come from the buffer, panics if the field's type isn't in `accepted`,
+    /// like wireshark would complain about the dissector. The range still has to be inside the buffer. If text is
+    /// provided it replaces the value in the label.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn add_value(
+        self,
+        hfindex: HFIndex,
+        buffer: BufferRef,
+        start: usize,
+        length: usize,
+        accepted: &[FieldType],
+        value: Value,
+        text: Option<&str>,
+    ) -> NodeRef {
+        if exceptions::pending().is_some() {
+            return self;
+        }
+        let index = self.with(|r| -> Result<usize, Exception> {
+            let field = r.field(hfindex);
+            if !accepted.contains(&field.field_type) {
+                panic!(
+                    "Field {:?} has type {:?}, this function needs one of {:?}.",
+                    field.abbrev, field.field_type, accepted
+                );
+            }
+            buffer.get(start, length)?;
+            // Strings are handed to wireshark as C strings, which can't hold null characters.
+            let value = match value {
+                Value::String(v) => Value::String(v.replace('\0', "")),
+                v => v,
+            };
+            r.events.push(Event::AddItem {
+                abbrev: field.abbrev.clone(),
+                offset: buffer.origin + start,
+                length,
+                value: value.clone(),
+            });
+            let text = text.map(|t| format!("{}: {}", field.name, t.replace('\0', "")));
+            Ok(r.add(
+                self.node,
+                Item {
+                    field,
+                    offset: buffer.origin + start,
+                    length,
+                    bits: None,
+                    encoding: Encoding::NA,
+                    value,
+                    text,
+                    subtree: None,
+                    children: Vec::new(),
+                    experts: Vec::new(),
+                    generated: false,
+                },
+            ))
+        });
+        self.added(index)
+    }
+
+    /// Add a variable length integer of at most length bytes, returns the decoded value and the number of bytes used.
+    /// If the integer doesn't end within length bytes the value and the number of bytes are zero.
+    pub(crate) fn add_varint(
+        self,
+        hfindex: HFIndex,
+        buffer: BufferRef,
+        start: usize,
+        length: usize,
+        encoding: Encoding,
+    ) -> (NodeRef, u64, usize) {
+        if exceptions::pending().is_some() {
+            return (self, 0, 0);
+        }
+        let (raw, used) = match get_varint(&buffer.data()[start.min(buffer.len())..], length, encoding) {
+            Some(v) => v,
+            // Ran into the end of the buffer, wireshark would throw an exception.
+            None if start + length > buffer.len() => {
+                exceptions::set_pending(Exception::ReportedBoundsError);
+                return (self, 0, 0);
+            }
+            None => (0, 0),
+        };
+        let index = self.with(|r| -> Result<usize, Exception> {
+            let field = r.field(hfindex);
+            let value = integer_value(field.field_type, raw, 64);
+            r.events.push(Event::AddItem {
+                abbrev: field.abbrev.clone(),
+                offset: buffer.origin + start,
+                length: used,
+                value: value.clone(),
+            });
+            Ok(r.add(
+                self.node,
+                Item {
+                    field,
+                    offset: buffer.origin + start,
+                    length: used,
+                    bits: None,
+                    encoding,
+                    value,
+                    text: None,
+                    subtree: None,
+                    children: Vec::new(),
+                    experts: Vec::new(),
+                    generated: false,
+                },
+            ))
+        });
+        (self.added(index), raw, used)
+    }
+
+    /// The number of bytes the item this refers to covers.
+    pub(crate) fn length(self) -> usize {
+        if exceptions::pending().is_some() {
+            return 0;
+        }
+        let index = self.index();
+        self.with(|r| r.nodes[index].item.length)
+    }
+
+    /// The value of the item this refers to.
+    pub(crate) fn value(self) -> Value {
+        if exceptions::pending().is_some() {
+            return Value::None;
+        }
+        let index = self.index();
+        self.with(|r| r.nodes[index].item.value.clone())
+    }
+
+    pub(crate) fn add_subtree(self, ett: ETTIndex) -> NodeRef {
+        if exceptions::pending().is_some() {
+            return self;
+        }
+        let index = self.index();
+        self.with(|r| {
+            if ett.0 < 0 || ett.0 as usize >= r.tree_count {
+                panic!("Subtree index {:?} is not registered.", ett);
+            }
+            let item = &mut r.nodes[index].item;
+            item.subtree = Some(ett);
+            let abbrev = item.field.abbrev.clone();
+            r.events.push(Event::AddSubtree { abbrev, ett });
+        });
+        self
+    }
+
+    pub(crate) fn set_generated(self) {
+        if exceptions::pending().is_some() {
+            return;
+        }
+        let index = self.index();
+        self.with(|r| r.nodes[index].item.generated = true);
+    }
+
+    pub(crate) fn set_text(self, text: &str) {
+        if exceptions::pending().is_some() {
+            return;
+        }
+        let index = self.index();
+        self.with(|r| {
+            let item = &mut r.nodes[index].item;
+            item.text = Some(text.to_owned());
+            let abbrev = item.field.abbrev.clone();
+            r.events.push(Event::SetText {
+                abbrev,
+                text: text.to_owned(),
+            });
+        });
+    }
+
+    pub(crate) fn append_text(self, text: &str) {
+        if exceptions::pending().is_some() {
+            return;
+        }
+        let index = self.index();
+        self.with(|r| {
+            let item = &mut r.nodes[index].item;
+            item.text = Some(item.label() + text);
+            let abbrev = item.field.abbrev.clone();
+            r.events.push(Event::AppendText {
+                abbrev,
+                text: text.to_owned(),
+            });
+        });
+    }
+
+    /// Add an item for the expert info, the label is the text if provided and the summary otherwise. The range has to
+    /// be inside the buffer.
+    pub(crate) fn add_expert(
+        self,
+        eiindex: EIIndex,
+        buffer: BufferRef,
+        start: usize,
+        length: usize,
+        text: Option<&str>,
+    ) -> NodeRef {
+        if exceptions::pending().is_some() {
+            return self;
+        }
+        let index = self.with(|r| -> Result<usize, Exception> {
+            let mut expert = r.expert(eiindex);
+            let field = r.field(HFIndex(eiindex.hf));
+            buffer.get(start, length)?;
+            if let Some(text) = text {
+                expert.text = text.to_owned();
+            }
+            let index = r.add(
+                self.node,
+                Item {
+                    field,
+                    offset: buffer.origin + start,
+                    length,
+                    bits: None,
+                    encoding: Encoding::NA,
+                    value: Value::None,
+                    text: text.map(|t| t.to_owned()),
+                    subtree: None,
+                    children: Vec::new(),
+                    experts: Vec::new(),
+                    generated: false,
+                },
+            );
+            r.report(index, expert);
+            Ok(index)
+        });
+        self.added(index)
+    }
+
+    /// Attach the expert info to the item this refers to.
+    pub(crate) fn add_expert_info(self, eiindex: EIIndex) {
+        if exceptions::pending().is_some() {
+            return;
+        }
+        let index = self.index();
+        self.with(|r| {
+            let expert = r.expert(eiindex);
+            r.report(index, expert);
+        });
+    }
+
+    /// Call the stand-in for the dissector on the buffer, returns the number of bytes it consumed. Unknown dissectors
+    /// reject everything.
+    pub(crate) fn call_dissector(self, name: &str, buffer: BufferRef) -> usize {
+        if exceptions::pending().is_some() {
+            return 0;
+        }
+        let stand_in = self.with(|r| {
+            let registry = r.registry.borrow();
+            let found = registry.dissectors.iter().find(|(n, _, _)| n == name);
+            found.map(|(_, protocol, dissector)| (protocol.clone(), dissector.clone()))
+        });
+        let (protocol, dissector) = match stand_in {
+            Some(v) => v,
+            None => return 0,
+        };
+        let consumed = dissector(buffer.data()).min(buffer.len());
+        if consumed == 0 {
+            return 0;
+        }
+        self.with(|r| {
+            r.events.push(Event::CallDissector {
+                dissector: name.to_owned(),
+                offset: buffer.origin,
+                length: consumed,
+            });
+            // The called dissector has no registered field, it's shown as a protocol item.
+            let field = Field {
+                index: HFIndex(-1),
+                name: protocol.clone(),
+                abbrev: name.to_owned(),
+                field_type: FieldType::PROTOCOL,
+                display: FieldDisplay::BASE_NONE,
+                strings: HeaderFieldStrings::None,
+                bitmask: 0,
+            };
+            r.add(
+                self.node,
+                Item {
+                    field,
+                    offset: buffer.origin,
+                    length: consumed,
+                    bits: None,
+                    encoding: Encoding::NA,
+                    value: Value::None,
+                    text: Some(format!("{} ({} bytes)", protocol, consumed)),
+                    subtree: None,
+                    children: Vec::new(),
+                    experts: Vec::new(),
+                    generated: false,
+                },
+            );
+        });
+        consumed
+    }
+
+    /// The dissector registered in the integer table for the value.
+    pub(crate) fn table_uint(self, table: &str, value: u32) -> Option<String> {
+        self.with(|r| r.registry.borrow().uint_entry(table, value))
+    }
+
+    /// The dissector registered in the string table for the value.
+    pub(crate) fn table_string(self, table: &str, value: &str) -> Option<String> {
+        self.with(|r| r.registry.borrow().string_entry(table, value))
+    }
+
+    /// The dissectors in the heuristic list, in the order they are tried.
+    pub(crate) fn heuristics(self, list: &str) -> Vec<String> {
+        self.with(|r| {
+            let registry = r.registry.borrow();
+            registry
+                .heuristics
+                .iter()
+                .filter(|(l, _)| l == list)
+                .map(|(_, d)| d.clone())
+                .collect()
+        })
+    }
+
+    pub(crate) fn prepend_text(self, text: &str) {
+        if exceptions::pending().is_some() {
+            return;
+        }
+        let index = self.index();
+        self.with(|r| {
+            let item = &mut r.nodes[index].item;
+            item.text = Some(text.to_owned() + &item.label());
+            let abbrev = item.field.abbrev.clone();
+            r.events.push(Event::PrependText {
+                abbrev,
+                text: text.to_owned(),
+            });
+        });
+    }
+}
+
+/// Reference to the recording, this is what the [`epan::PacketInfo`] holds for the mock backend. It's only valid while
+/// the [`Harness`] is running the dissector.
+#[derive(Copy, Clone)]
+pub struct InfoRef {
+    recording: *const RefCell<Recording>,
+}
+
+impl InfoRef {
+    fn with<R>(self, f: impl FnOnce(&mut Recording) -> R) -> R {
+        // The recording outlives the dissection, it's owned by Harness::run.
+        let recording = unsafe { &*self.recording };
+        f(&mut recording.borrow_mut())
+    }
+
+    /// The metadata of the packet.
+    pub(crate) fn packet(self) -> Packet {
+        self.with(|r| r.packet.clone())
+    }
+
+    /// Whether the dissector may ask for more data, only if it's called from [`Harness::dissect_segments()`].
+    pub(crate) fn can_desegment(self) -> bool {
+        self.with(|r| r.can_desegment)
+    }
+
+    /// Ask for more data, length bytes at least, the data from the offset is handed to the dissector again.
+    pub(crate) fn set_desegment(self, offset: usize, length: usize) {
+        self.with(|r| r.desegment = Some((offset, length)));
+    }
+
+    /// Report the exception in the tree, the dissection continues.
+    pub(crate) fn report_exception(self, exception: Exception) {
+        self.with(|r| {
+            r.events.push(Event::ReportException {
+                exception: exception.clone(),
+            });
+            r.reported_exceptions.push(exception);
+        });
+    }
+
+    /// The conversation of the packet, by the source and destination address and port.
+    pub(crate) fn conversation(self) -> ConversationRef {
+        self.with(|r| {
+            let src = (r.packet.src.clone(), r.packet.src_port);
+            let dst = (r.packet.dst.clone(), r.packet.dst_port);
+            let mut file = r.file.borrow_mut();
+            let conversations = &mut file.conversations;
+            let existing = conversations
+                .iter()
+                .find(|c| c.endpoints == [src.clone(), dst.clone()] || c.endpoints == [dst.clone(), src.clone()]);
+            let data = match existing {
+                Some(c) => c.data.clone(),
+                None => {
+                    let data = Rc::new(epan::ProtoData::new());
+                    conversations.push(Conversation {
+                        endpoints: [src, dst],
+                        data: data.clone(),
+                    });
+                    data
+                }
+            };
+            ConversationRef { data }
+        })
+    }
+
+    /// Add the fragment to the table, returns the message if it is complete and this packet is one of its fragments.
+    /// Like wireshark, fragments are only added on the first pass and the first of duplicate fragments is used.
+    pub(crate) fn add_fragment(
+        self,
+        table: &ReassemblyRef,
+        id: u32,
+        frag_number: u32,
+        data: &[u8],
+        more_frags: bool,
+    ) -> Option<Rc<Reassembled>> {
+        self.with(|r| {
+            let frame = r.packet.number;
+            let ports = match table.key {
+                ReassemblyKey::Addresses => None,
+                ReassemblyKey::AddressesPorts => Some((r.packet.src_port, r.packet.dst_port)),
+            };
+            let key = (table.index, r.packet.src.clone(), r.packet.dst.clone(), ports, id);
+            let mut file = r.file.borrow_mut();
+            let existing = file.reassembled.iter().find(|m| {
+                m.key == key && (m.fragments.iter().any(|(f, _)| *f == frame) || m.duplicates.contains(&frame))
+            });
+            if let Some(message) = existing {
+                return Some(message.clone());
+            }
+            if r.packet.visited {
+                return None;
+            }
+
+            let index = match file.fragments.iter().position(|f| f.key == key) {
+                Some(index) => index,
+                None => {
+                    file.fragments.push(Fragments {
+                        key: key.clone(),
+                        fragments: BTreeMap::new(),
+                        duplicates: Vec::new(),
+                        last: None,
+                    });
+                    file.fragments.len() - 1
+                }
+            };
+            let pending = &mut file.fragments[index];
+            match pending.fragments.entry(frag_number) {
+                std::collections::btree_map::Entry::Vacant(e) => {
+                    e.insert((frame, data.to_vec()));
+                }
+                std::collections::btree_map::Entry::Occupied(_) => pending.duplicates.push(frame),
+            }
+            if !more_frags {
+                pending.last = Some(frag_number);
+            }
+            let last = pending.last?;
+            if !(0..=last).all(|n| pending.fragments.contains_key(&n)) {
+                return None;
+            }
+
+            let pending = file.fragments.remove(index);
+            let fragments: Vec<&(u32, Vec<u8>)> = pending.fragments.range(0..=last).map(|(_, f)| f).collect();
+            let message = Rc::new(Reassembled {
+                key,
+                data: fragments.iter().flat_map(|(_, d)| d.iter().copied()).collect(),
+                fragments: fragments.iter().map(|(f, d)| (*f, d.len())).collect(),
+                duplicates: pending.duplicates.clone(),
+                reassembled_in: frame,
+            });
+            file.reassembled.push(message.clone());
+            Some(message)
+        })
+    }
+
+    /// Whether there is a stand-in for the dissector.
+    pub(crate) fn has_dissector(self, name: &str) -> bool {
+        self.with(|r| r.registry.borrow().dissectors.iter().any(|(n, _, _)| n == name))
+    }
+
+    /// Whether the dissector table exists.
+    pub(crate) fn has_table(self, name: &str) -> bool {
+        self.with(|r| r.registry.borrow().tables.iter().any(|t| t == name))
+    }
+
+    /// Whether the heuristic list exists, only lists with dissectors in them do.
+    pub(crate) fn has_heuristic_list(self, name: &str) -> bool {
+        self.with(|r| r.registry.borrow().heuristics.iter().any(|(l, _)| l == name))
+    }
+
+    /// Show the buffer as its own tab in the bytes pane.
+    pub(crate) fn add_data_source(self, name: &str, buffer: BufferRef) {
+        self.with(|r| r.data_sources.push((name.to_owned(), buffer.data().to_vec())));
+    }
+
+    /// The data attached to the packet under this key, the packets are told apart by their frame number.
+    pub(crate) fn packet_data(self, key: u32) -> Rc<epan::ProtoData> {
+        self.with(|r| {
+            let mut file = r.file.borrow_mut();
+            file.packet_data
+                .entry((r.packet.number, key))
+                .or_insert_with(|| Rc::new(epan::ProtoData::new()))
+                .clone()
+        })
+    }
+
+    /// Replace the text after the fence.
+    pub(crate) fn set_column(self, column: Column, text: &str) {
+        self.with(|r| {
+            let column = r.column(column);
+            column.text.truncate(column.fence);
+            column.text.push_str(text);
+        });
+    }
+
+    /// Append the text, preceded by the separator if the column isn't empty.
+    pub(crate) fn append_column(self, column: Column, separator: Option<&str>, text: &str) {
+        self.with(|r| {
+            let column = r.column(column);
+            if let Some(separator) = separator {
+                if !column.text.is_empty() {
+                    column.text.push_str(separator);
+                }
+            }
+            column.text.push_str(text);
+        });
+    }
+
+    /// Remove the text after the fence.
+    pub(crate) fn clear_column(self, column: Column) {
+        self.with(|r| {
+            let column = r.column(column);
+            column.text.truncate(column.fence);
+        });
+    }
+
+    /// Put the fence at the end of the current text.
+    pub(crate) fn set_column_fence(self, column: Column) {
+        self.with(|r| {
+            let column = r.column(column);
+            column.fence = column.text.len();
+        });
+    }
+}
+
+/// The values of the preferences, this is what the [`epan::Preferences`] holds for the mock backend. The values are
+/// shared between the harness and the dissector.
+#[derive(Clone)]
+pub struct PreferencesRef {
+    values: Rc<RefCell<Vec<(String, PreferenceValue)>>>,
+}
+
+impl PreferencesRef {
+    pub(crate) fn get(&self, name: &str) -> Option<PreferenceValue> {
+        let values = self.values.borrow();
+        values.iter().find(|(n, _)| n == name).map(|(_, v)| v.clone())
+    }
+
+    fn set(&self, name: &str, value: PreferenceValue) {
+        let mut values = self.values.borrow_mut();
+        let current = match values.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => v,
+            None => panic!("Preference {:?} is not registered.", name),
+        };
+        if std::mem::discriminant(current) != std::mem::discriminant(&value) {
+            panic!("Preference {:?} is {:?}, can't set it to {:?}.", name, current, value);
+        }
+        *current = value;
+    }
+}
+
+/// Reference to the data of a conversation, this is what the [`epan::Conversation`] holds for the mock backend.
+#[derive(Clone)]
+pub struct ConversationRef {
+    data: Rc<epan::ProtoData>,
+}
+
+impl ConversationRef {
+    pub(crate) fn data(&self) -> &epan::ProtoData {
+        &self.data
+    }
+
+    /// The harness always runs the dissector under test, so this has no effect.
+    pub(crate) fn set_dissector(&self) {}
+}
+
+/// A reassembly table of the mock backend, this is what the [`epan::ReassemblyTable`] holds. The fragments are kept
+/// with the other data of the capture file.
+#[derive(Clone)]
+pub struct ReassemblyRef {
+    index: usize,
+    key: ReassemblyKey,
+    pub(crate) name: String,
+    /// The fields, in the order of [`crate::dissector::Reassembly`]'s fields.
+    pub(crate) hf: Vec<HFIndex>,
+    /// The subtrees for a fragment and for the list of fragments.
+    pub(crate) ett: [ETTIndex; 2],
+}
+
+/// Reference to the data of a buffer, this is what the [`epan::TVB`] holds for the mock backend. It's only valid while
+/// the [`Harness`] is running the dissector.
+#[derive(Copy, Clone)]
+pub struct BufferRef {
+    recording: *const RefCell<Recording>,
+    data: *const u8,
+    len: usize,
+    /// The offset of the data in the packet, for buffers that are a subset of it.
+    origin: usize,
+}
+
+impl BufferRef {
+    /// The data this buffer refers to, the lifetime is not bound to anything, same as for the wireshark buffers.
+    pub(crate) fn data<'a>(self) -> &'a [u8] {
+        unsafe { std::slice::from_raw_parts(self.data, self.len) }
+    }
+
+    pub(crate) fn len(self) -> usize {
+        self.len
+    }
+
+    /// A buffer for a section of this one, the range is clamped to the data. Items added to it are positioned in the
+    /// packet, like wireshark does.
+    pub(crate) fn subset(self, offset: usize, length: usize) -> BufferRef {
+        let offset = offset.min(self.len);
+        BufferRef {
+            recording: self.recording,
+            data: self.data().as_ptr().wrapping_add(offset),
+            len: length.min(self.len - offset),
+            origin: self.origin + offset,
+        }
+    }
+
+    /// A buffer with new data, like decompressed or decrypted data, it is kept until the dissection is done.
+    pub(crate) fn new_child(self, data: Vec<u8>) -> BufferRef {
+        // The recording outlives the dissection, it's owned by Harness::run.
+        let recording = unsafe { &*self.recording };
+        let buffer = BufferRef {
+            recording: self.recording,
+            data: data.as_ptr(),
+            len: data.len(),
+            origin: 0,
+        };
+        // Moving the vector doesn't move its data.
+        recording.borrow_mut().buffers.push(data);
+        buffer
+    }
+
+    /// Retrieve a section of the buffer, returns the exception wireshark would throw if it is out of bounds. There is no
+    /// captured length for mock buffers, so this is always [`Exception::ReportedBoundsError`].
+    pub(crate) fn get<'a>(self, offset: usize, length: usize) -> Result<&'a [u8], Exception> {
+        match offset.checked_add(length) {
+            Some(end) if end <= self.len => Ok(&self.data()[offset..end]),
+            _ => Err(Exception::ReportedBoundsError),
+        }
+    }
+}
+
+/// Read an integer of up to 8 bytes from the data, using the endianness from the encoding.
+fn get_uint(data: &[u8], encoding: Encoding) -> u64 {
+    let little_endian = (encoding as u32) & (Encoding::LITTLE_ENDIAN as u32) != 0;
+    let mut v: u64 = 0;
+    for i in 0..data.len().min(8) {
+        let byte = if little_endian {
+            data[data.len().min(8) - 1 - i]
+        } else {
+            data[i]
+        };
+        v = (v << 8) | byte as u64;
+    }
+    v
+}
+
+/// Read no_of_bits starting at bit_offset (within the first byte) from the data.
+fn get_bits(data: &[u8], bit_offset: usize, no_of_bits: usize, encoding: Encoding) -> u64 {
+    let mask = if no_of_bits >= 64 {
+        u64::MAX
+    } else {
+        (1u64 << no_of_bits) - 1
+    };
+    let little_endian = (encoding as u32) & (Encoding::LITTLE_ENDIAN as u32) != 0;
+    if little_endian {
+        // Bits are counted from the least significant bit of the first byte.
+        (get_uint(data, encoding) >> bit_offset) & mask
+    } else {
+        // Bits are counted from the most significant bit of the first byte.
+        let total = data.len() * 8;
+        (get_uint(data, encoding) >> (total - bit_offset - no_of_bits)) & mask
+    }
+}
+
+/// Decode a string using the character encoding, the string ends at the first null character. None is returned for
+/// encodings the mock doesn't decode.
+pub(crate) fn get_string(data: &[u8], encoding: Encoding) -> Option<String> {
+    let character_encoding = (encoding as u32) & (Encoding::STR_MASK as u32);
+    let units = |size: usize| -> Vec<u32> {
+        data.chunks_exact(size)
+            .map(|c| get_uint(c, encoding) as u32)
+            .take_while(|c| *c != 0)
+            .collect()
+    };
+    let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+    if character_encoding == Encoding::UTF_16 as u32 || character_encoding == Encoding::UCS_2 as u32 {
+        let units: Vec<u16> = units(2).iter().map(|c| *c as u16).collect();
+        Some(String::from_utf16_lossy(&units))
+    } else if character_encoding == Encoding::UCS_4 as u32 {
+        Some(
+            units(4)
+                .iter()
+                .map(|c| char::from_u32(*c).unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect(),
+        )
+    } else if character_encoding == Encoding::ISO_8859_1 as u32 {
+        Some(data[0..end].iter().map(|b| *b as char).collect())
+    } else if character_encoding == Encoding::ASCII as u32 || character_encoding == Encoding::UTF_8 as u32 {
+        Some(String::from_utf8_lossy(&data[0..end]).into_owned())
+    } else {
+        None
+    }
+}
+
+/// Decode a variable length integer of at most max_length bytes, returns the value and the number of bytes used. None
+/// is returned if the data ends before the integer does.
+fn get_varint(data: &[u8], max_length: usize, encoding: Encoding) -> Option<(u64, usize)> {
+    let max_length = max_length.min(10);
+    let encoding = encoding as u32;
+    if encoding == Encoding::VARINT_QUIC as u32 {
+        // The two most significant bits of the first byte hold the length.
+        let length = 1usize << (data.first()? >> 6);
+        if length > max_length {
+            return Some((0, 0));
+        }
+        let bytes = data.get(0..length)?;
+        let value = get_uint(bytes, Encoding::BIG_ENDIAN) & (u64::MAX >> (64 - (length * 8 - 2)));
+        return Some((value, length));
+    }
+    if encoding == Encoding::VARINT_SDNV as u32 {
+        // Most significant group first, the high bit is set on all but the last byte.
+        let mut value: u64 = 0;
+        for i in 0..max_length {
+            let b = *data.get(i)?;
+            value = (value << 7) | (b & 0x7f) as u64;
+            if b & 0x80 == 0 {
+                return Some((value, i + 1));
+            }
+        }
+        return Some((0, 0));
+    }
+    // Protobuf style, least significant group first.
+    let mut value: u64 = 0;
+    for i in 0..max_length {
+        let b = *data.get(i)?;
+        value |= ((b & 0x7f) as u64) << (7 * i);
+        if b & 0x80 == 0 {
+            if encoding == Encoding::VARINT_ZIGZAG as u32 {
+                value = (value >> 1) ^ (-((value & 1) as i64) as u64);
+            }
+            return Some((value, i + 1));
+        }
+    }
+    Some((0, 0))
+}
+
+/// Convert a raw integer into a value appropriate for the field type, signed types are sign extended from the width.
+fn integer_value(field_type: FieldType, raw: u64, width_bits: usize) -> Value {
+    match field_type {
+        FieldType::INT8
+        | FieldType::INT16
+        | FieldType::INT24
+        | FieldType::INT32
+        | FieldType::INT40
+        | FieldType::INT48
+        | FieldType::INT56
+        | FieldType::INT64 => {
+            let shift = 64 - width_bits.clamp(1, 64);
+            Value::Int(((raw << shift) as i64) >> shift)
+        }
+        FieldType::BOOLEAN => Value::Bool(raw != 0),
+        _ => Value::UInt(raw),
+    }
+}
+
+/// Decode the data for a field into a value.
+fn decode(field: &Field, data: &[u8], encoding: Encoding) -> Value {
+    match field.field_type {
+        FieldType::NONE | FieldType::PROTOCOL => Value::None,
+        FieldType::BOOLEAN
+        | FieldType::CHAR
+        | FieldType::UINT8
+        | FieldType::UINT16
+        | FieldType::UINT24
+        | FieldType::UINT32
+        | FieldType::UINT40
+        | FieldType::UINT48
+        | FieldType::UINT56
+        | FieldType::UINT64
+        | FieldType::FRAMENUM
+        | FieldType::INT8
+        | FieldType::INT16
+        | FieldType::INT24
+        | FieldType::INT32
+        | FieldType::INT40
+        | FieldType::INT48
+        | FieldType::INT56
+        | FieldType::INT64 => {
+            let mut raw = get_uint(data, encoding);
+            let mut width = data.len().min(8) * 8;
+            if field.bitmask != 0 {
+                raw = (raw & field.bitmask) >> field.bitmask.trailing_zeros();
+                width = 64 - (field.bitmask.leading_zeros() + field.bitmask.trailing_zeros()) as usize;
+            }
+            integer_value(field.field_type, raw, width)
+        }
+        FieldType::FLOAT if data.len() == 4 => Value::Double(f32::from_bits(get_uint(data, encoding) as u32) as f64),
+        FieldType::DOUBLE if data.len() == 8 => Value::Double(f64::from_bits(get_uint(data, encoding))),
+        FieldType::STRING
+        | FieldType::STRINGZ
+        | FieldType::UINT_STRING
+        | FieldType::STRINGZPAD
+        | FieldType::STRINGZTRUNC => match get_string(data, encoding) {
+            Some(text) => Value::String(text),
+            None => Value::Bytes(data.to_vec()),
+        },
+        _ => Value::Bytes(data.to_vec()),
+    }
+}
+
+/// The number of hexadecimal digits wireshark pads integer types to.
+fn hex_digits(field_type: FieldType) -> usize {
+    match field_type {
+        FieldType::CHAR | FieldType::UINT8 | FieldType::INT8 => 2,
+        FieldType::UINT16 | FieldType::INT16 => 4,
+        FieldType::UINT24 | FieldType::INT24 => 6,
+        FieldType::UINT32 | FieldType::INT32 | FieldType::FRAMENUM => 8,
+        FieldType::UINT40 | FieldType::INT40 => 10,
+        FieldType::UINT48 | FieldType::INT48 => 12,
+        FieldType::UINT56 | FieldType::INT56 => 14,
+        FieldType::UINT64 | FieldType::INT64 => 16,
+        _ => 0,
+    }
+}
+
+/// Format an integer according to the field's display.
+fn format_integer(field: &Field, value: &Value) -> String {
+    let digits = hex_digits(field.field_type);
+    let raw = value.as_u64().unwrap_or(0);
+    // Negative values are shown as their two's complement in the width of the field.
+    let masked = match value {
+        Value::Int(v) if *v < 0 && digits != 0 && digits < 16 => raw & ((1u64 << (digits * 4)) - 1),
+        _ => raw,
+    };
+    let dec = match value {
+        Value::Int(v) => format!("{}", v),
+        _ => format!("{}", raw),
+    };
+    let hex = format!("0x{:0width$x}", masked, width = digits);
+    match field.display {
+        FieldDisplay::BASE_HEX => hex,
+        FieldDisplay::BASE_OCT => format!("0{:o}", masked),
+        FieldDisplay::BASE_DEC_HEX => format!("{} ({})", dec, hex),
+        FieldDisplay::BASE_HEX_DEC => format!("{} ({})", hex, dec),
+        _ => dec,
+    }
+}
+
+/// Look up the value in the field's strings.
+fn lookup_string(strings: &HeaderFieldStrings, value: u64) -> Option<String> {
+    match strings {
+        HeaderFieldStrings::None => None,
+        HeaderFieldStrings::ValueString(v) => v.iter().find(|(k, _)| *k as u64 == value).map(|(_, s)| s.clone()),
+        HeaderFieldStrings::Value64String(v) => v.iter().find(|(k, _)| *k == value).map(|(_, s)| s.clone()),
+        HeaderFieldStrings::RangeString(v) => v
+            .iter()
+            .find(|((min, max), _)| (*min as u64) <= value && value <= (*max as u64))
+            .map(|(_, s)| s.clone()),
+    }
+}
+
+/// Format a value like wireshark would display it for this field.
+fn format_value(field: &Field, value: &Value) -> String {
+    match value {
+        Value::None => String::new(),
+        Value::UInt(_) | Value::Int(_) => {
+            let number = format_integer(field, value);
+            match field.strings {
+                HeaderFieldStrings::None => number,
+                _ => match lookup_string(&field.strings, value.as_u64().unwrap_or(0)) {
+                    Some(s) => format!("{} ({})", s, number),
+                    None => format!("Unknown ({})", number),
+                },
+            }
+        }
+        Value::Bool(v) => (if *v { "True" } else { "False" }).to_owned(),
+        Value::Double(v) => format!("{}", v),
+        Value::String(s) => s.clone(),
+        Value::Time { secs, nsecs } => format_time(field.field_type, *secs, *nsecs),
+        Value::Bytes(b) => {
+            if b.is_empty() {
+                return "<MISSING>".to_owned();
+            }
+            match (field.field_type, b.len()) {
+                (FieldType::IPv4, 4) => return format!("{}.{}.{}.{}", b[0], b[1], b[2], b[3]),
+                (FieldType::IPv6, 16) => {
+                    let mut octets = [0; 16];
+                    octets.copy_from_slice(b);
+                    return std::net::Ipv6Addr::from(octets).to_string();
+                }
+                (FieldType::ETHER, 6) => {
+                    return b
+                        .iter()
+                        .map(|v| format!("{:02x}", v))
+                        .collect::<Vec<String>>()
+                        .join(":");
+                }
+                (FieldType::GUID, 16) => {
+                    let hex: String = b.iter().map(|v| format!("{:02x}", v)).collect();
+                    return format!(
+                        "{}-{}-{}-{}-{}",
+                        &hex[0..8],
+                        &hex[8..12],
+                        &hex[12..16],
+                        &hex[16..20],
+                        &hex[20..32]
+                    );
+                }
+                _ => {}
+            }
+            let separator = match field.display {
+                FieldDisplay::SEP_DOT => ".",
+                FieldDisplay::SEP_DASH => "-",
+                FieldDisplay::SEP_COLON => ":",
+                FieldDisplay::SEP_SPACE => " ",
+                _ => "",
+            };
+            b.iter()
+                .map(|v| format!("{:02x}", v))
+                .collect::<Vec<String>>()
+                .join(separator)
+        }
+    }
+}
+
+/// Format a time, relative times in seconds and absolute times as a date in UTC.
+fn format_time(field_type: FieldType, secs: i64, nsecs: i32) -> String {
+    if field_type != FieldType::ABSOLUTE_TIME {
+        let sign = if secs < 0 || nsecs < 0 { "-" } else { "" };
+        return format!("{}{}.{:09} seconds", sign, secs.abs(), nsecs.abs());
+    }
+
+    // Convert days since the epoch into a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let days = secs.div_euclid(86400);
+    let seconds_of_day = secs.rem_euclid(86400);
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    format!(
+        "{} {:2}, {} {:02}:{:02}:{:02}.{:09} UTC",
+        MONTHS[(month - 1) as usize],
+        day,
+        year,
+        seconds_of_day / 3600,
+        (seconds_of_day / 60) % 60,
+        seconds_of_day % 60,
+        nsecs
+    )
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::dissector::{BasicHeaderFieldInfo, DissectorTableInfo, ExpertField, HeaderFieldInfo, Reassembly};
+    use crate::epan::{DissectorTable, PacketInfo, ProtoTree, ReassemblyTable, TVB};
+
+    type DissectFn = Box<dyn Fn(&TestDissector, &mut ProtoTree, &mut TVB, &mut PacketInfo) -> usize>;
+
+    /// Dissector for the tests, registers the provided fields, expert info, subtrees, reassembly tables and dissector
+    /// tables and runs the closure on the data. The closure finds the indices and tables it needs in the dissector it's
+    /// handed.
+    pub(crate) struct TestDissector {
+        pub(crate) fields: Vec<BasicHeaderFieldInfo>,
+        pub(crate) experts: Vec<ExpertField>,
+        pub(crate) tree_count: usize,
+        pub(crate) reassemblies: Vec<Reassembly>,
+        pub(crate) hf: Vec<HFIndex>,
+        pub(crate) ei: Vec<EIIndex>,
+        pub(crate) ett: Vec<ETTIndex>,
+        pub(crate) tables: Vec<ReassemblyTable>,
+        pub(crate) table_infos: Vec<DissectorTableInfo>,
+        pub(crate) dissector_tables: Vec<DissectorTable>,
+        pub(crate) registrations: Vec<Registration>,
+        dissect: DissectFn,
+    }
+
+    impl TestDissector {
+        pub(crate) fn new(
+            fields: &[BasicHeaderFieldInfo],
+            dissect: impl Fn(&TestDissector, &mut ProtoTree, &mut TVB, &mut PacketInfo) -> usize + 'static,
+        ) -> TestDissector {
+            TestDissector {
+                fields: fields.to_vec(),
+                experts: Vec::new(),
+                tree_count: 0,
+                reassemblies: Vec::new(),
+                hf: Vec::new(),
+                ei: Vec::new(),
+                ett: Vec::new(),
+                tables: Vec::new(),
+                table_infos: Vec::new(),
+                dissector_tables: Vec::new(),
+                registrations: Vec::new(),
+                dissect: Box::new(dissect),
+            }
+        }
+
+        pub(crate) fn with_experts(mut self, experts: &[ExpertField]) -> TestDissector {
+            self.experts = experts.to_vec();
+            self
+        }
+
+        pub(crate) fn with_trees(mut self, tree_count: usize) -> TestDissector {
+            self.tree_count = tree_count;
+            self
+        }
+
+        pub(crate) fn with_reassemblies(mut self, reassemblies: &[Reassembly]) -> TestDissector {
+            self.reassemblies = reassemblies.to_vec();
+            self
+        }
+
+        pub(crate) fn with_dissector_tables(mut self, tables: &[DissectorTableInfo]) -> TestDissector {
+            self.table_infos = tables.to_vec();
+            self
+        }
+
+        pub(crate) fn with_registrations(mut self, registrations: &[Registration]) -> TestDissector {
+            self.registrations = registrations.to_vec();
+            self
+        }
+    }
+
+    impl Dissector for TestDissector {
+        fn get_fields(&self) -> Vec<Box<dyn HeaderFieldInfo>> {
+            self.fields.iter().map(|f| f.as_boxed()).collect()
+        }
+        fn set_field_indices(&mut self, hf_indices: Vec<(Box<dyn HeaderFieldInfo>, HFIndex)>) {
+            self.hf = hf_indices.iter().map(|(_, index)| *index).collect();
+        }
+        fn get_expert_fields(&self) -> Vec<ExpertField> {
+            self.experts.clone()
+        }
+        fn set_expert_indices(&mut self, ei_indices: Vec<(ExpertField, EIIndex)>) {
+            self.ei = ei_indices.iter().map(|(_, index)| *index).collect();
+        }
+        fn get_tree_count(&self) -> usize {
+            self.tree_count
+        }
+        fn set_tree_indices(&mut self, ett_indices: Vec<ETTIndex>) {
+            self.ett = ett_indices;
+        }
+        fn get_reassembly_tables(&self) -> Vec<Reassembly> {
+            self.reassemblies.clone()
+        }
+        fn set_reassembly_tables(&mut self, tables: Vec<(Reassembly, ReassemblyTable)>) {
+            self.tables = tables.into_iter().map(|(_, table)| table).collect();
+        }
+        fn get_dissector_tables(&self) -> Vec<DissectorTableInfo> {
+            self.table_infos.clone()
+        }
+        fn set_dissector_tables(&mut self, tables: Vec<(DissectorTableInfo, DissectorTable)>) {
+            self.dissector_tables = tables.into_iter().map(|(_, table)| table).collect();
+        }
+        fn get_registration(&self) -> Vec<Registration> {
+            self.registrations.clone()
+        }
+        fn get_protocol_name(&self) -> (&'static str, &'static str, &'static str) {
+            ("Test Protocol", "Test", "test")
+        }
+        fn dissect(&self, proto: &mut ProtoTree, tvb: &mut TVB, pinfo: &mut PacketInfo) -> usize {
+            (self.dissect)(self, proto, tvb, pinfo)
+        }
+    }
+
+    const FIELDS: [BasicHeaderFieldInfo; 4] = [
+        BasicHeaderFieldInfo::simple("Header", "test.header", FieldType::NONE, FieldDisplay::BASE_NONE),
+        BasicHeaderFieldInfo::simple("Length", "test.length", FieldType::UINT16, FieldDisplay::BASE_DEC),
+        BasicHeaderFieldInfo::simple("Flag", "test.flag", FieldType::UINT8, FieldDisplay::BASE_HEX),
+        BasicHeaderFieldInfo::simple("Name", "test.name", FieldType::STRING, FieldDisplay::BASE_NONE),
+    ];
+
+    /// Header with a length, a flag and a name of that length, all in a subtree.
+    fn header_dissector() -> TestDissector {
+        TestDissector::new(&FIELDS, |d, proto, tvb, pinfo| {
+            pinfo.col_set_str(Column::Protocol, "Test");
+            let mut header = proto.add_item(d.hf[0], tvb, 0, 3, Encoding::NA);
+            let mut subtree = header.add_subtree(d.ett[0]);
+            let (_, length) = subtree.add_item_ret_uint(d.hf[1], tvb, 0, 2, Encoding::BIG_ENDIAN);
+            subtree.add_item(d.hf[2], tvb, 2, 1, Encoding::NA);
+            proto.add_item(d.hf[3], tvb, 3, length as usize, Encoding::UTF_8);
+            pinfo.col_add_str(Column::Info, &format!("{} bytes", length));
+            3 + length as usize
+        })
+        .with_trees(1)
+    }
+
+    #[test]
+    fn registers_fields_and_experts_in_order() {
+        let expert = ExpertField::simple("test.expert", Group::MALFORMED, Severity::ERROR, "Broken");
+        let mut d = TestDissector::new(&FIELDS[..2], |_, _, _, _| 0).with_experts(&[expert]);
+        let harness = Harness::new(&mut d);
+        assert_eq!(d.hf, vec![HFIndex(0), HFIndex(1)]);
+        assert_eq!(d.ei, vec![EIIndex { ei: 0, hf: 2 }]);
+        let abbrevs: Vec<&str> = harness.fields().iter().map(|f| f.abbrev.as_str()).collect();
+        assert_eq!(abbrevs, vec!["test.header", "test.length", "test.expert"]);
+        assert_eq!(harness.experts()[0].text, "Broken");
+    }
+
+    #[test]
+    fn dissects_into_tree() {
+        let mut d = header_dissector();
+        let harness = Harness::new(&mut d);
+        let dissection = harness.dissect(&d, &[0x00, 0x02, 0x81, b'h', b'i', 0xff]);
+        assert_eq!(dissection.consumed, 5);
+        assert_eq!(dissection.exception, None);
+        assert_eq!(dissection.items.len(), 2);
+
+        let header = &dissection.items[0];
+        assert_eq!(header.subtree, Some(d.ett[0]));
+        assert_eq!(header.children.len(), 2);
+        let length = dissection.find("test.length").unwrap();
+        assert_eq!((length.offset, length.length), (0, 2));
+        assert_eq!(length.value, Value::UInt(2));
+        assert_eq!(length.label(), "Length: 2");
+        assert_eq!(dissection.find("test.flag").unwrap().label(), "Flag: 0x81");
+        let name = dissection.find("test.name").unwrap();
+        assert_eq!((name.offset, name.length), (3, 2));
+        assert_eq!(name.value, Value::String("hi".to_owned()));
+
+        assert_eq!(dissection.columns[&Column::Protocol], "Test");
+        assert_eq!(dissection.columns[&Column::Info], "2 bytes");
+        assert_eq!(
+            dissection.events[1],
+            Event::AddSubtree {
+                abbrev: "test.header".to_owned(),
+                ett: d.ett[0]
+            }
+        );
+    }
+
+    #[test]
+    fn exception_stops_the_tree() {
+        let mut d = header_dissector();
+        let harness = Harness::new(&mut d);
+        // The name is longer than the data that is left.
+        let dissection = harness.dissect(&d, &[0x00, 0x05, 0x01, b'h']);
+        assert_eq!(dissection.exception, Some(Exception::ReportedBoundsError));
+        assert!(dissection.find("test.name").is_none());
+        assert!(dissection.find("test.flag").is_some());
+        // The info column is written after the exception, so it's not changed.
+        assert!(!dissection.columns.contains_key(&Column::Info));
+        // The next dissection starts without the exception.
+        let dissection = harness.dissect(&d, &[0x00, 0x00, 0x01]);
+        assert_eq!(dissection.exception, None);
+    }
+
+    #[test]
+    fn text_and_bitmask_labels() {
+        let mut flag = BasicHeaderFieldInfo::simple("Flag", "test.flag", FieldType::UINT8, FieldDisplay::BASE_DEC);
+        flag.bitmask = 0x0c;
+        let mut d = TestDissector::new(&[flag], |d, proto, tvb, _| {
+            let mut item = proto.add_item(d.hf[0], tvb, 0, 1, Encoding::NA);
+            item.append_text(" (appended)");
+            proto.add_item(d.hf[0], tvb, 0, 1, Encoding::NA).set_text("Replaced");
+            1
+        });
+        let harness = Harness::new(&mut d);
+        let dissection = harness.dissect(&d, &[0xf4]);
+        assert_eq!(dissection.items[0].value, Value::UInt(1));
+        assert_eq!(dissection.items[0].label(), ".... 01.. = Flag: 1 (appended)");
+        assert_eq!(dissection.items[1].label(), "Replaced");
+        assert_eq!(dissection.find_all("test.flag").len(), 2);
+    }
+}
@@ -21,7 +21,7 @@ impl From<&Box<dyn epan::HeaderFieldInfo>> for epan::proto::header_field_info {
             for (i, s) in v.iter() {
                 string_entries.push(epan::value_string::value_string {
                     value: *i,
-                    string: util::perm_string_ptr(&s),
+                    strptr: util::perm_string_ptr(&s),
                 })
             }
             // Needs to be terminated with a null entry
@@ -39,7 +39,7 @@ impl From<&Box<dyn epan::HeaderFieldInfo>> for epan::proto::header_field_info {
             for (i, s) in v.iter() {
                 string_entries.push(epan::value_string::value64_string {
                     value: *i,
-                    string: util::perm_string_ptr(&s),
+                    strptr: util::perm_string_ptr(&s),
                 })
             }
             // Needs to be terminated with a null entry
@@ -58,7 +58,7 @@ impl From<&Box<dyn epan::HeaderFieldInfo>> for epan::proto::header_field_info {
                 string_entries.push(epan::value_string::value_range_string {
                     value_min: i.0,
                     value_max: i.1,
-                    string: util::perm_string_ptr(&s),
+                    strptr: util::perm_string_ptr(&s),
                 })
             }
             // Needs to be terminated with a null entry
@@ -82,91 +82,308 @@ impl From<&Box<dyn epan::HeaderFieldInfo>> for epan::proto::header_field_info {
         epan::proto::header_field_info {
             name: util::perm_string_ptr(&hfi.name()),
             abbrev: util::perm_string_ptr(&hfi.abbrev()),
+            #[cfg(not(feature = "bindgen"))]
             type_: hfi.feature_type(),
+            #[cfg(feature = "bindgen")]
+            type_: hfi.feature_type() as u32,
             display: ((hfi.display_type() as i32) | display_or).into(),
             strings: strings_output,
             bitmask: hfi.bitmask(),
             blurb: blurb_output,
 
-            // pub id: i32,
-            // pub parent: i32,
-            // pub ref_type: hf_ref_type,
-            // pub same_name_pref_id: i32,
-            // pub same_name_next: *mut header_field_info,
+            // The remainder is what HFILL provides.
+            id: -1,
+            same_name_prev_id: -1,
             ..Default::default()
         }
     }
 }
 
-use std::rc::Rc;
-// Global state
-static mut DISSECTOR_PTR: Option<Rc<dyn Dissector>> = None;
-static mut HF_ENTRIES: Option<Vec<epan::proto::hf_register_info>> = None;
-static mut PROTO_ID: i32 = -1; // Todo? change into a newtype.
+impl From<&dissector::ExpertField> for epan::expert::expert_field_info {
+    fn from(expert: &dissector::ExpertField) -> Self {
+        // The remainder is what EXPFILL provides, it's filled in during registration.
+        epan::expert::expert_field_info {
+            name: util::perm_string_ptr(expert.abbrev.as_str()),
+            group: expert.group as i32,
+            severity: expert.severity as i32,
+            summary: util::perm_string_ptr(expert.summary.as_str()),
+            id: 0,
+            protocol: std::ptr::null(),
+            orig_severity: 0,
+            hf_info: Default::default(),
+        }
+    }
+}
+
+use std::cell::RefCell;
+
+/// The maximum number of dissectors a single plugin can hold. Wireshark's callbacks don't carry any user data, so each
+/// dissector gets its own set of monomorphized callback functions, this is the number of sets we instantiate.
+pub const MAX_DISSECTORS: usize = 32;
+
+/// Everything we need to keep around for a single registered dissector.
+struct PluginEntry {
+    /// The dissector object the user handed us.
+    dissector: Box<dyn Dissector>,
+    /// The protocol id returned by `proto_register_protocol`.
+    proto_id: i32,
+    /// The header field entries, wireshark holds pointers into these so they must persist.
+    hf_entries: Vec<epan::proto::hf_register_info>,
+    /// The expert info entries, these must persist for the same reason.
+    ei_entries: Vec<epan::expert::ei_register_info>,
+    /// The reassembly tables, wireshark holds pointers into these as well.
+    reassemblies: Vec<Box<epan::RegisteredReassembly>>,
+    /// The dissector handle created during the handoff.
+    handle: epan::packet::dissector_handle_t,
+    /// The preferences, if the dissector has any.
+    preferences: Option<epan::Preferences>,
+}
+
+// Global state, one entry per dissector, the index into this vector is the index the callbacks are instantiated with.
+// The vector is only modified by setup, before wireshark calls any of the callbacks. The entries are leaked so they
+// stay in place, the registration borrows them mutably and the dissection immutably, the RefCell checks these don't
+// overlap. See dissector::FileState for why the callbacks need no locking.
+static mut DISSECTORS: Vec<&'static RefCell<PluginEntry>> = Vec::new();
+
+/// Retrieve the entry for the dissector at this index.
+fn entry(index: usize) -> &'static RefCell<PluginEntry> {
+    unsafe { (&(*std::ptr::addr_of!(DISSECTORS)))[index] }
+}
+
+/// Retrieve the number of dissectors that were set up.
+fn entry_count() -> usize {
+    unsafe { (*std::ptr::addr_of!(DISSECTORS)).len() }
+}
+
+/// Helper to instantiate a generic callback function for each possible dissector index.
+macro_rules! per_dissector {
+    ($function:ident) => {
+        per_dissector!($function; 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31)
+    };
+    ($function:ident; $($index:literal)*) => {
+        [$($function::<$index>),*]
+    };
+}
+
+type DissectFunction = extern "C" fn(
+    *mut epan::tvbuff::tvbuff_t,
+    *mut epan::packet_info::packet_info,
+    *mut epan::proto::proto_tree,
+    *mut libc::c_void,
+) -> i32;
+type HeuristicFunction = extern "C" fn(
+    *mut epan::tvbuff::tvbuff_t,
+    *mut epan::packet_info::packet_info,
+    *mut epan::proto::proto_tree,
+    *mut libc::c_void,
+) -> bool;
+
+/// Callback without arguments, used for the preferences and the init and cleanup routines.
+type ApplyFunction = extern "C" fn();
+
+const DISSECT_FUNCTIONS: [DissectFunction; MAX_DISSECTORS] = per_dissector!(dissect_protocol_function);
+const HEURISTIC_FUNCTIONS: [HeuristicFunction; MAX_DISSECTORS] = per_dissector!(heuristic_dissector_function);
+const APPLY_FUNCTIONS: [ApplyFunction; MAX_DISSECTORS] = per_dissector!(apply_preferences_function);
+const INIT_FUNCTIONS: [ApplyFunction; MAX_DISSECTORS] = per_dissector!(init_function);
+const CLEANUP_FUNCTIONS: [ApplyFunction; MAX_DISSECTORS] = per_dissector!(cleanup_function);
+
+/// Actual implementation of setup that stores the passed in dissectors into the global state.
+pub fn setup(dissectors: Vec<Box<dyn Dissector>>) {
+    let first_setup = entry_count() == 0;
+    if entry_count() + dissectors.len() > MAX_DISSECTORS {
+        panic!("A single plugin can hold at most {} dissectors.", MAX_DISSECTORS);
+    }
 
-/// Actual implementation of setup that stores the passed in dissector into the global singleton.
-pub fn setup<T: 'static + Dissector>(d: Rc<T>) {
     unsafe {
-        // Register our two global functions.
-        let mut plugin_handle_box: Box<epan::proto::proto_plugin> = Box::new(Default::default());
-        plugin_handle_box.register_protoinfo = Some(proto_register_protoinfo);
-        plugin_handle_box.register_handoff = Some(proto_register_handoff);
-        let ptr_to_plugin = Box::leak(plugin_handle_box); // Need this to persist, but we don't ever need it anymore
-        epan::proto::proto_register_plugin(ptr_to_plugin);
-
-        // store the dissector we got handed in.
-        DISSECTOR_PTR = Some(d);
+        // Store the dissectors we got handed in.
+        let entries = &mut *std::ptr::addr_of_mut!(DISSECTORS);
+        for d in dissectors {
+            entries.push(Box::leak(Box::new(RefCell::new(PluginEntry {
+                dissector: d,
+                proto_id: -1,
+                hf_entries: Vec::new(),
+                ei_entries: Vec::new(),
+                reassemblies: Vec::new(),
+                handle: std::ptr::null_mut(),
+                preferences: None,
+            }))));
+        }
+
+        // Register our two global functions, they iterate over all dissectors, so this only has to happen once.
+        if first_setup {
+            let mut plugin_handle_box: Box<epan::proto::proto_plugin> = Box::new(Default::default());
+            plugin_handle_box.register_protoinfo = Some(proto_register_protoinfo);
+            plugin_handle_box.register_handoff = Some(proto_register_handoff);
+            let ptr_to_plugin = Box::leak(plugin_handle_box); // Need this to persist, but we don't ever need it anymore
+            epan::proto::proto_register_plugin(ptr_to_plugin);
+        }
+    }
+}
+
+thread_local! {
+    /// The index of the dissector that is running, None outside of the dissection.
+    static CURRENT: std::cell::Cell<Option<usize>> = const { std::cell::Cell::new(None) };
+}
+
+/// The entry of the dissector that is running, data attached to conversations and packets is stored under its
+/// protocol id.
+fn current() -> std::cell::Ref<'static, PluginEntry> {
+    match CURRENT.with(|c| c.get()) {
+        Some(index) => entry(index).borrow(),
+        None => panic!("This can only be used while dissecting."),
+    }
+}
+
+/// The protocol id of the dissector that is running.
+pub(crate) fn proto_id() -> i32 {
+    return current().proto_id;
+}
+
+/// The handle of the dissector that is running.
+pub(crate) fn handle() -> epan::packet::dissector_handle_t {
+    return current().handle;
+}
+
+/// Message of a panic, for reporting it as dissector bug.
+pub(crate) fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        return message.to_string();
     }
+    if let Some(message) = payload.downcast_ref::<String>() {
+        return message.clone();
+    }
+    return "panic in Rust dissector".to_string();
 }
 
-/// Global dissection function that retrieves the dissector from the singleton, calls dissect and returns it.
-extern "C" fn dissect_protocol_function(
+/// Run the dissection, a panic must not unwind into wireshark so it's caught here and returns fallback instead. Panics
+/// and exceptions thrown during the dissection are then reported in the tree, like wireshark does for C dissectors.
+fn contain_dissection<R>(
+    index: usize,
     tvb: *mut epan::tvbuff::tvbuff_t,
-    _packet_info: *mut epan::packet_info::packet_info,
+    packet_info: *mut epan::packet_info::packet_info,
+    tree: *mut epan::proto::proto_tree,
+    fallback: R,
+    dissect: impl FnOnce() -> R,
+) -> R {
+    // Our dissector may be called from another one of ours, keep the exception of that one aside.
+    let outer = epan::exceptions::take_pending();
+    let outer_index = CURRENT.with(|c| c.replace(Some(index)));
+
+    let result = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(dissect)) {
+        Ok(v) => v,
+        Err(payload) => {
+            epan::exceptions::set_pending(epan::Exception::DissectorError(panic_message(payload.as_ref())));
+            fallback
+        }
+    };
+
+    if let Some(exception) = epan::exceptions::take_pending() {
+        unsafe {
+            epan::exceptions::report(tvb, packet_info, tree, &exception);
+        }
+    }
+    if let Some(exception) = outer {
+        epan::exceptions::set_pending(exception);
+    }
+    CURRENT.with(|c| c.set(outer_index));
+    return result;
+}
+
+/// Run one of the callbacks that happen outside of the dissection, like the init and cleanup routines. Like the
+/// dissection, a panic must not unwind into wireshark, there's no tree to report it in so it's logged as a warning.
+fn contain_callback(index: usize, callback: impl FnOnce()) {
+    let outer_index = CURRENT.with(|c| c.replace(Some(index)));
+    if let Err(payload) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(callback)) {
+        let (_, short_name, _) = entry(index).borrow().dissector.get_protocol_name();
+        let message = util::c_string(&format!("{}: {}", short_name, panic_message(payload.as_ref())));
+        unsafe {
+            epan::glib::g_log(
+                std::ptr::null(),
+                epan::glib::G_LOG_LEVEL_WARNING,
+                b"%s\0".as_ptr() as *const libc::c_char,
+                message.as_ptr(),
+            );
+        }
+    }
+    CURRENT.with(|c| c.set(outer_index));
+}
+
+/// Dissection function for the dissector at INDEX, retrieves the dissector from the global state and calls dissect.
+extern "C" fn dissect_protocol_function<const INDEX: usize>(
+    tvb: *mut epan::tvbuff::tvbuff_t,
+    packet_info: *mut epan::packet_info::packet_info,
     tree: *mut epan::proto::proto_tree,
     _data: *mut libc::c_void,
 ) -> i32 {
     // Create our nice safe wrappers
     let mut proto: epan::ProtoTree = unsafe { epan::ProtoTree::from_ptr(tree) };
-    let mut tvb: epan::TVB = unsafe { epan::TVB::from_ptr(tvb) };
-
-    // A temporary to hold the,  we retrieve from a mutable static, so it's unsafe.
-    let dissector_tmp = unsafe { &DISSECTOR_PTR.as_ref().unwrap() };
+    let mut tvb_wrapper: epan::TVB = unsafe { epan::TVB::from_ptr(tvb) };
+    let mut pinfo: epan::PacketInfo = unsafe { epan::PacketInfo::from_ptr(packet_info) };
 
-    // Call the dissector.
-    let used_bytes = dissector_tmp.dissect(&mut proto, &mut tvb);
+    // Call the dissector, if it panics the packet is consumed as a whole.
+    let used_bytes = contain_dissection(INDEX, tvb, packet_info, tree, tvb_wrapper.reported_length(), || {
+        entry(INDEX)
+            .borrow()
+            .dissector
+            .dissect(&mut proto, &mut tvb_wrapper, &mut pinfo)
+    });
 
     // Return how much bytes we consumed.
-    return used_bytes as i32;
+    used_bytes as i32
 }
 
-/// Global heuristic dissector function.
-extern "C" fn heuristic_dissector_function(
+/// Heuristic dissector function for the dissector at INDEX.
+extern "C" fn heuristic_dissector_function<const INDEX: usize>(
     tvb: *mut epan::tvbuff::tvbuff_t,
-    _packet_info: *mut epan::packet_info::packet_info,
+    packet_info: *mut epan::packet_info::packet_info,
     tree: *mut epan::proto::proto_tree,
     _data: *mut libc::c_void,
 ) -> bool {
-    // A temporary to hold the,  we retrieve from a mutable static, so it's unsafe.
-    let dissector_tmp = unsafe { &DISSECTOR_PTR.as_ref().unwrap() };
-
-    // Make our objects and invoke the heuristic dissector method.
+    // Make our objects and invoke the heuristic dissector method, a panic claims the packet to report it.
     let mut proto: epan::ProtoTree = unsafe { epan::ProtoTree::from_ptr(tree) };
-    let mut tvb: epan::TVB = unsafe { epan::TVB::from_ptr(tvb) };
+    let mut tvb_wrapper: epan::TVB = unsafe { epan::TVB::from_ptr(tvb) };
+    let mut pinfo: epan::PacketInfo = unsafe { epan::PacketInfo::from_ptr(packet_info) };
+
+    contain_dissection(INDEX, tvb, packet_info, tree, true, || {
+        entry(INDEX)
+            .borrow()
+            .dissector
+            .heuristic_dissect(&mut proto, &mut tvb_wrapper, &mut pinfo)
+    })
+}
 
-    let applies = dissector_tmp.heuristic_dissect(&mut proto, &mut tvb);
+/// Preferences callback for the dissector at INDEX, wireshark calls this after the preferences changed.
+extern "C" fn apply_preferences_function<const INDEX: usize>() {
+    contain_callback(INDEX, || {
+        let entry = entry(INDEX).borrow();
+        if let Some(preferences) = &entry.preferences {
+            entry.dissector.preferences_changed(preferences);
+        }
+    });
+}
+
+/// Init routine for the dissector at INDEX, called before a capture file is dissected.
+extern "C" fn init_function<const INDEX: usize>() {
+    contain_callback(INDEX, || entry(INDEX).borrow().dissector.init());
+}
 
-    return applies;
+/// Cleanup routine for the dissector at INDEX, called when a capture file is closed.
+extern "C" fn cleanup_function<const INDEX: usize>() {
+    contain_callback(INDEX, || entry(INDEX).borrow().dissector.cleanup());
 }
 
-/// Global function to register our protocol.
+/// Global function to register our protocols.
 extern "C" fn proto_register_protoinfo() {
-    // We're only called once, ensure we have our HF entries setup.
-    unsafe {
-        HF_ENTRIES = Some(Vec::new());
+    for index in 0..entry_count() {
+        register_protoinfo(index);
     }
+}
 
-    let dissector_tmp = unsafe { Rc::<dyn Dissector + 'static>::get_mut(DISSECTOR_PTR.as_mut().unwrap()).unwrap() };
+/// Register the protocol, fields, expert info, tables, preferences and subtrees of the dissector at this index.
+fn register_protoinfo(index: usize) {
+    let mut entry = entry(index).borrow_mut();
+    let entry = &mut *entry;
+    let dissector_tmp = entry.dissector.as_mut();
 
     // Make a vector to hold the HFIndex entries.
     let mut field_ids: Vec<epan::proto::HFIndex> = Vec::new();
@@ -176,27 +393,26 @@ extern "C" fn proto_register_protoinfo() {
     unsafe {
         // Register our protocol names and abbreviation.
         let (full_name, short_name, filter_name) = dissector_tmp.get_protocol_name();
-        PROTO_ID = epan::proto::proto_register_protocol(
+        entry.proto_id = epan::proto::proto_register_protocol(
             util::perm_string_ptr(full_name),
             util::perm_string_ptr(short_name),
             util::perm_string_ptr(filter_name),
         );
 
         // ok, here we get to make our header fields array, and then we can pass that to wireshark.
-        let hf_fields = &mut HF_ENTRIES.as_mut().unwrap();
+        let hf_fields = &mut entry.hf_entries;
 
         // Now, build the struct we're going to pass to wireshark.
         field_ids.resize(fields_input.len(), epan::proto::HFIndex(-1));
         for i in 0..fields_input.len() {
             hf_fields.push(epan::proto::hf_register_info {
-                p_id: &mut field_ids[i],
+                p_id: (&mut field_ids[i] as *mut epan::proto::HFIndex).cast(),
                 hfinfo: (&fields_input[i]).into(),
             });
         }
 
-        // pass our struct to wireshark.
-        let rawptr = &mut hf_fields[0] as *mut epan::proto::hf_register_info;
-        epan::proto::proto_register_field_array(PROTO_ID, rawptr, hf_fields.len() as i32);
+        // pass our struct to wireshark, a dissector without fields passes an empty array.
+        epan::proto::proto_register_field_array(entry.proto_id, hf_fields.as_mut_ptr(), hf_fields.len() as i32);
     }
 
     // And, then we assemble the return struct.
@@ -209,6 +425,77 @@ extern "C" fn proto_register_protoinfo() {
     // Pass the now usable indices back to the dissector.
     dissector_tmp.set_field_indices(hf_indices);
 
+    // Register the expert info, this also registers a header field for each entry to make them filterable.
+    let mut experts_input = dissector_tmp.get_expert_fields();
+    if !experts_input.is_empty() {
+        let mut ei_indices: Vec<epan::expert::EIIndex> = Vec::new();
+        ei_indices.resize(experts_input.len(), Default::default());
+        let ei_entries = &mut entry.ei_entries;
+        for i in 0..experts_input.len() {
+            ei_entries.push(epan::expert::ei_register_info {
+                ids: &mut ei_indices[i],
+                eiinfo: (&experts_input[i]).into(),
+            });
+        }
+        unsafe {
+            let module = epan::expert::expert_register_protocol(entry.proto_id);
+            epan::expert::expert_register_field_array(module, ei_entries.as_mut_ptr(), ei_entries.len() as i32);
+        }
+
+        let ei_indices = experts_input
+            .drain(..)
+            .zip(ei_indices.iter().copied())
+            .collect::<Vec<(dissector::ExpertField, epan::expert::EIIndex)>>();
+        dissector_tmp.set_expert_indices(ei_indices);
+    }
+
+    // Register the reassembly tables, each brings its own fields and subtrees to show the fragments.
+    let tables = dissector_tmp.get_reassembly_tables();
+    if !tables.is_empty() {
+        let proto_id = entry.proto_id;
+        let reassemblies = &mut entry.reassemblies;
+        let tables = tables
+            .into_iter()
+            .map(|t| {
+                let (table, registered) = unsafe { epan::ReassemblyTable::register(proto_id, &t) };
+                reassemblies.push(registered);
+                (t, table)
+            })
+            .collect();
+        dissector_tmp.set_reassembly_tables(tables);
+    }
+
+    // Register the dissector tables this protocol owns, other dissectors add themselves to these during the handoff.
+    let tables = dissector_tmp.get_dissector_tables();
+    if !tables.is_empty() {
+        let proto_id = entry.proto_id;
+        let tables = tables
+            .into_iter()
+            .map(|t| {
+                let table = unsafe { epan::DissectorTable::register(proto_id, &t) };
+                (t, table)
+            })
+            .collect();
+        dissector_tmp.set_dissector_tables(tables);
+    }
+
+    // Register the preferences, wireshark calls the apply function for this dissector when they change.
+    let preferences = dissector_tmp.get_preferences();
+    if !preferences.is_empty() {
+        unsafe {
+            let module = epan::prefs::prefs_register_protocol(entry.proto_id, Some(APPLY_FUNCTIONS[index]));
+            let handle = epan::Preferences::register(module, &preferences);
+            entry.preferences = Some(handle.clone());
+            dissector_tmp.set_preferences(handle);
+        }
+    }
+
+    // The init and cleanup routines let the dissector reset its state for each capture file.
+    unsafe {
+        epan::packet::register_init_routine(Some(INIT_FUNCTIONS[index]));
+        epan::packet::register_cleanup_routine(Some(CLEANUP_FUNCTIONS[index]));
+    }
+
     // And, then lastly, we create the tree indices.
     let desired_count = dissector_tmp.get_tree_count();
     if desired_count != 0 {
@@ -220,31 +507,35 @@ extern "C" fn proto_register_protoinfo() {
         }
         unsafe {
             // now, we can pass this vector to register the ETTIndices we want.
-            epan::proto::proto_register_subtree_array(
-                &mut ett_index_vector[0] as *mut *mut epan::proto::ETTIndex,
-                desired_count as i32,
-            );
+            epan::proto::proto_register_subtree_array(ett_index_vector.as_mut_ptr(), desired_count as i32);
         }
 
         dissector_tmp.set_tree_indices(ett_indices);
     }
 }
 
-/// Global handoff function to register the dissector.
+/// Global handoff function to register the dissectors.
 extern "C" fn proto_register_handoff() {
+    for index in 0..entry_count() {
+        register_handoff(index);
+    }
+}
+
+/// Create the dissector handle for the dissector at this index and register it where it desires to be registered.
+fn register_handoff(index: usize) {
     // A handoff routine associates a protocol handler with the protocol’s traffic. It consists of two major steps:
     // The first step is to create a dissector handle, which is a handle associated with the protocol and the function called to do the actual dissecting.
     // The second step is to register the dissector handle so that traffic associated with the protocol calls the dissector.
+    let mut entry = entry(index).borrow_mut();
 
     unsafe {
-        let dissector_tmp = &DISSECTOR_PTR.as_ref().unwrap();
-
-        let dissector_handle = epan::packet::create_dissector_handle(Some(dissect_protocol_function), PROTO_ID);
+        entry.handle = epan::packet::create_dissector_handle(Some(DISSECT_FUNCTIONS[index]), entry.proto_id);
+        let dissector_handle = entry.handle;
 
-        for registration in dissector_tmp.get_registration() {
+        for registration in entry.dissector.get_registration() {
             match registration {
                 // Register as a post dissector
-                dissector::Registration::Post {} => {
+                dissector::Registration::Post => {
                     epan::packet::register_postdissector(dissector_handle);
                 }
                 // Register in a specific table with an integer.
@@ -273,6 +564,21 @@ extern "C" fn proto_register_handoff() {
                     epan::packet::dissector_add_for_decode_as(util::perm_string_ptr(abbrev), dissector_handle);
                 }
 
+                // Register in a specific table with a string, wireshark keeps a copy of the pattern.
+                dissector::Registration::String { table, pattern } => {
+                    let pattern = util::c_string(pattern);
+                    epan::packet::dissector_add_string(
+                        util::perm_string_ptr(table),
+                        pattern.as_ptr(),
+                        dissector_handle,
+                    );
+                }
+
+                // Register for decode as functionality in a string table, this is the same call as for integers.
+                dissector::Registration::StringDecodeAs { abbrev } => {
+                    epan::packet::dissector_add_for_decode_as(util::perm_string_ptr(abbrev), dissector_handle);
+                }
+
                 // Register as a heuristic dissector.
                 dissector::Registration::Heuristic {
                     table,
@@ -282,10 +588,10 @@ extern "C" fn proto_register_handoff() {
                 } => {
                     epan::packet::heur_dissector_add(
                         util::perm_string_ptr(table),
-                        Some(heuristic_dissector_function),
+                        Some(HEURISTIC_FUNCTIONS[index]),
                         util::perm_string_ptr(display_name),
                         util::perm_string_ptr(internal_name),
-                        PROTO_ID,
+                        entry.proto_id,
                         if enabled {
                             epan::packet::heuristic_enable_e::HEURISTIC_ENABLE
                         } else {
@@ -297,3 +603,142 @@ extern "C" fn proto_register_handoff() {
         }
     }
 }
+
+/// Creates all the symbols wireshark needs to find in a plugin, and registers the provided dissectors.
+///
+/// This emits `plugin_register`, which calls [`dissector::setup()`] with the provided dissector expressions, as well as
+/// the `plugin_version`, `plugin_release`, `plugin_want_major` and `plugin_want_minor` symbols wireshark uses to
+/// decide whether it can load the plugin. For wireshark 4.2 and up the `plugin_describe` function is emitted as well.
+///
+/// The optional `wireshark` argument is the major and minor version of wireshark the plugin is built for, it defaults to
+/// the version detected at build time, see the [`crate::version`] module. The optional `version` is the version of the
+/// plugin itself, it defaults to the version of the crate invoking the macro.
+///
+/// ```ignore
+/// wireshark_plugin!(dissectors: [MyDissector::new(), MyOtherDissector::new()]);
+/// wireshark_plugin!(wireshark: (3, 6), dissectors: [MyDissector::new()]);
+/// ```
+#[macro_export]
+macro_rules! wireshark_plugin {
+    (
+        dissectors: [$($dissector:expr),* $(,)?] $(,)?
+    ) => {
+        $crate::wireshark_plugin!(version: env!("CARGO_PKG_VERSION"), dissectors: [$($dissector),*]);
+    };
+    (
+        version: $version:expr,
+        dissectors: [$($dissector:expr),* $(,)?] $(,)?
+    ) => {
+        $crate::wireshark_plugin!(
+            @symbols
+            version: $version,
+            release: $crate::version::WIRESHARK_RELEASE,
+            major: $crate::version::WIRESHARK_VERSION_MAJOR,
+            minor: $crate::version::WIRESHARK_VERSION_MINOR,
+            dissectors: [$($dissector),*]
+        );
+        $crate::__plugin_describe!();
+    };
+    (
+        wireshark: ($major:tt, $minor:tt),
+        dissectors: [$($dissector:expr),* $(,)?] $(,)?
+    ) => {
+        $crate::wireshark_plugin!(
+            version: env!("CARGO_PKG_VERSION"),
+            wireshark: ($major, $minor),
+            dissectors: [$($dissector),*]
+        );
+    };
+    (
+        version: $version:expr,
+        wireshark: ($major:tt, $minor:tt),
+        dissectors: [$($dissector:expr),* $(,)?] $(,)?
+    ) => {
+        $crate::wireshark_plugin!(
+            @symbols
+            version: $version,
+            release: concat!($major, ".", $minor),
+            major: $major,
+            minor: $minor,
+            dissectors: [$($dissector),*]
+        );
+        $crate::wireshark_plugin!(@describe $major, $minor);
+    };
+
+    (
+        @symbols
+        version: $version:expr,
+        release: $release:expr,
+        major: $major:expr,
+        minor: $minor:expr,
+        dissectors: [$($dissector:expr),*]
+    ) => {
+        /// This function is the main entry point for the plugin. It's the only symbol called automatically.
+        #[no_mangle]
+        pub extern "C" fn plugin_register() {
+            let dissectors: Vec<::std::boxed::Box<dyn $crate::dissector::Dissector>> =
+                vec![$(::std::boxed::Box::new($dissector)),*];
+            $crate::dissector::setup(dissectors);
+        }
+
+        // And we need these public symbols to tell wireshark we are a plugin that's made for the right version.
+        #[no_mangle]
+        #[allow(non_upper_case_globals)]
+        pub static plugin_version: [::std::os::raw::c_char; $version.len() + 1] = $crate::c_char_array($version);
+        #[no_mangle]
+        #[allow(non_upper_case_globals)]
+        pub static plugin_release: [::std::os::raw::c_char; $release.len() + 1] = $crate::c_char_array($release);
+
+        // Later versions of wireshark also want these integers.
+        #[no_mangle]
+        #[allow(non_upper_case_globals)]
+        pub static plugin_want_major: ::std::os::raw::c_int = $major as ::std::os::raw::c_int;
+        #[no_mangle]
+        #[allow(non_upper_case_globals)]
+        pub static plugin_want_minor: ::std::os::raw::c_int = $minor as ::std::os::raw::c_int;
+    };
+
+    // Wireshark 4.2 introduced plugin_describe, older versions don't look for it.
+    (@describe 1, $minor:tt) => {};
+    (@describe 2, $minor:tt) => {};
+    (@describe 3, $minor:tt) => {};
+    (@describe 4, 0) => {};
+    (@describe 4, 1) => {};
+    (@describe $major:tt, $minor:tt) => {
+        $crate::__plugin_describe!(@emit);
+    };
+}
+
+/// Emits `plugin_describe` if the detected wireshark version needs it, used by [`wireshark_plugin!`] when the version
+/// isn't provided. The cfg is evaluated for this crate, so this can't be done inside that macro.
+#[cfg(ws_4_2)]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __plugin_describe {
+    () => {
+        $crate::__plugin_describe!(@emit);
+    };
+    (@emit) => {
+        /// Tells wireshark what kind of plugin this is.
+        #[no_mangle]
+        pub extern "C" fn plugin_describe() -> u32 {
+            // WS_PLUGIN_DESC_DISSECTOR
+            1 << 0
+        }
+    };
+}
+
+#[cfg(not(ws_4_2))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __plugin_describe {
+    () => {};
+    (@emit) => {
+        /// Tells wireshark what kind of plugin this is.
+        #[no_mangle]
+        pub extern "C" fn plugin_describe() -> u32 {
+            // WS_PLUGIN_DESC_DISSECTOR
+            1 << 0
+        }
+    };
+}
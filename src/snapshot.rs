@@ -0,0 +1,114 @@
+// Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+/*
+Snapshot testing on top of the mock backend. A packet is dissected, the resulting tree is rendered in the indented
+format tshark -V uses and compared against a checked in .snap file. Setting the UPDATE_SNAPSHOTS environment variable
+writes the rendered output to the file instead, after which the change can be reviewed in version control.
+*/
+
+use crate::dissector::Dissector;
+use crate::mock;
+use std::path::Path;
+
+/// The environment variable that enables the update mode, if set the snapshot files are (re)written.
+pub const UPDATE_VARIABLE: &str = "UPDATE_SNAPSHOTS";
+
+/// Convert a hexadecimal string into bytes, whitespace and ':' or '-' separators are ignored.
+pub fn parse_hex(hex: &str) -> Vec<u8> {
+    let digits: Vec<u8> = hex
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ':' && *c != '-')
+        .map(|c| match c.to_digit(16) {
+            Some(v) => v as u8,
+            None => panic!("Invalid hexadecimal character {:?} in {:?}.", c, hex),
+        })
+        .collect();
+    if !digits.len().is_multiple_of(2) {
+        panic!("Hexadecimal string {:?} has an odd number of digits.", hex);
+    }
+    digits.chunks(2).map(|pair| (pair[0] << 4) | pair[1]).collect()
+}
+
+/// Render the dissection in a stable text format, similar to `tshark -V`. Each item is on its own line, children are
+/// indented by four spaces, followed by the expert info attached to the item. Columns that were written to are listed
+/// before the tree, the data sources that were added after it. An exception that stopped the dissection is shown on
+/// the last line.
+pub fn render(dissection: &mock::Dissection) -> String {
+    fn recurse(items: &[mock::Item], depth: usize, output: &mut String) {
+        for item in items {
+            output.push_str(&"    ".repeat(depth));
+            output.push_str(&item.label());
+            output.push('\n');
+            for expert in item.experts.iter() {
+                output.push_str(&"    ".repeat(depth + 1));
+                output.push_str(&format!(
+                    "[Expert Info ({:?}/{:?}): {}]\n",
+                    expert.severity, expert.group, expert.text
+                ));
+            }
+            recurse(&item.children, depth + 1, output);
+        }
+    }
+    let mut output = format!("Consumed {} bytes\n", dissection.consumed);
+    for (column, text) in dissection.columns.iter() {
+        output.push_str(&format!("[{:?} column: {}]\n", column, text));
+    }
+    recurse(&dissection.items, 0, &mut output);
+    for (name, data) in dissection.data_sources.iter() {
+        output.push_str(&format!("[Data source {}: {} bytes]\n", name, data.len()));
+    }
+    if let Some(exception) = &dissection.exception {
+        output.push_str(&format!("[Exception: {}]\n", exception));
+    }
+    output
+}
+
+/// Compare the rendered text against the contents of the snapshot file, panics with both texts if they differ or if
+/// the file doesn't exist. If [`UPDATE_VARIABLE`] is set in the environment the file is written instead. Relative paths
+/// are relative to the working directory, which is the package root when running `cargo test`.
+pub fn assert_snapshot<P: AsRef<Path>>(path: P, rendered: &str) {
+    let path = path.as_ref();
+    if std::env::var_os(UPDATE_VARIABLE).is_some() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("Failed to create the snapshot directory.");
+        }
+        std::fs::write(path, rendered).expect("Failed to write the snapshot file.");
+        return;
+    }
+
+    let expected = match std::fs::read_to_string(path) {
+        Ok(v) => v,
+        Err(e) => panic!(
+            "Failed to read snapshot {:?}: {}, run with {}=1 to create it.\nRendered:\n{}",
+            path, e, UPDATE_VARIABLE, rendered
+        ),
+    };
+    if expected != rendered {
+        panic!(
+            "Snapshot {:?} doesn't match, run with {}=1 to update it.\nExpected:\n{}\nRendered:\n{}",
+            path, UPDATE_VARIABLE, expected, rendered
+        );
+    }
+}
+
+/// Dissect the raw bytes of a packet using the harness and compare the rendered tree against the snapshot file.
+pub fn assert_dissection<D: Dissector + ?Sized, P: AsRef<Path>>(
+    harness: &mock::Harness,
+    dissector: &D,
+    packet: &[u8],
+    path: P,
+) {
+    let dissection = harness.dissect(dissector, packet);
+    assert_snapshot(path, &render(&dissection));
+}
+
+/// Dissect a packet provided as hexadecimal string and compare the rendered tree against the snapshot file.
+pub fn assert_dissection_hex<D: Dissector + ?Sized, P: AsRef<Path>>(
+    harness: &mock::Harness,
+    dissector: &D,
+    packet: &str,
+    path: P,
+) {
+    assert_dissection(harness, dissector, &parse_hex(packet), path);
+}
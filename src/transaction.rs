@@ -0,0 +1,373 @@
+// Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+// Request / response matching, like the transaction tracking many of the C dissectors do by hand with a wmem map in
+// their conversation data.
+
+use crate::dissector::{BasicHeaderFieldInfo, ExpertField, ExpertGroup, ExpertSeverity, FieldDisplay, FieldType};
+use crate::dissector::{HeaderFieldInfo, StringContainer};
+use crate::epan;
+use crate::epan::nstime::nstime_t;
+use crate::epan::{PacketInfo, ProtoTree, TVB};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// A request and the response that belongs to it, as far as they have been seen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Transaction {
+    /// The frame number of the request.
+    pub request_frame: u32,
+    /// The absolute time of the request.
+    pub request_time: nstime_t,
+    /// The frame number of the response, None if no response was seen.
+    pub response_frame: Option<u32>,
+    /// The absolute time of the response, None if no response was seen.
+    pub response_time: Option<nstime_t>,
+}
+
+impl Transaction {
+    /// The time between the request and the response, None if no response was seen.
+    pub fn response_delay(self: &Self) -> Option<nstime_t> {
+        return Some(self.response_time? - self.request_time);
+    }
+}
+
+type SharedTransaction = Rc<RefCell<Transaction>>;
+
+/// The requests in a conversation that wait for their response, only used on the first pass.
+#[derive(Default)]
+struct PendingRequests(HashMap<u64, SharedTransaction>);
+
+/// The transactions a packet is part of, it's cloned, but shares the transactions themselves.
+#[derive(Default, Clone)]
+struct PacketTransactions(HashMap<u64, SharedTransaction>);
+
+/// Matches requests with their responses by a transaction id the dissector takes from the packet, for protocols where
+/// the response carries the id of the request. The state is kept in the [`epan::Conversation`] of the packets and
+/// in their per packet data, so only responses in the same conversation are matched. Only one tracker should be used
+/// per dissector.
+///
+/// The tracker adds its own fields and expert info, the dissector must include them in the registration:
+/// ```ignore
+/// fn get_fields(self: &Self) -> Vec<Box<dyn HeaderFieldInfo>> {
+///     let mut fields = ...;
+///     fields.extend(self.tracker.get_fields());
+///     return fields;
+/// }
+/// fn set_field_indices(self: &mut Self, hf_indices: Vec<(Box<dyn HeaderFieldInfo>, epan::proto::HFIndex)>) {
+///     self.tracker.set_field_indices(&hf_indices);
+///     ...
+/// }
+/// ```
+/// And the same for [`TransactionTracker::get_expert_fields()`] and [`TransactionTracker::set_expert_indices()`].
+pub struct TransactionTracker {
+    request_in: BasicHeaderFieldInfo,
+    response_in: BasicHeaderFieldInfo,
+    time: BasicHeaderFieldInfo,
+    no_response: ExpertField,
+    hf_request_in: epan::proto::HFIndex,
+    hf_response_in: epan::proto::HFIndex,
+    hf_time: epan::proto::HFIndex,
+    ei_no_response: epan::expert::EIIndex,
+}
+
+impl TransactionTracker {
+    /// Create a tracker, the abbreviations of its fields and expert info start with the prefix, usually the filter
+    /// name of the protocol.
+    pub fn new(prefix: &str) -> TransactionTracker {
+        let field = |name: &str, abbrev: &str, field_type: FieldType, blurb: &str| BasicHeaderFieldInfo {
+            name: StringContainer::String(name.to_owned()),
+            abbrev: StringContainer::String(format!("{}.{}", prefix, abbrev)),
+            field_type,
+            display: FieldDisplay::BASE_NONE,
+            blurb: Some(blurb.to_owned()),
+            ..Default::default()
+        };
+        return TransactionTracker {
+            request_in: field(
+                "Request In",
+                "request_in",
+                FieldType::FRAMENUM,
+                "The request to this response is in this frame",
+            ),
+            response_in: field(
+                "Response In",
+                "response_in",
+                FieldType::FRAMENUM,
+                "The response to this request is in this frame",
+            ),
+            time: field(
+                "Time",
+                "time",
+                FieldType::RELATIVE_TIME,
+                "The time between the request and the response",
+            ),
+            no_response: ExpertField {
+                abbrev: StringContainer::String(format!("{}.no_response", prefix)),
+                group: ExpertGroup::SEQUENCE,
+                severity: ExpertSeverity::WARN,
+                summary: StringContainer::StaticStr("No response seen to this request"),
+            },
+            hf_request_in: epan::proto::HFIndex(-1),
+            hf_response_in: epan::proto::HFIndex(-1),
+            hf_time: epan::proto::HFIndex(-1),
+            ei_no_response: Default::default(),
+        };
+    }
+
+    /// The fields the tracker adds, these must be registered with those of the dissector.
+    pub fn get_fields(self: &Self) -> Vec<Box<dyn HeaderFieldInfo>> {
+        return vec![
+            self.request_in.as_boxed(),
+            self.response_in.as_boxed(),
+            self.time.as_boxed(),
+        ];
+    }
+
+    /// The expert info the tracker adds, these must be registered with those of the dissector.
+    pub fn get_expert_fields(self: &Self) -> Vec<ExpertField> {
+        return vec![self.no_response.clone()];
+    }
+
+    /// Pick the indices of the tracker's fields from those the dissector received, the others are ignored.
+    pub fn set_field_indices(self: &mut Self, hf_indices: &[(Box<dyn HeaderFieldInfo>, epan::proto::HFIndex)]) {
+        for (field, index) in hf_indices {
+            let abbrev = field.abbrev();
+            if self.request_in.abbrev == abbrev.as_str() {
+                self.hf_request_in = *index;
+            } else if self.response_in.abbrev == abbrev.as_str() {
+                self.hf_response_in = *index;
+            } else if self.time.abbrev == abbrev.as_str() {
+                self.hf_time = *index;
+            }
+        }
+    }
+
+    /// Pick the index of the tracker's expert info from those the dissector received, the others are ignored.
+    pub fn set_expert_indices(self: &mut Self, ei_indices: &[(ExpertField, epan::expert::EIIndex)]) {
+        for (expert, index) in ei_indices {
+            if *expert == self.no_response {
+                self.ei_no_response = *index;
+            }
+        }
+    }
+
+    /// The transaction with this id that this packet is part of, created by the callback on the first pass.
+    fn lookup(
+        pinfo: &mut PacketInfo,
+        id: u64,
+        first_pass: impl FnOnce(&mut PacketInfo) -> Option<SharedTransaction>,
+    ) -> Option<SharedTransaction> {
+        let mut transactions = pinfo.get_proto_data::<PacketTransactions>(0).ok()?.unwrap_or_default();
+        if let Some(transaction) = transactions.0.get(&id) {
+            return Some(transaction.clone());
+        }
+        if pinfo.visited() {
+            return None;
+        }
+        let transaction = first_pass(pinfo)?;
+        transactions.0.insert(id, transaction.clone());
+        pinfo.set_proto_data(0, transactions).ok()?;
+        return Some(transaction);
+    }
+
+    /// Record that this packet holds the request with this id and add the generated items that link to its response.
+    /// If no response was seen the request is flagged with an expert warning, responses are only known after the
+    /// first pass.
+    pub fn request(
+        self: &Self,
+        tree: &mut ProtoTree,
+        pinfo: &mut PacketInfo,
+        tvb: &TVB,
+        id: u64,
+    ) -> Option<Transaction> {
+        let transaction = TransactionTracker::lookup(pinfo, id, |pinfo| {
+            let transaction = Rc::new(RefCell::new(Transaction {
+                request_frame: pinfo.number(),
+                request_time: pinfo.abs_ts(),
+                response_frame: None,
+                response_time: None,
+            }));
+            // A retransmitted request replaces the earlier one, the response is matched with the latest.
+            let mut conversation = epan::Conversation::find_or_create(pinfo);
+            if conversation
+                .with_data(|p: &mut PendingRequests| p.0.insert(id, transaction.clone()))
+                .ok()?
+                .is_none()
+            {
+                let mut pending = PendingRequests::default();
+                pending.0.insert(id, transaction.clone());
+                conversation.set_data(pending).ok()?;
+            }
+            return Some(transaction);
+        })?;
+        let transaction = *transaction.borrow();
+
+        if transaction.request_frame != pinfo.number() {
+            return Some(transaction);
+        }
+        match transaction.response_frame {
+            Some(frame) => tree.add_uint(self.hf_response_in, tvb, 0, 0, frame).set_generated(),
+            None => {
+                tree.add_expert(pinfo, self.ei_no_response, tvb, 0, 0);
+            }
+        }
+        return Some(transaction);
+    }
+
+    /// Record that this packet holds the response to the request with this id and add the generated items that link
+    /// to the request. Returns None if no request with this id was seen before the response.
+    pub fn response(
+        self: &Self,
+        tree: &mut ProtoTree,
+        pinfo: &mut PacketInfo,
+        tvb: &TVB,
+        id: u64,
+    ) -> Option<Transaction> {
+        let transaction = TransactionTracker::lookup(pinfo, id, |pinfo| {
+            let conversation = epan::Conversation::find_or_create(pinfo);
+            let transaction = conversation
+                .with_data(|p: &mut PendingRequests| p.0.remove(&id))
+                .ok()???;
+            let mut t = transaction.borrow_mut();
+            t.response_frame = Some(pinfo.number());
+            t.response_time = Some(pinfo.abs_ts());
+            drop(t);
+            return Some(transaction);
+        })?;
+        let transaction = *transaction.borrow();
+
+        if transaction.response_frame != Some(pinfo.number()) {
+            return Some(transaction);
+        }
+        tree.add_uint(self.hf_request_in, tvb, 0, 0, transaction.request_frame)
+            .set_generated();
+        if let Some(delay) = transaction.response_delay() {
+            tree.add_time(self.hf_time, tvb, 0, 0, &delay).set_generated();
+        }
+        return Some(transaction);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dissector::Dissector;
+    use crate::epan::expert::EIIndex;
+    use crate::epan::proto::HFIndex;
+    use crate::mock::{Dissection, Harness, Packet, Value};
+
+    /// Packets of a byte that is either `Q` for a request or `R` for a response, followed by the transaction id.
+    struct RequestResponse {
+        tracker: TransactionTracker,
+    }
+
+    impl Dissector for RequestResponse {
+        fn get_fields(&self) -> Vec<Box<dyn HeaderFieldInfo>> {
+            self.tracker.get_fields()
+        }
+        fn set_field_indices(&mut self, hf_indices: Vec<(Box<dyn HeaderFieldInfo>, HFIndex)>) {
+            self.tracker.set_field_indices(&hf_indices);
+        }
+        fn get_expert_fields(&self) -> Vec<ExpertField> {
+            self.tracker.get_expert_fields()
+        }
+        fn set_expert_indices(&mut self, ei_indices: Vec<(ExpertField, EIIndex)>) {
+            self.tracker.set_expert_indices(&ei_indices);
+        }
+        fn get_protocol_name(&self) -> (&'static str, &'static str, &'static str) {
+            ("Test Protocol", "Test", "test")
+        }
+        fn dissect(&self, proto: &mut ProtoTree, tvb: &mut TVB, pinfo: &mut PacketInfo) -> usize {
+            let id = tvb.get_u8(1).unwrap() as u64;
+            match tvb.get_u8(0) {
+                Ok(b'Q') => self.tracker.request(proto, pinfo, tvb, id),
+                _ => self.tracker.response(proto, pinfo, tvb, id),
+            };
+            tvb.reported_length()
+        }
+    }
+
+    fn time(secs: i64, nsecs: i32) -> nstime_t {
+        nstime_t { secs, nsecs }
+    }
+
+    /// Dissect the packets in order on the first pass, then again once they are all visited.
+    fn dissect_twice(packets: &[(&[u8], nstime_t)]) -> (Vec<Dissection>, Vec<Dissection>) {
+        let mut d = RequestResponse {
+            tracker: TransactionTracker::new("test"),
+        };
+        let mut harness = Harness::new(&mut d);
+        let mut passes = (Vec::new(), Vec::new());
+        for visited in [false, true] {
+            for (i, (data, abs_ts)) in packets.iter().enumerate() {
+                harness.set_packet(Packet {
+                    number: i as u32 + 1,
+                    visited,
+                    abs_ts: *abs_ts,
+                    ..Default::default()
+                });
+                let dissection = harness.dissect(&d, data);
+                match visited {
+                    false => passes.0.push(dissection),
+                    true => passes.1.push(dissection),
+                }
+            }
+        }
+        passes
+    }
+
+    #[test]
+    fn matches_response_to_request() {
+        let (_, second) = dissect_twice(&[
+            (b"Q\x01", time(10, 0)),
+            (b"Q\x02", time(11, 0)),
+            (b"R\x02", time(11, 250_000_000)),
+        ]);
+        let response_in = second[1].find("test.response_in").unwrap();
+        assert_eq!(response_in.value, Value::UInt(3));
+        assert!(response_in.generated);
+
+        let request_in = second[2].find("test.request_in").unwrap();
+        assert_eq!(request_in.value, Value::UInt(2));
+        assert!(request_in.generated);
+        let delay = second[2].find("test.time").unwrap();
+        assert_eq!(
+            delay.value,
+            Value::Time {
+                secs: 0,
+                nsecs: 250_000_000
+            }
+        );
+        assert!(delay.generated);
+    }
+
+    #[test]
+    fn flags_unanswered_request() {
+        let (first, second) = dissect_twice(&[
+            (b"Q\x01", time(10, 0)),
+            (b"Q\x02", time(11, 0)),
+            (b"R\x02", time(12, 0)),
+        ]);
+        // Responses are only known after the first pass.
+        assert_eq!(first[1].experts.len(), 1);
+        assert!(second[1].experts.is_empty());
+        assert!(second[1].find("test.response_in").is_some());
+
+        assert_eq!(second[0].experts.len(), 1);
+        assert_eq!(second[0].experts[0].abbrev, "test.no_response");
+        assert!(second[0].find("test.response_in").is_none());
+    }
+
+    #[test]
+    fn response_without_request() {
+        let (first, second) = dissect_twice(&[(b"R\x01", time(10, 0))]);
+        for dissection in [&first[0], &second[0]] {
+            assert!(dissection.find("test.request_in").is_none());
+            assert!(dissection.find("test.time").is_none());
+            assert!(dissection.experts.is_empty());
+        }
+    }
+}
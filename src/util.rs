@@ -6,9 +6,11 @@ Wireshark often expects const char* strings to exist indefinitely... So here we
 Such that if we require the same string in various places, we don't end up leaking that string over and over.
 */
 
+use std::collections::BTreeMap;
 use std::ffi::CStr;
 use std::ffi::CString;
 use std::os::raw::c_char;
+use std::sync::Mutex;
 
 static mut STRING_STORAGE: Option<Vec<*const i8>> = Some(Vec::new());
 
@@ -50,3 +52,36 @@ pub fn perm_string(input: &str) -> &CStr {
 pub fn perm_string_ptr(input: &str) -> *const c_char {
     return perm_string(input).as_ptr();
 }
+
+/// Copy the string for use as C string. Text from packets may contain null characters, which can't be represented,
+/// these are removed instead of failing.
+#[allow(dead_code)]
+pub fn c_string(input: &str) -> CString {
+    return CString::new(input.replace('\0', "")).unwrap();
+}
+
+/// Permanent C string for a static string, cached by the address and length of the string so it's only copied the
+/// first time it's used. Null characters are removed, like [`c_string`] does.
+#[allow(dead_code)]
+pub fn static_string_ptr(input: &'static str) -> *const c_char {
+    static STRINGS: Mutex<BTreeMap<(usize, usize), CString>> = Mutex::new(BTreeMap::new());
+    let mut strings = STRINGS.lock().unwrap();
+    let key = (input.as_ptr() as usize, input.len());
+    return strings.entry(key).or_insert_with(|| c_string(input)).as_ptr();
+}
+
+/// Convert a string into a null terminated `c_char` array at compile time, `N` must be the string length plus one.
+/// This is used to create the static version strings wireshark looks for in a plugin.
+pub const fn c_char_array<const N: usize>(input: &str) -> [c_char; N] {
+    let bytes = input.as_bytes();
+    if bytes.len() + 1 != N {
+        panic!("Array length must be the string length plus one for the null terminator.");
+    }
+    let mut result: [c_char; N] = [0; N];
+    let mut i = 0;
+    while i < bytes.len() {
+        result[i] = bytes[i] as c_char;
+        i += 1;
+    }
+    result
+}
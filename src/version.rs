@@ -0,0 +1,6 @@
+// Copyright 2021-2021, Ivor Wanders and the wireshark_dissector_rs contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+// Mirrors ws_version.h, the values are determined by the build script. This is the version found with pkg-config,
+// or the one from the WIRESHARK_VERSION environment variable.
+include!(concat!(env!("OUT_DIR"), "/wireshark_version.rs"));